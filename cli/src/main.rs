@@ -1,15 +1,29 @@
-use anyhow::anyhow;
+mod output;
+
+use anyhow::{anyhow, bail};
 use clap::{Parser, Subcommand};
-use escrow_client::Client;
+use escrow_client::{
+    Client, ComputeUnitLimit, ComputeUnitPrice, EscrowAddresses, ExchangeAmounts, ListFilter,
+};
+use escrow_program::event::EscrowEvent;
+use escrow_program::state::Escrow;
+use output::{
+    CliAccount, CliAccounts, CliExchange, CliInit, CliSignOnly, CliSignature, OutputFormat,
+};
 use solana_cli_config::Config;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::{EncodableKey, Keypair};
+use solana_sdk::signature::{EncodableKey, Keypair, Signature, Signer};
+use solana_sdk::signer::null_signer::NullSigner;
+use solana_sdk::signer::presigner::Presigner;
+use solana_sdk::transaction::Transaction;
 use std::env;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Cli is a struct that represents the command line arguments.
 #[derive(Parser)]
@@ -22,10 +36,70 @@ struct Cli {
     #[arg(long)]
     escrow_program_id: Option<Pubkey>,
 
-    /// Token program ID.
+    /// Token program ID of the mints, detected from the owner of each mint by default.
     #[arg(long)]
     token_program_id: Option<Pubkey>,
 
+    /// Format of the command output.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Keypair path or address of the seller or buyer, defaults to the configured keypair.
+    #[arg(short, long, global = true)]
+    keypair: Option<String>,
+
+    /// Keypair path or address of the account paying the transaction fees, defaults to the
+    /// keypair.
+    #[arg(long, global = true)]
+    fee_payer: Option<String>,
+
+    /// Signature of a signer given by address, collected with `--sign-only`.
+    #[arg(long = "signer", global = true, value_name = "PUBKEY=SIGNATURE", value_parser = parse_signer)]
+    signers: Vec<(Pubkey, Signature)>,
+
+    /// Blockhash of the transaction instead of the latest one.
+    ///
+    /// `init`, `exchange` and `cancel` then build the transaction without reading the cluster,
+    /// from the mints, token programs and amounts given on the command line.
+    #[arg(long, global = true)]
+    blockhash: Option<Hash>,
+
+    /// Durable nonce account to build the transaction against instead of a recent blockhash.
+    #[arg(long, global = true)]
+    nonce: Option<Pubkey>,
+
+    /// Keypair path or address of the nonce authority, defaults to the keypair.
+    #[arg(long, global = true, requires = "nonce")]
+    nonce_authority: Option<String>,
+
+    /// Compute unit limit of the transaction.
+    #[arg(long, global = true, value_name = "UNITS")]
+    compute_unit_limit: Option<u32>,
+
+    /// Simulate the transaction to set its compute unit limit to the consumed units plus a margin.
+    #[arg(long, global = true, conflicts_with = "compute_unit_limit")]
+    simulate_compute_unit_limit: bool,
+
+    /// Compute unit price of the transaction, which sets its priority fee.
+    #[arg(long, global = true, value_name = "MICRO_LAMPORTS")]
+    compute_unit_price: Option<u64>,
+
+    /// Set the compute unit price to the percentile of the recent prioritization fees paid for
+    /// the accounts written by the transaction.
+    #[arg(
+        long,
+        global = true,
+        value_name = "PERCENTILE",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        conflicts_with = "compute_unit_price"
+    )]
+    priority_fee_percentile: Option<u8>,
+
+    /// Sign the transaction with the available signers and print the signatures instead of
+    /// sending it.
+    #[arg(long, global = true, requires = "blockhash")]
+    sign_only: bool,
+
     /// Subcommands for the CLI.
     #[command(subcommand)]
     command: Commands,
@@ -37,20 +111,153 @@ enum Commands {
     #[clap(about = "Initialize escrow agent")]
     #[clap(arg_required_else_help = true)]
     Init {
-        #[clap(help = "Address of mint token to be sent")]
+        #[clap(help = "Address of mint token to be sent, or SOL", value_parser = parse_mint)]
         send_mint_token_address: Pubkey,
         #[clap(help = "Amount of mint token to be sent")]
         send_amount: u64,
-        #[clap(help = "Address of mint token to be received")]
+        #[clap(help = "Address of mint token to be received, or SOL", value_parser = parse_mint)]
         receive_mint_token_address: Pubkey,
         #[clap(help = "Expected amount of mint token to be received")]
         receive_expected_amount: u64,
+        #[clap(long, default_value_t = 0, help = "Seed to derive the escrow address")]
+        seed: u64,
+        #[clap(long, value_parser = humantime::parse_duration, help = "Duration after which the escrow expires (e.g. 24h)")]
+        expires_in: Option<Duration>,
+        #[clap(
+            long,
+            conflicts_with = "expires_in",
+            help = "Unix timestamp at which the escrow expires"
+        )]
+        expires_at: Option<i64>,
+        #[clap(long, help = "Only allow this buyer to take the escrow")]
+        taker: Option<Pubkey>,
+        #[clap(
+            long,
+            help = "Token program of the mint token to be sent, required with --blockhash unless --token-program-id is given"
+        )]
+        send_token_program_id: Option<Pubkey>,
+        #[clap(
+            long,
+            help = "Token program of the mint token to be received, required with --blockhash unless --token-program-id is given"
+        )]
+        receive_token_program_id: Option<Pubkey>,
     },
     #[clap(about = "Exchange tokens between parties")]
     #[clap(arg_required_else_help = true)]
     Exchange {
         #[clap(help = "Address of escrow account")]
         escrow_address: Pubkey,
+        #[clap(
+            long,
+            help = "Amount of the deposited token to take (defaults to all remaining)"
+        )]
+        amount: Option<u64>,
+        #[clap(
+            long,
+            help = "Minimum amount of the deposited token to receive (defaults to the current price)"
+        )]
+        expected_receive: Option<u64>,
+        #[clap(
+            long,
+            help = "Maximum amount of the expected token to pay (defaults to the current price)"
+        )]
+        max_pay: Option<u64>,
+        #[clap(long, help = "Seller of the escrow, required with --blockhash")]
+        seller: Option<Pubkey>,
+        #[clap(
+            long,
+            help = "Token account of the seller receiving the tokens, required with --blockhash"
+        )]
+        seller_token_account: Option<Pubkey>,
+        #[clap(long, value_parser = parse_mint, help = "Address of the deposited mint token, or SOL, required with --blockhash")]
+        send_mint: Option<Pubkey>,
+        #[clap(
+            long,
+            help = "Token program of the deposited mint token, required with --blockhash unless --token-program-id is given"
+        )]
+        send_token_program_id: Option<Pubkey>,
+        #[clap(long, value_parser = parse_mint, help = "Address of the expected mint token, or SOL, required with --blockhash")]
+        receive_mint: Option<Pubkey>,
+        #[clap(
+            long,
+            help = "Token program of the expected mint token, required with --blockhash unless --token-program-id is given"
+        )]
+        receive_token_program_id: Option<Pubkey>,
+    },
+    #[clap(about = "Cancel escrow and return tokens to the seller")]
+    #[clap(arg_required_else_help = true)]
+    Cancel {
+        #[clap(help = "Address of escrow account")]
+        escrow_address: Pubkey,
+        #[clap(long, value_parser = parse_mint, help = "Address of the deposited mint token, or SOL, required with --blockhash")]
+        send_mint: Option<Pubkey>,
+        #[clap(
+            long,
+            help = "Token program of the deposited mint token, required with --blockhash unless --token-program-id is given"
+        )]
+        send_token_program_id: Option<Pubkey>,
+    },
+    #[clap(about = "Update the terms of an escrow")]
+    #[clap(arg_required_else_help = true)]
+    Update {
+        #[clap(help = "Address of escrow account")]
+        escrow_address: Pubkey,
+        #[clap(
+            long,
+            help = "Expected amount of mint token for all remaining tokens (defaults to the current rate)"
+        )]
+        amount: Option<u64>,
+        #[clap(
+            long,
+            default_value_t = 0,
+            help = "Amount of mint token to deposit in addition"
+        )]
+        deposit: u64,
+        #[clap(
+            long,
+            default_value_t = 0,
+            help = "Amount of the remaining tokens to withdraw"
+        )]
+        withdraw: u64,
+        #[clap(long, value_parser = humantime::parse_duration, help = "Duration from now after which the escrow expires (e.g. 24h)")]
+        expires_in: Option<Duration>,
+        #[clap(
+            long,
+            conflicts_with = "expires_in",
+            help = "Remove the expiry of the escrow"
+        )]
+        no_expiry: bool,
+        #[clap(long, help = "Only allow this buyer to take the escrow")]
+        taker: Option<Pubkey>,
+        #[clap(
+            long,
+            conflicts_with = "taker",
+            help = "Allow anyone to take the escrow"
+        )]
+        no_taker: bool,
+    },
+    #[clap(about = "Refund an expired escrow and return tokens to the seller")]
+    #[clap(arg_required_else_help = true)]
+    Refund {
+        #[clap(help = "Address of escrow account")]
+        escrow_address: Pubkey,
+    },
+    #[clap(about = "Migrate escrow account to the newest layout")]
+    #[clap(arg_required_else_help = true)]
+    Migrate {
+        #[clap(help = "Address of escrow account")]
+        escrow_address: Pubkey,
+    },
+    #[clap(about = "List open escrow accounts")]
+    List {
+        #[clap(long, help = "Only list escrows of this seller")]
+        seller: Option<Pubkey>,
+        #[clap(long, help = "Only list escrows paying into this seller token account")]
+        seller_token_account: Option<Pubkey>,
+        #[clap(long, value_parser = parse_mint, help = "Only list escrows of this deposited mint token, or SOL")]
+        mint: Option<Pubkey>,
+        #[clap(long, help = "Only list escrows expecting at least this amount")]
+        min_amount: Option<u64>,
     },
     #[clap(about = "Get account details of escrow account")]
     #[clap(arg_required_else_help = true)]
@@ -60,6 +267,97 @@ enum Commands {
     },
 }
 
+/// Parse a mint address, accepting `SOL` as an alias of the native mint.
+fn parse_mint(s: &str) -> Result<Pubkey, String> {
+    if s.eq_ignore_ascii_case("SOL") {
+        return Ok(spl_token::native_mint::id());
+    }
+    Pubkey::from_str(s).map_err(|e| e.to_string())
+}
+
+/// Parse a signature given by address, as `PUBKEY=SIGNATURE`.
+fn parse_signer(s: &str) -> Result<(Pubkey, Signature), String> {
+    let (pubkey, signature) = s
+        .split_once('=')
+        .ok_or_else(|| "expected PUBKEY=SIGNATURE".to_string())?;
+    let pubkey = Pubkey::from_str(pubkey).map_err(|e| e.to_string())?;
+    let signature = Signature::from_str(signature).map_err(|e| e.to_string())?;
+    Ok((pubkey, signature))
+}
+
+/// Resolve a signer from a keypair path, or from an address.
+///
+/// A signer given by address signs with the signature given by `--signer`. Without one, it does
+/// not sign when signing only, so that the transaction can be signed by its other signers.
+fn resolve_signer(
+    source: &str,
+    signers: &[(Pubkey, Signature)],
+    sign_only: bool,
+) -> anyhow::Result<Box<dyn Signer + Send + Sync>> {
+    let Ok(pubkey) = Pubkey::from_str(source) else {
+        let keypair =
+            Keypair::read_from_file(source).map_err(|e| anyhow::Error::msg(e.to_string()))?;
+        return Ok(Box::new(keypair));
+    };
+    if let Some((_, signature)) = signers.iter().find(|(signer, _)| *signer == pubkey) {
+        return Ok(Box::new(Presigner::new(&pubkey, signature)));
+    }
+    if sign_only {
+        return Ok(Box::new(NullSigner::new(&pubkey)));
+    }
+    bail!(
+        "missing signature of {}, pass --signer {}=SIGNATURE",
+        pubkey,
+        pubkey
+    )
+}
+
+/// Get an input of a transaction built with `--blockhash`, which must be given since the
+/// transaction is built without reading the cluster.
+fn offline_arg<T>(value: Option<T>, flag: &str) -> anyhow::Result<T> {
+    value.ok_or_else(|| anyhow!("{flag} is required with --blockhash"))
+}
+
+/// Sign the transaction built with `--blockhash` with the available signers, then print the
+/// signatures for `--sign-only` or send it.
+async fn offline_output(
+    escrow: &Client,
+    mut tx: Transaction,
+    sign_only: bool,
+    output: OutputFormat,
+) -> anyhow::Result<String> {
+    escrow.sign_transaction(&mut tx)?;
+    if sign_only {
+        return output.formatted(&CliSignOnly::new(&tx));
+    }
+    let signature = escrow.send_transaction(&tx).await?;
+    output.formatted(&CliSignature::from(signature))
+}
+
+/// Get the unix timestamp after the duration from now.
+fn timestamp_after(expires_in: Duration) -> anyhow::Result<i64> {
+    let expires_at = SystemTime::now().duration_since(UNIX_EPOCH)? + expires_in;
+    Ok(i64::try_from(expires_at.as_secs())?)
+}
+
+/// Get the output of the escrow account, with the details of its tokens.
+async fn account_output(
+    escrow: &Client,
+    escrow_address: Pubkey,
+    account: &Escrow,
+) -> anyhow::Result<CliAccount> {
+    let (send_mint, receive_mint) = escrow.escrow_mints(account).await?;
+    let send_token = escrow.mint(send_mint).await?.into();
+    let receive_token = escrow.mint(receive_mint).await?.into();
+
+    Ok(CliAccount::new(
+        escrow_address,
+        account,
+        send_token,
+        receive_token,
+    ))
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Cli::parse();
@@ -80,8 +378,8 @@ async fn main() -> anyhow::Result<()> {
         .to_str()
         .ok_or_else(|| anyhow!("config path is invalid"))?;
     let config = Config::load(path)?;
-    let keypair = Keypair::read_from_file(config.keypair_path)
-        .map_err(|e| anyhow::Error::msg(e.to_string()))?;
+    let keypair_source = args.keypair.as_deref().unwrap_or(&config.keypair_path);
+    let keypair = resolve_signer(keypair_source, &args.signers, args.sign_only)?;
 
     let json_rpc_url = config.json_rpc_url.to_string();
     let commitment_config = CommitmentConfig::from_str(&config.commitment)?;
@@ -96,47 +394,277 @@ async fn main() -> anyhow::Result<()> {
     if let Some(escrow_program_id) = args.escrow_program_id {
         builder = builder.with_escrow_program_id(escrow_program_id);
     }
+    if let Some(fee_payer) = &args.fee_payer {
+        builder = builder.with_fee_payer(resolve_signer(fee_payer, &args.signers, args.sign_only)?);
+    }
+    if let Some(blockhash) = args.blockhash {
+        builder = builder.with_blockhash(blockhash);
+    }
+    if let Some(units) = args.compute_unit_limit {
+        builder = builder.with_compute_unit_limit(ComputeUnitLimit::Static(units));
+    } else if args.simulate_compute_unit_limit {
+        builder = builder.with_compute_unit_limit(ComputeUnitLimit::Simulated);
+    }
+    if let Some(micro_lamports) = args.compute_unit_price {
+        builder = builder.with_compute_unit_price(ComputeUnitPrice::Static(micro_lamports));
+    } else if let Some(percentile) = args.priority_fee_percentile {
+        builder = builder.with_compute_unit_price(ComputeUnitPrice::Percentile(percentile));
+    }
+    if let Some(nonce) = args.nonce {
+        let nonce_authority = resolve_signer(
+            args.nonce_authority.as_deref().unwrap_or(keypair_source),
+            &args.signers,
+            args.sign_only,
+        )?;
+        builder = builder.with_nonce(nonce, nonce_authority);
+    }
     let escrow = builder.build();
 
-    match args.command {
+    if args.sign_only
+        && !matches!(
+            args.command,
+            Commands::Init { .. } | Commands::Exchange { .. } | Commands::Cancel { .. }
+        )
+    {
+        bail!("--sign-only is only supported by init, exchange and cancel");
+    }
+
+    let output = match args.command {
         Commands::Init {
             send_mint_token_address,
             send_amount,
             receive_mint_token_address,
             receive_expected_amount,
+            seed,
+            expires_in,
+            expires_at,
+            taker,
+            send_token_program_id,
+            receive_token_program_id,
         } => {
-            let (signature, escrow_account_pubkey) = escrow
-                .init(
+            let expires_at = match expires_in {
+                Some(expires_in) => Some(timestamp_after(expires_in)?),
+                None => expires_at,
+            };
+            if args.blockhash.is_some() {
+                let tx = escrow.init_transaction(
+                    seed,
                     send_mint_token_address,
+                    offline_arg(
+                        send_token_program_id.or(args.token_program_id),
+                        "--send-token-program-id",
+                    )?,
                     send_amount,
                     receive_mint_token_address,
+                    offline_arg(
+                        receive_token_program_id.or(args.token_program_id),
+                        "--receive-token-program-id",
+                    )?,
                     receive_expected_amount,
-                )
-                .await?;
+                    expires_at,
+                    taker,
+                )?;
+                offline_output(&escrow, tx, args.sign_only, args.output).await?
+            } else {
+                let result = escrow
+                    .init(
+                        seed,
+                        send_mint_token_address,
+                        send_amount,
+                        receive_mint_token_address,
+                        receive_expected_amount,
+                        expires_at,
+                        taker,
+                    )
+                    .await?;
 
-            println!("Create Account: {:?}\n", escrow_account_pubkey);
-            println!("Signature: {:?}", signature);
+                args.output.formatted(&CliInit {
+                    escrow: result.escrow_account_pubkey.to_string(),
+                    signature: result.signature.to_string(),
+                    send_token: escrow.mint(send_mint_token_address).await?.into(),
+                    send_amount,
+                    receive_token: escrow.mint(receive_mint_token_address).await?.into(),
+                    receive_amount: receive_expected_amount,
+                    created_token_accounts: result
+                        .created_token_accounts
+                        .iter()
+                        .map(Pubkey::to_string)
+                        .collect(),
+                })?
+            }
         }
-        Commands::Exchange { escrow_address } => {
-            let signature = escrow.exchange(escrow_address).await?;
-            println!("Signature: {:?}", signature);
+        Commands::Exchange {
+            escrow_address,
+            amount,
+            expected_receive,
+            max_pay,
+            seller,
+            seller_token_account,
+            send_mint,
+            send_token_program_id,
+            receive_mint,
+            receive_token_program_id,
+        } => {
+            if args.blockhash.is_some() {
+                let addresses = EscrowAddresses {
+                    escrow_account_pubkey: escrow_address,
+                    seller_pubkey: offline_arg(seller, "--seller")?,
+                    seller_token_account_pubkey: offline_arg(
+                        seller_token_account,
+                        "--seller-token-account",
+                    )?,
+                    send_mint_pubkey: offline_arg(send_mint, "--send-mint")?,
+                    send_token_program_id: offline_arg(
+                        send_token_program_id.or(args.token_program_id),
+                        "--send-token-program-id",
+                    )?,
+                    receive_mint_pubkey: offline_arg(receive_mint, "--receive-mint")?,
+                    receive_token_program_id: offline_arg(
+                        receive_token_program_id.or(args.token_program_id),
+                        "--receive-token-program-id",
+                    )?,
+                };
+                let tx = escrow.exchange_transaction(
+                    &addresses,
+                    offline_arg(amount, "--amount")?,
+                    offline_arg(expected_receive, "--expected-receive")?,
+                    offline_arg(max_pay, "--max-pay")?,
+                )?;
+                offline_output(&escrow, tx, args.sign_only, args.output).await?
+            } else {
+                let result = escrow
+                    .exchange(escrow_address, amount, expected_receive, max_pay)
+                    .await?;
+
+                // The amounts actually exchanged are logged by the program. The exchange has
+                // succeeded either way, so the amounts computed by the client are shown if the
+                // event cannot be fetched.
+                let event = match escrow.events(&result.signature).await {
+                    Ok(events) => events.into_iter().find_map(|event| match event {
+                        EscrowEvent::Exchanged(event) if event.escrow == escrow_address => {
+                            Some(event)
+                        }
+                        _ => None,
+                    }),
+                    Err(err) => {
+                        eprintln!(
+                            "Warning: failed to fetch the events of {}: {err}",
+                            result.signature
+                        );
+                        None
+                    }
+                };
+                let amounts = match event {
+                    Some(event) => ExchangeAmounts {
+                        send_mint: event.send_mint,
+                        amount: event.amount,
+                        receive_mint: event.receive_mint,
+                        paid_amount: event.paid_amount,
+                        remaining_amount: event.remaining_amount,
+                    },
+                    None => {
+                        eprintln!(
+                            "Warning: exchange event not found in {}, showing the amounts expected \
+                             when the transaction was sent",
+                            result.signature
+                        );
+                        result.amounts
+                    }
+                };
+                args.output.formatted(&CliExchange {
+                    escrow: escrow_address.to_string(),
+                    signature: result.signature.to_string(),
+                    send_token: escrow.mint(amounts.send_mint).await?.into(),
+                    amount: amounts.amount,
+                    receive_token: escrow.mint(amounts.receive_mint).await?.into(),
+                    paid_amount: amounts.paid_amount,
+                    remaining_amount: amounts.remaining_amount,
+                    created_token_accounts: result
+                        .created_token_accounts
+                        .iter()
+                        .map(Pubkey::to_string)
+                        .collect(),
+                })?
+            }
+        }
+        Commands::Cancel {
+            escrow_address,
+            send_mint,
+            send_token_program_id,
+        } => {
+            if args.blockhash.is_some() {
+                let tx = escrow.cancel_transaction(
+                    escrow_address,
+                    offline_arg(send_mint, "--send-mint")?,
+                    offline_arg(
+                        send_token_program_id.or(args.token_program_id),
+                        "--send-token-program-id",
+                    )?,
+                )?;
+                offline_output(&escrow, tx, args.sign_only, args.output).await?
+            } else {
+                let signature = escrow.cancel(escrow_address).await?;
+                args.output.formatted(&CliSignature::from(signature))?
+            }
+        }
+        Commands::Update {
+            escrow_address,
+            amount,
+            deposit,
+            withdraw,
+            expires_in,
+            no_expiry,
+            taker,
+            no_taker,
+        } => {
+            let expires_at = match expires_in {
+                Some(expires_in) => Some(Some(timestamp_after(expires_in)?)),
+                None if no_expiry => Some(None),
+                None => None,
+            };
+            let taker = match taker {
+                Some(taker) => Some(Some(taker)),
+                None if no_taker => Some(None),
+                None => None,
+            };
+            let signature = escrow
+                .update(escrow_address, amount, deposit, withdraw, expires_at, taker)
+                .await?;
+            args.output.formatted(&CliSignature::from(signature))?
+        }
+        Commands::Refund { escrow_address } => {
+            let signature = escrow.refund(escrow_address).await?;
+            args.output.formatted(&CliSignature::from(signature))?
+        }
+        Commands::Migrate { escrow_address } => {
+            let signature = escrow.migrate(escrow_address).await?;
+            args.output.formatted(&CliSignature::from(signature))?
+        }
+        Commands::List {
+            seller,
+            seller_token_account,
+            mint,
+            min_amount,
+        } => {
+            let filter = ListFilter {
+                seller,
+                seller_token_account,
+                mint,
+                min_amount,
+            };
+            let mut escrows = Vec::new();
+            for (escrow_address, account) in escrow.list(&filter).await? {
+                escrows.push(account_output(&escrow, escrow_address, &account).await?);
+            }
+            args.output.formatted(&CliAccounts { escrows })?
         }
         Commands::Account { escrow_address } => {
             let account = escrow.account(escrow_address).await?;
-            println!("Seller: {:?}", account.seller_pubkey);
-            println!(
-                "Seller Token Account: {:?}",
-                account.seller_token_account_pubkey
-            );
-            println!(
-                "Escrow Token Account: {:?}",
-                account.temp_token_account_pubkey
-            );
-            println!("Expected amount: {:?}", account.amount);
-
-            return Ok(());
+            args.output
+                .formatted(&account_output(&escrow, escrow_address, &account).await?)?
         }
-    }
+    };
+    println!("{}", output);
 
     Ok(())
 }
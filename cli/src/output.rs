@@ -0,0 +1,278 @@
+use clap::ValueEnum;
+use escrow_client::MintInfo;
+use escrow_program::state::Escrow;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// OutputFormat is an enum that represents the format of the command output.
+#[derive(ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// JSON, for scripts.
+    Json,
+    /// YAML, for scripts.
+    Yaml,
+}
+
+impl OutputFormat {
+    /// Format the command output.
+    pub fn formatted<T: Serialize + fmt::Display>(&self, output: &T) -> anyhow::Result<String> {
+        match self {
+            OutputFormat::Text => Ok(output.to_string()),
+            OutputFormat::Json => Ok(serde_json::to_string_pretty(output)?),
+            OutputFormat::Yaml => Ok(serde_yaml::to_string(output)?),
+        }
+    }
+}
+
+/// Token of one side of an escrow.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliToken {
+    pub mint: String,
+    pub token_program: String,
+    pub decimals: u8,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+}
+
+impl From<MintInfo> for CliToken {
+    fn from(mint: MintInfo) -> Self {
+        Self {
+            mint: mint.mint_pubkey.to_string(),
+            token_program: mint.token_program_id.to_string(),
+            decimals: mint.decimals,
+            name: mint.name,
+            symbol: mint.symbol,
+        }
+    }
+}
+
+impl fmt::Display for CliToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mint)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " ({})", symbol)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Output of the `init` command.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliInit {
+    pub escrow: String,
+    pub signature: String,
+    pub send_token: CliToken,
+    pub send_amount: u64,
+    pub receive_token: CliToken,
+    pub receive_amount: u64,
+    pub created_token_accounts: Vec<String>,
+}
+
+impl fmt::Display for CliInit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token_account in &self.created_token_accounts {
+            writeln!(f, "Create Token Account: {}", token_account)?;
+        }
+        writeln!(f, "Create Account: {}\n", self.escrow)?;
+        write!(f, "Signature: {}", self.signature)
+    }
+}
+
+/// Output of the `exchange` command.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliExchange {
+    pub escrow: String,
+    pub signature: String,
+    pub send_token: CliToken,
+    pub amount: u64,
+    pub receive_token: CliToken,
+    pub paid_amount: u64,
+    pub remaining_amount: u64,
+    pub created_token_accounts: Vec<String>,
+}
+
+impl fmt::Display for CliExchange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token_account in &self.created_token_accounts {
+            writeln!(f, "Create Token Account: {}", token_account)?;
+        }
+        writeln!(f, "Received: {} {}", self.amount, self.send_token)?;
+        writeln!(f, "Paid: {} {}", self.paid_amount, self.receive_token)?;
+        write!(f, "Signature: {}", self.signature)
+    }
+}
+
+/// Output of the commands that only send a transaction.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliSignature {
+    pub signature: String,
+}
+
+impl From<Signature> for CliSignature {
+    fn from(signature: Signature) -> Self {
+        Self {
+            signature: signature.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CliSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Signature: {}", self.signature)
+    }
+}
+
+/// Output of the `account` command.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliAccount {
+    pub escrow: String,
+    pub seller: String,
+    pub seller_token_account: String,
+    pub escrow_token_account: String,
+    pub send_token: CliToken,
+    pub receive_token: CliToken,
+    pub amount: u64,
+    pub offered_amount: u64,
+    pub filled_amount: u64,
+    pub remaining_amount: u64,
+    pub taker: Option<String>,
+    pub expires_at: Option<i64>,
+}
+
+impl CliAccount {
+    pub fn new(
+        escrow_pubkey: Pubkey,
+        escrow: &Escrow,
+        send_token: CliToken,
+        receive_token: CliToken,
+    ) -> Self {
+        Self {
+            escrow: escrow_pubkey.to_string(),
+            seller: escrow.seller_pubkey.to_string(),
+            seller_token_account: escrow.seller_token_account_pubkey.to_string(),
+            escrow_token_account: escrow.temp_token_account_pubkey.to_string(),
+            send_token,
+            receive_token,
+            amount: escrow.amount,
+            offered_amount: escrow.offered_amount,
+            filled_amount: escrow.filled_amount,
+            remaining_amount: escrow.remaining_amount(),
+            taker: escrow.taker.map(|taker| taker.to_string()),
+            expires_at: escrow.expires_at,
+        }
+    }
+}
+
+impl fmt::Display for CliAccount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Escrow: {}", self.escrow)?;
+        writeln!(f, "Seller: {}", self.seller)?;
+        writeln!(f, "Seller Token Account: {}", self.seller_token_account)?;
+        writeln!(f, "Escrow Token Account: {}", self.escrow_token_account)?;
+        writeln!(f, "Send Token: {}", self.send_token)?;
+        writeln!(f, "Receive Token: {}", self.receive_token)?;
+        writeln!(f, "Expected amount: {}", self.amount)?;
+        writeln!(f, "Offered amount: {}", self.offered_amount)?;
+        write!(f, "Filled amount: {}", self.filled_amount)?;
+        if let Some(taker) = &self.taker {
+            write!(f, "\nTaker: {}", taker)?;
+        }
+        if let Some(expires_at) = self.expires_at {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs());
+            match u64::try_from(expires_at) {
+                Ok(expires_at) if expires_at > now => write!(
+                    f,
+                    "\nExpires in: {}",
+                    humantime::format_duration(Duration::from_secs(expires_at - now))
+                )?,
+                _ => write!(f, "\nExpires in: expired")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Output of the `list` command.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliAccounts {
+    pub escrows: Vec<CliAccount>,
+}
+
+impl fmt::Display for CliAccounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, escrow) in self.escrows.iter().enumerate() {
+            if i > 0 {
+                writeln!(f, "\n")?;
+            }
+            write!(f, "{}", escrow)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Output of the commands run with `--sign-only`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliSignOnly {
+    pub blockhash: String,
+    pub signers: Vec<String>,
+    pub absent: Vec<String>,
+}
+
+impl CliSignOnly {
+    pub fn new(tx: &Transaction) -> Self {
+        let mut signers = Vec::new();
+        let mut absent = Vec::new();
+        for (pubkey, signature) in tx.message.account_keys.iter().zip(&tx.signatures) {
+            if *signature == Signature::default() {
+                absent.push(pubkey.to_string());
+            } else {
+                signers.push(format!("{}={}", pubkey, signature));
+            }
+        }
+
+        Self {
+            blockhash: tx.message.recent_blockhash.to_string(),
+            signers,
+            absent,
+        }
+    }
+}
+
+impl fmt::Display for CliSignOnly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Blockhash: {}", self.blockhash)?;
+        if !self.signers.is_empty() {
+            write!(f, "\nSigners (Pubkey=Signature):")?;
+            for signer in &self.signers {
+                write!(f, "\n  {}", signer)?;
+            }
+        }
+        if !self.absent.is_empty() {
+            write!(f, "\nAbsent Signers (Pubkey):")?;
+            for pubkey in &self.absent {
+                write!(f, "\n  {}", pubkey)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,27 +1,110 @@
-use borsh::BorshDeserialize;
+use crate::event::parse_events;
+use crate::state::EscrowAccount;
+use escrow_program::error::EscrowError;
+use escrow_program::event::EscrowEvent;
+use escrow_program::pda::{
+    find_authority_address, find_escrow_address, find_legacy_authority_address, find_vault_address,
+};
 use escrow_program::state::Escrow;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_rpc_client_api::config::RpcSendTransactionConfig;
+use solana_client::nonce_utils::nonblocking as nonce_utils;
+use solana_rpc_client_api::config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSendTransactionConfig,
+    RpcSimulateTransactionConfig, RpcTransactionConfig,
+};
+use solana_rpc_client_api::filter::{Memcmp, RpcFilterType};
+use solana_rpc_client_api::request::MAX_MULTIPLE_ACCOUNTS;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::decode_error::DecodeError;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::message::Message;
 use solana_sdk::program_error::ProgramError;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::rent::Rent;
-use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::signer::SignerError;
 use solana_sdk::system_instruction;
+use solana_sdk::system_program;
 use solana_sdk::sysvar::SysvarId;
-use solana_sdk::transaction::Transaction;
-use spl_token::solana_program::program_pack::Pack;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_transaction_status_client_types::UiTransactionEncoding;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeConfig};
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use spl_token_2022::state::{Account, Mint};
+use spl_token_metadata_interface::state::TokenMetadata;
 use std::sync::Arc;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ClientError {
     #[error("{0}")]
-    RpcError(#[from] solana_rpc_client_api::client_error::Error),
+    RpcError(#[from] Box<solana_rpc_client_api::client_error::Error>),
     #[error("{0}")]
     ProgramError(#[from] ProgramError),
+    #[error("{0}")]
+    EscrowError(#[from] EscrowError),
     #[error("{0:?}")]
     SerializeSizeError(borsh::schema::SchemaMaxSerializedSizeError),
     #[error("{0}")]
     IoError(#[from] std::io::Error),
+    #[error("{0}")]
+    Base64Error(#[from] base64::DecodeError),
+    #[error("{0}")]
+    SignerError(#[from] SignerError),
+    #[error("{0}")]
+    NonceError(#[from] nonce_utils::Error),
+    #[error("{0}")]
+    TransactionError(TransactionError),
+    #[error("{0} must be given to build the transaction without reading the cluster")]
+    OfflineError(&'static str),
+}
+
+impl From<solana_rpc_client_api::client_error::Error> for ClientError {
+    fn from(err: solana_rpc_client_api::client_error::Error) -> Self {
+        ClientError::RpcError(Box::new(err))
+    }
+}
+
+impl From<TransactionError> for ClientError {
+    fn from(err: TransactionError) -> Self {
+        ClientError::TransactionError(err)
+    }
+}
+
+impl ClientError {
+    /// Decode the error of the transaction of the message as an [`EscrowError`] if it was returned
+    /// by an instruction of the escrow program.
+    ///
+    /// The client decodes the errors of the transactions it sends; this is for transactions
+    /// created by the client and sent by other means.
+    ///
+    /// Custom error codes are only meaningful to the program returning them, so the errors of
+    /// other instructions, such as the ones creating token accounts, are left as they are. The
+    /// errors of the token programs invoked by the escrow program are reported with the index of
+    /// the escrow instruction, and are told apart by their codes, which are below
+    /// [`escrow_program::error::ERROR_CODE_OFFSET`].
+    pub fn decode_escrow_error(self, message: &Message, escrow_program_id: &Pubkey) -> Self {
+        let transaction_error = match &self {
+            ClientError::RpcError(err) => err.get_transaction_error(),
+            ClientError::TransactionError(err) => Some(err.clone()),
+            _ => None,
+        };
+        let Some(TransactionError::InstructionError(index, InstructionError::Custom(code))) =
+            transaction_error
+        else {
+            return self;
+        };
+        let is_escrow_instruction =
+            message.program_id(usize::from(index)) == Some(escrow_program_id);
+
+        match EscrowError::decode_custom_error_to_enum(code) {
+            Some(err) if is_escrow_instruction => ClientError::EscrowError(err),
+            _ => self,
+        }
+    }
 }
 
 impl From<borsh::schema::SchemaMaxSerializedSizeError> for ClientError {
@@ -32,13 +115,46 @@ impl From<borsh::schema::SchemaMaxSerializedSizeError> for ClientError {
 
 pub type Result<T> = std::result::Result<T, ClientError>;
 
+/// Offset of `Escrow::is_initialized` in the escrow account, right after the header.
+const IS_INITIALIZED_OFFSET: usize = Escrow::HEADER_LEN;
+
+/// Offset of `Escrow::seller_pubkey` in the escrow account.
+const SELLER_OFFSET: usize = IS_INITIALIZED_OFFSET + 1;
+
+/// Offset of `Escrow::seller_token_account_pubkey` in the escrow account.
+const SELLER_TOKEN_ACCOUNT_OFFSET: usize = SELLER_OFFSET + 32;
+
+/// Maximum compute unit limit of a transaction.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Margin added to the simulated compute units, in percent.
+///
+/// It also covers the instruction advancing a durable nonce, which is not simulated.
+const SIMULATED_COMPUTE_UNIT_MARGIN: u64 = 10;
+
 /// Client for interacting with the escrow program.
 pub struct Client {
     /// RPC client.
     client: Arc<RpcClient>,
 
-    /// Keypair of the payer.
-    payer: Keypair,
+    /// Signer acting as the seller or the buyer, who also pays for the rent of the accounts.
+    authority: Box<dyn Signer + Send + Sync>,
+
+    /// Signer paying the transaction fees, the authority if `None`.
+    fee_payer: Option<Box<dyn Signer + Send + Sync>>,
+
+    /// Recent blockhash of the transactions, fetched from the cluster if `None`.
+    blockhash: Option<Hash>,
+
+    /// Durable nonce account and its authority, advanced by the transactions instead of using a
+    /// recent blockhash.
+    nonce: Option<(Pubkey, Box<dyn Signer + Send + Sync>)>,
+
+    /// Compute unit limit of the transactions.
+    compute_unit_limit: ComputeUnitLimit,
+
+    /// Compute unit price of the transactions.
+    compute_unit_price: ComputeUnitPrice,
 
     /// Configuration for sending transactions.
     rpc_send_transaction_config: RpcSendTransactionConfig,
@@ -46,185 +162,1409 @@ pub struct Client {
     /// Escrow program ID.
     escrow_program_id: Pubkey,
 
-    /// Token program ID.
-    token_program_id: Pubkey,
+    /// Token program of the mints, detected from the owner of each mint if `None`.
+    token_program_id: Option<Pubkey>,
 }
 
 impl Client {
-    pub fn builder(client: Arc<RpcClient>, payer: Keypair) -> ClientBuilder {
-        ClientBuilder::new(client, payer)
+    pub fn builder<S: Signer + Send + Sync + 'static>(
+        client: Arc<RpcClient>,
+        authority: S,
+    ) -> ClientBuilder {
+        ClientBuilder::new(client, Box::new(authority))
     }
 
-    /// Initialize the escrow account.
+    /// Find the escrow account of the seller for the seed.
+    pub fn find_escrow_address(&self, seller_pubkey: &Pubkey, seed: u64) -> Pubkey {
+        find_escrow_address(seller_pubkey, seed, &self.escrow_program_id).0
+    }
+
+    /// Initialize the escrow account, expiring at the unix timestamp if given.
+    ///
+    /// If `taker` is given, only that buyer can exchange the escrow.
+    #[allow(clippy::too_many_arguments)]
     pub async fn init(
         &self,
+        seed: u64,
+        send_mint_token_account_pubkey: Pubkey,
+        send_amount: u64,
+        receive_mint_token_account_pubkey: Pubkey,
+        receive_expected_amount: u64,
+        expires_at: Option<i64>,
+        taker: Option<Pubkey>,
+    ) -> Result<InitResult> {
+        let (instructions, escrow_account_pubkey, created_token_accounts) = self
+            .init_instructions(
+                seed,
+                send_mint_token_account_pubkey,
+                send_amount,
+                receive_mint_token_account_pubkey,
+                receive_expected_amount,
+                expires_at,
+                taker,
+            )
+            .await?;
+
+        let signature = self.send_instructions(&instructions).await?;
+
+        Ok(InitResult {
+            signature,
+            escrow_account_pubkey,
+            created_token_accounts,
+        })
+    }
+
+    /// Create the unsigned transaction initializing the escrow account, see [`Client::init`].
+    ///
+    /// The transaction is built from its arguments alone, without reading the cluster, so that
+    /// it is the same when built again to be signed by another party or sent, see
+    /// [`Client::offline_transaction`]. It creates the associated token accounts of the seller
+    /// if they are missing, and leaves the one of the native mint open after wrapping SOL.
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_transaction(
+        &self,
+        seed: u64,
+        send_mint_token_account_pubkey: Pubkey,
+        send_token_program_id: Pubkey,
+        send_amount: u64,
+        receive_mint_token_account_pubkey: Pubkey,
+        receive_token_program_id: Pubkey,
+        receive_expected_amount: u64,
+        expires_at: Option<i64>,
+        taker: Option<Pubkey>,
+    ) -> Result<Transaction> {
+        let send_seller_token_account_pubkey = get_associated_token_address_with_program_id(
+            &self.authority.pubkey(),
+            &send_mint_token_account_pubkey,
+            &send_token_program_id,
+        );
+        let receive_seller_token_account_pubkey = get_associated_token_address_with_program_id(
+            &self.authority.pubkey(),
+            &receive_mint_token_account_pubkey,
+            &receive_token_program_id,
+        );
+        let escrow_account_pubkey = self.find_escrow_address(&self.authority.pubkey(), seed);
+        let (temp_token_account_pubkey, _) =
+            find_vault_address(&escrow_account_pubkey, &self.escrow_program_id);
+
+        let mut instructions = vec![
+            self.create_associated_token_account_instruction(
+                &self.authority.pubkey(),
+                &send_mint_token_account_pubkey,
+                &send_token_program_id,
+            ),
+            self.create_associated_token_account_instruction(
+                &self.authority.pubkey(),
+                &receive_mint_token_account_pubkey,
+                &receive_token_program_id,
+            ),
+        ];
+        if is_native_mint(&send_mint_token_account_pubkey) {
+            instructions.extend(self.wrap_instructions(
+                &send_mint_token_account_pubkey,
+                &send_token_program_id,
+                send_amount,
+            )?);
+        }
+        instructions.push(escrow_program::instruction::init(
+            self.escrow_program_id,
+            self.authority.pubkey(),
+            receive_seller_token_account_pubkey,
+            send_seller_token_account_pubkey,
+            send_mint_token_account_pubkey,
+            temp_token_account_pubkey,
+            escrow_account_pubkey,
+            Rent::id(),
+            send_token_program_id,
+            system_program::id(),
+            seed,
+            send_amount,
+            receive_expected_amount,
+            expires_at,
+            taker,
+        ));
+
+        self.offline_transaction(&instructions)
+    }
+
+    /// Create the instructions initializing the escrow account, and return them along with the
+    /// address of the escrow account and of the token accounts they create.
+    #[allow(clippy::too_many_arguments)]
+    async fn init_instructions(
+        &self,
+        seed: u64,
         send_mint_token_account_pubkey: Pubkey,
         send_amount: u64,
         receive_mint_token_account_pubkey: Pubkey,
         receive_expected_amount: u64,
-    ) -> Result<(Signature, Pubkey)> {
+        expires_at: Option<i64>,
+        taker: Option<Pubkey>,
+    ) -> Result<(Vec<Instruction>, Pubkey, Vec<Pubkey>)> {
+        let send_token_program_id = self
+            .mint_token_program_id(&send_mint_token_account_pubkey)
+            .await?;
         let send_seller_token_account_pubkey =
             spl_associated_token_account::get_associated_token_address_with_program_id(
-                &self.payer.pubkey(),
+                &self.authority.pubkey(),
                 &send_mint_token_account_pubkey,
-                &self.token_program_id,
+                &send_token_program_id,
             );
 
+        let receive_token_program_id = self
+            .mint_token_program_id(&receive_mint_token_account_pubkey)
+            .await?;
         let receive_seller_token_account_pubkey =
             spl_associated_token_account::get_associated_token_address_with_program_id(
-                &self.payer.pubkey(),
+                &self.authority.pubkey(),
                 &receive_mint_token_account_pubkey,
-                &self.token_program_id,
+                &receive_token_program_id,
             );
 
-        let temp_token_account = Keypair::new();
-        let temp_token_account_len = spl_token::state::Account::LEN;
-        let temp_token_account_lamports = self
-            .client
-            .get_minimum_balance_for_rent_exemption(temp_token_account_len)
+        let escrow_account_pubkey = self.find_escrow_address(&self.authority.pubkey(), seed);
+        let (temp_token_account_pubkey, _) =
+            find_vault_address(&escrow_account_pubkey, &self.escrow_program_id);
+
+        let mut instructions = Vec::new();
+        let created_token_accounts = self
+            .create_missing_token_accounts(
+                &mut instructions,
+                &[
+                    (send_mint_token_account_pubkey, send_token_program_id),
+                    (receive_mint_token_account_pubkey, receive_token_program_id),
+                ],
+            )
             .await?;
+        if is_native_mint(&send_mint_token_account_pubkey) {
+            instructions.extend(self.wrap_instructions(
+                &send_mint_token_account_pubkey,
+                &send_token_program_id,
+                send_amount,
+            )?);
+        }
+        instructions.push(escrow_program::instruction::init(
+            self.escrow_program_id,
+            self.authority.pubkey(),
+            receive_seller_token_account_pubkey,
+            send_seller_token_account_pubkey,
+            send_mint_token_account_pubkey,
+            temp_token_account_pubkey,
+            escrow_account_pubkey,
+            Rent::id(),
+            send_token_program_id,
+            system_program::id(),
+            seed,
+            send_amount,
+            receive_expected_amount,
+            expires_at,
+            taker,
+        ));
+        if is_native_mint(&send_mint_token_account_pubkey) {
+            instructions.extend(self.unwrap_instruction(
+                &send_mint_token_account_pubkey,
+                &send_token_program_id,
+                &created_token_accounts,
+                &receive_seller_token_account_pubkey,
+            )?);
+        }
 
-        let escrow_account = Keypair::new();
-        let escrow_account_len = borsh::max_serialized_size::<Escrow>()?;
-        let escrow_account_lamports = self
-            .client
-            .get_minimum_balance_for_rent_exemption(escrow_account_len)
+        Ok((instructions, escrow_account_pubkey, created_token_accounts))
+    }
+
+    /// Exchange the tokens in the escrow account.
+    ///
+    /// Takes `amount` of the deposited tokens at the posted rate, or all of the remaining tokens
+    /// if `None`.
+    ///
+    /// The exchange fails if the buyer would receive less than `expected_receive` or pay more
+    /// than `max_pay`. Unless given, the bounds are computed from the escrow state and the
+    /// transfer fees fetched from the cluster, so the exchange fails if they change before it is
+    /// executed.
+    pub async fn exchange(
+        &self,
+        escrow_account_pubkey: Pubkey,
+        amount: Option<u64>,
+        expected_receive: Option<u64>,
+        max_pay: Option<u64>,
+    ) -> Result<ExchangeResult> {
+        let (instructions, created_token_accounts, amounts) = self
+            .exchange_instructions(escrow_account_pubkey, amount, expected_receive, max_pay)
             .await?;
 
-        let blockhash = self.client.get_latest_blockhash().await?;
-
-        let tx = Transaction::new_signed_with_payer(
-            &[
-                system_instruction::create_account(
-                    &self.payer.pubkey(),
-                    &temp_token_account.pubkey(),
-                    temp_token_account_lamports,
-                    temp_token_account_len as u64,
-                    &self.token_program_id,
-                ),
-                spl_token::instruction::initialize_account(
-                    &self.token_program_id,
-                    &temp_token_account.pubkey(),
-                    &send_mint_token_account_pubkey,
-                    &self.payer.pubkey(),
-                )?,
-                spl_token::instruction::transfer(
-                    &self.token_program_id,
-                    &send_seller_token_account_pubkey,
-                    &temp_token_account.pubkey(),
-                    &self.payer.pubkey(),
-                    &[&self.payer.pubkey()],
-                    send_amount,
-                )?,
-                system_instruction::create_account(
-                    &self.payer.pubkey(),
-                    &escrow_account.pubkey(),
-                    escrow_account_lamports,
-                    escrow_account_len as u64,
-                    &self.escrow_program_id,
-                ),
-                escrow_program::instruction::init(
-                    self.escrow_program_id,
-                    self.payer.pubkey(),
-                    receive_seller_token_account_pubkey,
-                    temp_token_account.pubkey(),
-                    escrow_account.pubkey(),
-                    Rent::id(),
-                    self.token_program_id,
-                    receive_expected_amount,
-                ),
-            ],
-            Some(&self.payer.pubkey()),
-            &[&self.payer, &temp_token_account, &escrow_account],
-            blockhash,
+        let signature = self.send_instructions(&instructions).await?;
+        Ok(ExchangeResult {
+            signature,
+            created_token_accounts,
+            amounts,
+        })
+    }
+
+    /// Create the unsigned transaction exchanging `amount` of the tokens in the escrow account,
+    /// see [`Client::exchange`].
+    ///
+    /// The transaction is built from its arguments alone, without reading the cluster, so that
+    /// it is the same when built again to be signed by another party or sent, see
+    /// [`Client::offline_transaction`]. It creates the associated token accounts of the buyer if
+    /// they are missing, and leaves the ones of the native mint open after wrapping SOL or
+    /// receiving wrapped SOL.
+    pub fn exchange_transaction(
+        &self,
+        escrow: &EscrowAddresses,
+        amount: u64,
+        expected_receive: u64,
+        max_pay: u64,
+    ) -> Result<Transaction> {
+        let buyer_send_token_account_pubkey = get_associated_token_address_with_program_id(
+            &self.authority.pubkey(),
+            &escrow.receive_mint_pubkey,
+            &escrow.receive_token_program_id,
+        );
+        let buyer_receive_token_account_pubkey = get_associated_token_address_with_program_id(
+            &self.authority.pubkey(),
+            &escrow.send_mint_pubkey,
+            &escrow.send_token_program_id,
         );
+        let (temp_token_account_pubkey, _) =
+            find_vault_address(&escrow.escrow_account_pubkey, &self.escrow_program_id);
+        let (pda_account_pubkey, _) =
+            find_authority_address(&escrow.escrow_account_pubkey, &self.escrow_program_id);
 
-        let signature = self
-            .client
-            .send_and_confirm_transaction_with_spinner_and_config(
-                &tx,
-                self.client.commitment(),
-                self.rpc_send_transaction_config.clone(),
-            )
-            .await?;
+        let mut instructions = vec![
+            self.create_associated_token_account_instruction(
+                &self.authority.pubkey(),
+                &escrow.receive_mint_pubkey,
+                &escrow.receive_token_program_id,
+            ),
+            self.create_associated_token_account_instruction(
+                &self.authority.pubkey(),
+                &escrow.send_mint_pubkey,
+                &escrow.send_token_program_id,
+            ),
+        ];
+        if is_native_mint(&escrow.receive_mint_pubkey) {
+            instructions.extend(self.wrap_instructions(
+                &escrow.receive_mint_pubkey,
+                &escrow.receive_token_program_id,
+                max_pay,
+            )?);
+        }
+        instructions.push(escrow_program::instruction::exchange(
+            self.escrow_program_id,
+            self.authority.pubkey(),
+            buyer_send_token_account_pubkey,
+            buyer_receive_token_account_pubkey,
+            temp_token_account_pubkey,
+            escrow.seller_pubkey,
+            escrow.seller_token_account_pubkey,
+            escrow.escrow_account_pubkey,
+            escrow.send_token_program_id,
+            pda_account_pubkey,
+            escrow.send_mint_pubkey,
+            escrow.receive_mint_pubkey,
+            escrow.receive_token_program_id,
+            amount,
+            expected_receive,
+            max_pay,
+        ));
 
-        Ok((signature, escrow_account.pubkey()))
+        self.offline_transaction(&instructions)
     }
 
-    /// Exchange the tokens in the escrow account.
-    pub async fn exchange(&self, escrow_account_pubkey: Pubkey) -> Result<Signature> {
-        let escrow_account = self.client.get_account(&escrow_account_pubkey).await?;
-        let escrow_state = Escrow::try_from_slice(&escrow_account.data)?;
+    /// Create the instructions exchanging the tokens in the escrow account, and return them
+    /// along with the address of the token accounts they create and the expected amounts.
+    async fn exchange_instructions(
+        &self,
+        escrow_account_pubkey: Pubkey,
+        amount: Option<u64>,
+        expected_receive: Option<u64>,
+        max_pay: Option<u64>,
+    ) -> Result<(Vec<Instruction>, Vec<Pubkey>, ExchangeAmounts)> {
+        let escrow_state = self.escrow_state(&escrow_account_pubkey).await?;
 
-        let seller_token_account = self
-            .client
-            .get_account(&escrow_state.seller_token_account_pubkey)
+        let (receive_token_program_id, seller_token_account_state) = self
+            .token_account(&escrow_state.seller_token_account_pubkey)
             .await?;
-        let seller_token_account_state =
-            spl_token::state::Account::unpack(&seller_token_account.data)?;
 
-        let temp_token_account = self
-            .client
-            .get_account(&escrow_state.temp_token_account_pubkey)
+        let (send_token_program_id, temp_token_account_state) = self
+            .token_account(&escrow_state.temp_token_account_pubkey)
             .await?;
-        let temp_token_account_state = spl_token::state::Account::unpack(&temp_token_account.data)?;
 
         let buyer_send_token_account_pubkey =
             spl_associated_token_account::get_associated_token_address_with_program_id(
-                &self.payer.pubkey(),
+                &self.authority.pubkey(),
                 &seller_token_account_state.mint,
-                &self.token_program_id,
+                &receive_token_program_id,
             );
 
         let buyer_receive_token_account_pubkey =
             spl_associated_token_account::get_associated_token_address_with_program_id(
-                &self.payer.pubkey(),
+                &self.authority.pubkey(),
                 &temp_token_account_state.mint,
-                &self.token_program_id,
+                &send_token_program_id,
             );
 
         let (pda_account_pubkey, _) =
-            Pubkey::find_program_address(&[b"escrow"], &self.escrow_program_id);
+            find_authority_address(&escrow_account_pubkey, &self.escrow_program_id);
+
+        let amount = amount.unwrap_or_else(|| escrow_state.remaining_amount());
+        let expected_receive = match expected_receive {
+            Some(expected_receive) => expected_receive,
+            None => match self.transfer_fee(&temp_token_account_state.mint).await? {
+                Some(transfer_fee) => transfer_fee
+                    .calculate_fee(amount)
+                    .and_then(|fee| amount.checked_sub(fee))
+                    .ok_or(ProgramError::ArithmeticOverflow)?,
+                None => amount,
+            },
+        };
+        let price = escrow_state
+            .fill_price(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let paid_amount = match self.transfer_fee(&seller_token_account_state.mint).await? {
+            Some(transfer_fee) => transfer_fee
+                .calculate_pre_fee_amount(price)
+                .ok_or(ProgramError::ArithmeticOverflow)?,
+            None => price,
+        };
+        let max_pay = max_pay.unwrap_or(paid_amount);
+        let amounts = ExchangeAmounts {
+            send_mint: temp_token_account_state.mint,
+            amount,
+            receive_mint: seller_token_account_state.mint,
+            paid_amount,
+            remaining_amount: escrow_state.remaining_amount().saturating_sub(amount),
+        };
+        let mut instructions = Vec::new();
+        let created_token_accounts = self
+            .create_missing_token_accounts(
+                &mut instructions,
+                &[
+                    (seller_token_account_state.mint, receive_token_program_id),
+                    (temp_token_account_state.mint, send_token_program_id),
+                ],
+            )
+            .await?;
+        if is_native_mint(&seller_token_account_state.mint) {
+            instructions.extend(self.wrap_instructions(
+                &seller_token_account_state.mint,
+                &receive_token_program_id,
+                max_pay,
+            )?);
+        }
+        instructions.push(escrow_program::instruction::exchange(
+            self.escrow_program_id,
+            self.authority.pubkey(),
+            buyer_send_token_account_pubkey,
+            buyer_receive_token_account_pubkey,
+            escrow_state.temp_token_account_pubkey,
+            escrow_state.seller_pubkey,
+            escrow_state.seller_token_account_pubkey,
+            escrow_account_pubkey,
+            send_token_program_id,
+            pda_account_pubkey,
+            temp_token_account_state.mint,
+            seller_token_account_state.mint,
+            receive_token_program_id,
+            amount,
+            expected_receive,
+            max_pay,
+        ));
+        if is_native_mint(&seller_token_account_state.mint) {
+            instructions.extend(self.unwrap_instruction(
+                &seller_token_account_state.mint,
+                &receive_token_program_id,
+                &created_token_accounts,
+                &escrow_state.seller_token_account_pubkey,
+            )?);
+        }
+        if is_native_mint(&temp_token_account_state.mint) {
+            instructions.extend(self.unwrap_instruction(
+                &temp_token_account_state.mint,
+                &send_token_program_id,
+                &created_token_accounts,
+                &escrow_state.seller_token_account_pubkey,
+            )?);
+        }
+
+        Ok((instructions, created_token_accounts, amounts))
+    }
+
+    /// Cancel the escrow and return the deposited tokens to the seller.
+    pub async fn cancel(&self, escrow_account_pubkey: Pubkey) -> Result<Signature> {
+        let instructions = self.cancel_instructions(escrow_account_pubkey).await?;
 
-        let blockhash = self.client.get_latest_blockhash().await?;
+        let signature = self.send_instructions(&instructions).await?;
+        Ok(signature)
+    }
+
+    /// Create the unsigned transaction cancelling the escrow of the deposited mint, see
+    /// [`Client::cancel`].
+    ///
+    /// The transaction is built from its arguments alone, without reading the cluster, so that
+    /// it is the same when built again to be signed by another party or sent, see
+    /// [`Client::offline_transaction`]. It creates the associated token account of the seller if
+    /// it is missing, and leaves the one of the native mint open with the returned wrapped SOL.
+    pub fn cancel_transaction(
+        &self,
+        escrow_account_pubkey: Pubkey,
+        send_mint_pubkey: Pubkey,
+        send_token_program_id: Pubkey,
+    ) -> Result<Transaction> {
+        let seller_send_token_account_pubkey = get_associated_token_address_with_program_id(
+            &self.authority.pubkey(),
+            &send_mint_pubkey,
+            &send_token_program_id,
+        );
+        let (temp_token_account_pubkey, _) =
+            find_vault_address(&escrow_account_pubkey, &self.escrow_program_id);
+        let (pda_account_pubkey, _) =
+            find_authority_address(&escrow_account_pubkey, &self.escrow_program_id);
 
-        let tx = Transaction::new_signed_with_payer(
-            &[escrow_program::instruction::exchange(
+        let instructions = [
+            self.create_associated_token_account_instruction(
+                &self.authority.pubkey(),
+                &send_mint_pubkey,
+                &send_token_program_id,
+            ),
+            escrow_program::instruction::cancel(
                 self.escrow_program_id,
-                self.payer.pubkey(),
-                buyer_send_token_account_pubkey,
-                buyer_receive_token_account_pubkey,
-                escrow_state.temp_token_account_pubkey,
-                escrow_state.seller_pubkey,
-                escrow_state.seller_token_account_pubkey,
+                self.authority.pubkey(),
+                seller_send_token_account_pubkey,
+                temp_token_account_pubkey,
                 escrow_account_pubkey,
-                self.token_program_id,
+                send_token_program_id,
                 pda_account_pubkey,
-                temp_token_account_state.amount,
-            )],
-            Some(&self.payer.pubkey()),
-            &[&self.payer],
-            blockhash,
-        );
+                send_mint_pubkey,
+            ),
+        ];
+
+        self.offline_transaction(&instructions)
+    }
+
+    /// Create the instructions cancelling the escrow.
+    async fn cancel_instructions(&self, escrow_account_pubkey: Pubkey) -> Result<Vec<Instruction>> {
+        let escrow_state = self.escrow_state(&escrow_account_pubkey).await?;
+
+        let (send_token_program_id, temp_token_account_state) = self
+            .token_account(&escrow_state.temp_token_account_pubkey)
+            .await?;
+
+        let seller_send_token_account_pubkey =
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &self.authority.pubkey(),
+                &temp_token_account_state.mint,
+                &send_token_program_id,
+            );
+
+        let (pda_account_pubkey, _) =
+            find_authority_address(&escrow_account_pubkey, &self.escrow_program_id);
+
+        let mut instructions = Vec::new();
+        let mut created_token_accounts = Vec::new();
+        if is_native_mint(&temp_token_account_state.mint) {
+            created_token_accounts = self
+                .create_missing_token_accounts(
+                    &mut instructions,
+                    &[(temp_token_account_state.mint, send_token_program_id)],
+                )
+                .await?;
+        }
+        instructions.push(escrow_program::instruction::cancel(
+            self.escrow_program_id,
+            self.authority.pubkey(),
+            seller_send_token_account_pubkey,
+            escrow_state.temp_token_account_pubkey,
+            escrow_account_pubkey,
+            send_token_program_id,
+            pda_account_pubkey,
+            temp_token_account_state.mint,
+        ));
+        if is_native_mint(&temp_token_account_state.mint) {
+            instructions.extend(self.unwrap_instruction(
+                &temp_token_account_state.mint,
+                &send_token_program_id,
+                &created_token_accounts,
+                &escrow_state.seller_token_account_pubkey,
+            )?);
+        }
+
+        Ok(instructions)
+    }
+
+    /// Update the terms of the escrow.
+    ///
+    /// Deposits `deposit_amount` more tokens and withdraws `withdraw_amount` of the remaining
+    /// tokens, then expects `amount` for all of the tokens left in the escrow, or keeps the rate
+    /// of the remaining tokens if `None`. The expiry and the taker are replaced if given.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update(
+        &self,
+        escrow_account_pubkey: Pubkey,
+        amount: Option<u64>,
+        deposit_amount: u64,
+        withdraw_amount: u64,
+        expires_at: Option<Option<i64>>,
+        taker: Option<Option<Pubkey>>,
+    ) -> Result<Signature> {
+        let escrow_state = self.escrow_state(&escrow_account_pubkey).await?;
+
+        let (send_token_program_id, temp_token_account_state) = self
+            .token_account(&escrow_state.temp_token_account_pubkey)
+            .await?;
+
+        let seller_send_token_account_pubkey =
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &self.authority.pubkey(),
+                &temp_token_account_state.mint,
+                &send_token_program_id,
+            );
 
+        let (pda_account_pubkey, _) =
+            find_authority_address(&escrow_account_pubkey, &self.escrow_program_id);
+
+        let mut instructions = Vec::new();
+        let mut created_token_accounts = Vec::new();
+        // Native SOL moves through the wSOL account of the seller, which only a deposit or a
+        // withdrawal touches
+        let is_native = is_native_mint(&temp_token_account_state.mint);
+        if is_native && (deposit_amount > 0 || withdraw_amount > 0) {
+            created_token_accounts = self
+                .create_missing_token_accounts(
+                    &mut instructions,
+                    &[(temp_token_account_state.mint, send_token_program_id)],
+                )
+                .await?;
+        }
+        if is_native && deposit_amount > 0 {
+            instructions.extend(self.wrap_instructions(
+                &temp_token_account_state.mint,
+                &send_token_program_id,
+                deposit_amount,
+            )?);
+        }
+        instructions.push(escrow_program::instruction::update_terms(
+            self.escrow_program_id,
+            self.authority.pubkey(),
+            seller_send_token_account_pubkey,
+            escrow_state.temp_token_account_pubkey,
+            escrow_account_pubkey,
+            send_token_program_id,
+            pda_account_pubkey,
+            temp_token_account_state.mint,
+            amount,
+            deposit_amount,
+            withdraw_amount,
+            expires_at,
+            taker,
+        ));
+        if is_native {
+            instructions.extend(self.unwrap_instruction(
+                &temp_token_account_state.mint,
+                &send_token_program_id,
+                &created_token_accounts,
+                &escrow_state.seller_token_account_pubkey,
+            )?);
+        }
+
+        let signature = self.send_instructions(&instructions).await?;
+        Ok(signature)
+    }
+
+    /// Refund an expired escrow and return the deposited tokens to the seller.
+    ///
+    /// Anyone can refund an expired escrow; the client only pays the transaction fee.
+    pub async fn refund(&self, escrow_account_pubkey: Pubkey) -> Result<Signature> {
+        let escrow_state = self.escrow_state(&escrow_account_pubkey).await?;
+
+        let (send_token_program_id, temp_token_account_state) = self
+            .token_account(&escrow_state.temp_token_account_pubkey)
+            .await?;
+
+        let seller_send_token_account_pubkey =
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &escrow_state.seller_pubkey,
+                &temp_token_account_state.mint,
+                &send_token_program_id,
+            );
+
+        let (pda_account_pubkey, _) =
+            find_authority_address(&escrow_account_pubkey, &self.escrow_program_id);
+
+        // The seller cannot unwrap here as they do not sign, so wrapped SOL is returned to their
+        // associated token account
+        let mut instructions = Vec::new();
+        if is_native_mint(&temp_token_account_state.mint) {
+            instructions.push(self.create_associated_token_account_instruction(
+                &escrow_state.seller_pubkey,
+                &temp_token_account_state.mint,
+                &send_token_program_id,
+            ));
+        }
+        instructions.push(escrow_program::instruction::refund(
+            self.escrow_program_id,
+            escrow_state.seller_pubkey,
+            seller_send_token_account_pubkey,
+            escrow_state.temp_token_account_pubkey,
+            escrow_account_pubkey,
+            send_token_program_id,
+            pda_account_pubkey,
+            temp_token_account_state.mint,
+        ));
+
+        let signature = self.send_instructions(&instructions).await?;
+        Ok(signature)
+    }
+
+    /// Migrate the escrow account to the newest layout.
+    ///
+    /// Anyone can migrate an escrow; the authority funds the additional rent.
+    pub async fn migrate(&self, escrow_account_pubkey: Pubkey) -> Result<Signature> {
+        let escrow_state = self.escrow_state(&escrow_account_pubkey).await?;
+
+        let (send_token_program_id, _) = self
+            .token_account(&escrow_state.temp_token_account_pubkey)
+            .await?;
+
+        let (legacy_pda_account_pubkey, _) = find_legacy_authority_address(&self.escrow_program_id);
+
+        let instructions = [escrow_program::instruction::migrate(
+            self.escrow_program_id,
+            self.authority.pubkey(),
+            escrow_account_pubkey,
+            system_program::id(),
+            escrow_state.temp_token_account_pubkey,
+            legacy_pda_account_pubkey,
+            send_token_program_id,
+        )];
+
+        let signature = self.send_instructions(&instructions).await?;
+        Ok(signature)
+    }
+
+    /// List the open escrows matching the filter, along with their addresses.
+    ///
+    /// The seller and the seller token account are matched by the RPC node. The mint and the
+    /// minimum amount are not stored in a way the node can compare, so they are matched by the
+    /// client. Escrow accounts that have not been migrated to the newest layout are not listed.
+    pub async fn list(&self, filter: &ListFilter) -> Result<Vec<(Pubkey, Escrow)>> {
+        let mut header = Escrow::DISCRIMINATOR.to_vec();
+        header.push(Escrow::VERSION);
+        header.push(1);
+        let mut filters = vec![
+            RpcFilterType::DataSize(Escrow::LEN as u64),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, header)),
+        ];
+        if let Some(seller_pubkey) = filter.seller {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                SELLER_OFFSET,
+                seller_pubkey.to_bytes().to_vec(),
+            )));
+        }
+        if let Some(seller_token_account_pubkey) = filter.seller_token_account {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                SELLER_TOKEN_ACCOUNT_OFFSET,
+                seller_token_account_pubkey.to_bytes().to_vec(),
+            )));
+        }
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                commitment: Some(self.client.commitment()),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let accounts = self
+            .client
+            .get_program_accounts_with_config(&self.escrow_program_id, config)
+            .await?;
+
+        let mut escrows = accounts
+            .into_iter()
+            .map(|(pubkey, account)| Ok((pubkey, Escrow::unpack(&account.data)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(min_amount) = filter.min_amount {
+            escrows.retain(|(_, escrow)| escrow.amount >= min_amount);
+        }
+        if let Some(mint_pubkey) = filter.mint {
+            let temp_token_account_pubkeys = escrows
+                .iter()
+                .map(|(_, escrow)| escrow.temp_token_account_pubkey)
+                .collect::<Vec<_>>();
+            let mut temp_token_accounts = Vec::with_capacity(temp_token_account_pubkeys.len());
+            for pubkeys in temp_token_account_pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+                temp_token_accounts.extend(self.client.get_multiple_accounts(pubkeys).await?);
+            }
+            escrows = escrows
+                .into_iter()
+                .zip(temp_token_accounts)
+                .filter(|(_, temp_token_account)| {
+                    temp_token_account
+                        .as_ref()
+                        .and_then(|account| {
+                            StateWithExtensions::<Account>::unpack(&account.data).ok()
+                        })
+                        .is_some_and(|state| state.base.mint == mint_pubkey)
+                })
+                .map(|(escrow, _)| escrow)
+                .collect();
+        }
+
+        Ok(escrows)
+    }
+
+    /// Get the details of the mint, including the name and symbol of its token metadata if any.
+    pub async fn mint(&self, mint_pubkey: Pubkey) -> Result<MintInfo> {
+        let account = self.client.get_account(&mint_pubkey).await?;
+        let mint = StateWithExtensions::<Mint>::unpack(&account.data)?;
+        let metadata = mint.get_variable_len_extension::<TokenMetadata>().ok();
+
+        Ok(MintInfo {
+            mint_pubkey,
+            token_program_id: account.owner,
+            decimals: mint.base.decimals,
+            name: metadata.as_ref().map(|metadata| metadata.name.clone()),
+            symbol: metadata.map(|metadata| metadata.symbol),
+        })
+    }
+
+    /// Get the mints of the tokens deposited in the escrow and of the tokens expected by the
+    /// seller.
+    pub async fn escrow_mints(&self, escrow_state: &Escrow) -> Result<(Pubkey, Pubkey)> {
+        let (_, temp_token_account_state) = self
+            .token_account(&escrow_state.temp_token_account_pubkey)
+            .await?;
+        let (_, seller_token_account_state) = self
+            .token_account(&escrow_state.seller_token_account_pubkey)
+            .await?;
+
+        Ok((
+            temp_token_account_state.mint,
+            seller_token_account_state.mint,
+        ))
+    }
+
+    /// Get the addresses of the escrow and of its tokens, to build its transactions offline.
+    pub async fn escrow_addresses(&self, escrow_account_pubkey: Pubkey) -> Result<EscrowAddresses> {
+        let escrow_state = self.escrow_state(&escrow_account_pubkey).await?;
+        let (send_token_program_id, temp_token_account_state) = self
+            .token_account(&escrow_state.temp_token_account_pubkey)
+            .await?;
+        let (receive_token_program_id, seller_token_account_state) = self
+            .token_account(&escrow_state.seller_token_account_pubkey)
+            .await?;
+
+        Ok(EscrowAddresses {
+            escrow_account_pubkey,
+            seller_pubkey: escrow_state.seller_pubkey,
+            seller_token_account_pubkey: escrow_state.seller_token_account_pubkey,
+            send_mint_pubkey: temp_token_account_state.mint,
+            send_token_program_id,
+            receive_mint_pubkey: seller_token_account_state.mint,
+            receive_token_program_id,
+        })
+    }
+
+    /// Get the escrow account state, in the newest layout whatever the layout of the account.
+    ///
+    /// An escrow in the first layout is returned as the `Migrate` instruction would rewrite it.
+    pub async fn account(&self, account_pubkey: Pubkey) -> Result<Escrow> {
+        self.escrow_state(&account_pubkey).await
+    }
+
+    /// Get the events logged by the escrow program in the confirmed transaction.
+    ///
+    /// The transaction is fetched with the commitment of the RPC client, or `confirmed` if it is
+    /// lower, as transactions cannot be fetched at the `processed` commitment.
+    pub async fn events(&self, signature: &Signature) -> Result<Vec<EscrowEvent>> {
+        let commitment = if self.client.commitment().is_at_least_confirmed() {
+            self.client.commitment()
+        } else {
+            CommitmentConfig::confirmed()
+        };
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(commitment),
+            max_supported_transaction_version: Some(0),
+        };
+        let tx = self
+            .client
+            .get_transaction_with_config(signature, config)
+            .await?;
+        let log_messages = tx
+            .transaction
+            .meta
+            .map(|meta| meta.log_messages.unwrap_or_else(Vec::new))
+            .unwrap_or_default();
+
+        parse_events(&log_messages, &self.escrow_program_id)
+    }
+
+    /// Sign the transaction with the signers of the client that it requires.
+    ///
+    /// Signatures of other signers are left untouched, so the transaction can be signed by
+    /// several parties before it is sent. Signers without a key, such as a `NullSigner`, do not
+    /// sign.
+    pub fn sign_transaction(&self, tx: &mut Transaction) -> Result<()> {
+        let message_data = tx.message_data();
+        let signer_pubkeys =
+            &tx.message.account_keys[..tx.message.header.num_required_signatures as usize];
+        for signer in [
+            Some(&self.authority),
+            self.fee_payer.as_ref(),
+            self.nonce
+                .as_ref()
+                .map(|(_, nonce_authority)| nonce_authority),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            let Some(position) = signer_pubkeys
+                .iter()
+                .position(|pubkey| *pubkey == signer.pubkey())
+            else {
+                continue;
+            };
+            let signature = signer.try_sign_message(&message_data)?;
+            if signature != Signature::default() {
+                tx.signatures[position] = signature;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send the fully signed transaction and wait for its confirmation.
+    pub async fn send_transaction(&self, tx: &Transaction) -> Result<Signature> {
         let signature = self
             .client
             .send_and_confirm_transaction_with_spinner_and_config(
-                &tx,
+                tx,
                 self.client.commitment(),
-                self.rpc_send_transaction_config.clone(),
+                self.rpc_send_transaction_config,
             )
-            .await?;
+            .await
+            .map_err(|err| {
+                ClientError::from(err).decode_escrow_error(&tx.message, &self.escrow_program_id)
+            })?;
         Ok(signature)
     }
 
-    /// Get the escrow account state.
-    pub async fn account(&self, account_pubkey: Pubkey) -> Result<Escrow> {
-        let account = self.client.get_account(&account_pubkey).await?;
-        let state = Escrow::try_from_slice(&account.data)?;
+    /// Create an unsigned transaction of the instructions paid by the fee payer.
+    ///
+    /// With a durable nonce, the transaction first advances the nonce account and uses the nonce
+    /// stored in it as its blockhash, so that it does not expire.
+    async fn unsigned_transaction(&self, instructions: &[Instruction]) -> Result<Transaction> {
+        let instructions = self.with_compute_budget(instructions).await?;
+        let blockhash = match (self.blockhash, &self.nonce) {
+            (Some(blockhash), _) => blockhash,
+            (None, Some((nonce_account_pubkey, _))) => {
+                self.nonce_blockhash(nonce_account_pubkey).await?
+            }
+            (None, None) => self.client.get_latest_blockhash().await?,
+        };
+
+        Ok(Transaction::new_unsigned(
+            self.message(&instructions, blockhash),
+        ))
+    }
+
+    /// Create an unsigned transaction of the instructions paid by the fee payer, without reading
+    /// the cluster.
+    ///
+    /// The blockhash, or the stored nonce with a durable nonce, must be given, and so must the
+    /// compute unit limit and price unless they are left to their defaults, since simulating
+    /// the transaction or fetching the recent prioritization fees would read the cluster.
+    pub fn offline_transaction(&self, instructions: &[Instruction]) -> Result<Transaction> {
+        let blockhash = self
+            .blockhash
+            .ok_or(ClientError::OfflineError("the blockhash"))?;
+        let mut compute_budget_instructions = Vec::new();
+        match self.compute_unit_price {
+            ComputeUnitPrice::Default => {}
+            ComputeUnitPrice::Static(micro_lamports) => compute_budget_instructions.push(
+                ComputeBudgetInstruction::set_compute_unit_price(micro_lamports),
+            ),
+            ComputeUnitPrice::Percentile(_) => {
+                return Err(ClientError::OfflineError("the compute unit price"))
+            }
+        }
+        match self.compute_unit_limit {
+            ComputeUnitLimit::Default => {}
+            ComputeUnitLimit::Static(units) => compute_budget_instructions
+                .push(ComputeBudgetInstruction::set_compute_unit_limit(units)),
+            ComputeUnitLimit::Simulated => {
+                return Err(ClientError::OfflineError("the compute unit limit"))
+            }
+        }
+        compute_budget_instructions.extend_from_slice(instructions);
+
+        Ok(Transaction::new_unsigned(
+            self.message(&compute_budget_instructions, blockhash),
+        ))
+    }
+
+    /// Create the message of the instructions paid by the fee payer with the blockhash, advancing
+    /// the durable nonce first if any.
+    fn message(&self, instructions: &[Instruction], blockhash: Hash) -> Message {
+        let fee_payer_pubkey = self.fee_payer_pubkey();
+        match &self.nonce {
+            Some((nonce_account_pubkey, nonce_authority)) => {
+                let mut message = Message::new_with_nonce(
+                    instructions.to_vec(),
+                    Some(&fee_payer_pubkey),
+                    nonce_account_pubkey,
+                    &nonce_authority.pubkey(),
+                );
+                message.recent_blockhash = blockhash;
+                message
+            }
+            None => Message::new_with_blockhash(instructions, Some(&fee_payer_pubkey), &blockhash),
+        }
+    }
+
+    /// Prepend the instructions setting the compute unit limit and price to the instructions.
+    async fn with_compute_budget(&self, instructions: &[Instruction]) -> Result<Vec<Instruction>> {
+        let mut compute_budget_instructions = Vec::new();
+        let compute_unit_price = match self.compute_unit_price {
+            ComputeUnitPrice::Default => None,
+            ComputeUnitPrice::Static(micro_lamports) => Some(micro_lamports),
+            ComputeUnitPrice::Percentile(percentile) => {
+                Some(self.prioritization_fee(instructions, percentile).await?)
+            }
+        };
+        if let Some(micro_lamports) = compute_unit_price {
+            compute_budget_instructions.push(ComputeBudgetInstruction::set_compute_unit_price(
+                micro_lamports,
+            ));
+        }
+
+        let compute_unit_limit = match self.compute_unit_limit {
+            ComputeUnitLimit::Default => None,
+            ComputeUnitLimit::Static(units) => Some(units),
+            ComputeUnitLimit::Simulated => {
+                let mut simulated_instructions =
+                    vec![ComputeBudgetInstruction::set_compute_unit_limit(
+                        MAX_COMPUTE_UNIT_LIMIT,
+                    )];
+                simulated_instructions.extend_from_slice(&compute_budget_instructions);
+                simulated_instructions.extend_from_slice(instructions);
+                let units = self.simulate_compute_units(&simulated_instructions).await?;
+                let units = units * (100 + SIMULATED_COMPUTE_UNIT_MARGIN) / 100;
+                Some(
+                    u32::try_from(units).map_or(MAX_COMPUTE_UNIT_LIMIT, |units| {
+                        units.min(MAX_COMPUTE_UNIT_LIMIT)
+                    }),
+                )
+            }
+        };
+        if let Some(units) = compute_unit_limit {
+            compute_budget_instructions
+                .push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+
+        compute_budget_instructions.extend_from_slice(instructions);
+        Ok(compute_budget_instructions)
+    }
+
+    /// Simulate the instructions and get the compute units they consumed.
+    ///
+    /// The transaction is simulated without verifying signatures, so it can be simulated before
+    /// it is signed.
+    async fn simulate_compute_units(&self, instructions: &[Instruction]) -> Result<u64> {
+        let message = Message::new(instructions, Some(&self.fee_payer_pubkey()));
+        let tx = Transaction::new_unsigned(message);
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(self.client.commitment()),
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let result = self
+            .client
+            .simulate_transaction_with_config(&tx, config)
+            .await?
+            .value;
+        if let Some(err) = result.err {
+            return Err(
+                ClientError::from(err).decode_escrow_error(&tx.message, &self.escrow_program_id)
+            );
+        }
+
+        Ok(result
+            .units_consumed
+            .unwrap_or(u64::from(MAX_COMPUTE_UNIT_LIMIT)))
+    }
+
+    /// Get the percentile of the prioritization fees recently paid to write the accounts that the
+    /// instructions write, in micro-lamports per compute unit.
+    async fn prioritization_fee(
+        &self,
+        instructions: &[Instruction],
+        percentile: u8,
+    ) -> Result<u64> {
+        let mut writable_pubkeys = Vec::new();
+        for account in instructions.iter().flat_map(|ix| &ix.accounts) {
+            if account.is_writable && !writable_pubkeys.contains(&account.pubkey) {
+                writable_pubkeys.push(account.pubkey);
+            }
+        }
+        let mut fees = self
+            .client
+            .get_recent_prioritization_fees(&writable_pubkeys)
+            .await?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect::<Vec<_>>();
+        fees.sort_unstable();
 
-        Ok(state)
+        Ok(percentile_of(&fees, percentile))
+    }
+
+    /// Get the durable nonce stored in the nonce account.
+    async fn nonce_blockhash(&self, nonce_account_pubkey: &Pubkey) -> Result<Hash> {
+        let account = nonce_utils::get_account_with_commitment(
+            &self.client,
+            nonce_account_pubkey,
+            self.client.commitment(),
+        )
+        .await?;
+        let data = nonce_utils::data_from_account(&account)?;
+
+        Ok(data.blockhash())
+    }
+
+    /// Sign and send the instructions in a transaction paid by the fee payer.
+    async fn send_instructions(&self, instructions: &[Instruction]) -> Result<Signature> {
+        let mut tx = self.unsigned_transaction(instructions).await?;
+        self.sign_transaction(&mut tx)?;
+
+        self.send_transaction(&tx).await
+    }
+
+    /// Get the address of the account paying the transaction fees.
+    fn fee_payer_pubkey(&self) -> Pubkey {
+        self.fee_payer.as_ref().unwrap_or(&self.authority).pubkey()
+    }
+
+    /// Create an instruction to create the associated token account of the owner if missing.
+    fn create_associated_token_account_instruction(
+        &self,
+        owner_pubkey: &Pubkey,
+        mint_pubkey: &Pubkey,
+        token_program_id: &Pubkey,
+    ) -> Instruction {
+        create_associated_token_account_idempotent(
+            &self.authority.pubkey(),
+            owner_pubkey,
+            mint_pubkey,
+            token_program_id,
+        )
     }
+
+    /// Add instructions to create the associated token accounts of the authority for the mints that
+    /// do not exist yet, and return the addresses of the accounts to be created.
+    async fn create_missing_token_accounts(
+        &self,
+        instructions: &mut Vec<Instruction>,
+        mints: &[(Pubkey, Pubkey)],
+    ) -> Result<Vec<Pubkey>> {
+        let token_account_pubkeys = mints
+            .iter()
+            .map(|(mint_pubkey, token_program_id)| {
+                get_associated_token_address_with_program_id(
+                    &self.authority.pubkey(),
+                    mint_pubkey,
+                    token_program_id,
+                )
+            })
+            .collect::<Vec<_>>();
+        let token_accounts = self
+            .client
+            .get_multiple_accounts(&token_account_pubkeys)
+            .await?;
+
+        let mut created_token_accounts = Vec::new();
+        for (((mint_pubkey, token_program_id), token_account_pubkey), token_account) in
+            mints.iter().zip(token_account_pubkeys).zip(token_accounts)
+        {
+            if token_account.is_none() && !created_token_accounts.contains(&token_account_pubkey) {
+                instructions.push(self.create_associated_token_account_instruction(
+                    &self.authority.pubkey(),
+                    mint_pubkey,
+                    token_program_id,
+                ));
+                created_token_accounts.push(token_account_pubkey);
+            }
+        }
+
+        Ok(created_token_accounts)
+    }
+
+    /// Create instructions to wrap lamports of the authority into its associated token account of
+    /// the native mint, which must exist.
+    fn wrap_instructions(
+        &self,
+        mint_pubkey: &Pubkey,
+        token_program_id: &Pubkey,
+        lamports: u64,
+    ) -> Result<Vec<Instruction>> {
+        let token_account_pubkey = get_associated_token_address_with_program_id(
+            &self.authority.pubkey(),
+            mint_pubkey,
+            token_program_id,
+        );
+
+        Ok(vec![
+            system_instruction::transfer(&self.authority.pubkey(), &token_account_pubkey, lamports),
+            spl_token_2022::instruction::sync_native(token_program_id, &token_account_pubkey)?,
+        ])
+    }
+
+    /// Create an instruction to close the associated token account of the authority for the native
+    /// mint, unwrapping its balance back to SOL, if the transaction creates the account.
+    ///
+    /// An account that existed before may hold wrapped SOL of the authority or receive the tokens
+    /// of one of its escrows, so it is left open, as is the token account receiving the tokens of
+    /// the escrow the transaction is for.
+    fn unwrap_instruction(
+        &self,
+        mint_pubkey: &Pubkey,
+        token_program_id: &Pubkey,
+        created_token_accounts: &[Pubkey],
+        seller_token_account_pubkey: &Pubkey,
+    ) -> Result<Option<Instruction>> {
+        let token_account_pubkey = get_associated_token_address_with_program_id(
+            &self.authority.pubkey(),
+            mint_pubkey,
+            token_program_id,
+        );
+        if !created_token_accounts.contains(&token_account_pubkey)
+            || token_account_pubkey == *seller_token_account_pubkey
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(spl_token_2022::instruction::close_account(
+            token_program_id,
+            &token_account_pubkey,
+            &self.authority.pubkey(),
+            &self.authority.pubkey(),
+            &[],
+        )?))
+    }
+
+    /// Get the token program of the mint, the one that owns it unless overridden.
+    async fn mint_token_program_id(&self, mint_pubkey: &Pubkey) -> Result<Pubkey> {
+        if let Some(token_program_id) = self.token_program_id {
+            return Ok(token_program_id);
+        }
+        let account = self.client.get_account(mint_pubkey).await?;
+
+        Ok(account.owner)
+    }
+
+    /// Get the transfer fee charged by the mint in the current epoch, if any.
+    async fn transfer_fee(&self, mint_pubkey: &Pubkey) -> Result<Option<TransferFee>> {
+        let account = self.client.get_account(mint_pubkey).await?;
+        let mint = StateWithExtensions::<Mint>::unpack(&account.data)?;
+        match mint.get_extension::<TransferFeeConfig>() {
+            Ok(transfer_fee_config) => {
+                let epoch = self.client.get_epoch_info().await?.epoch;
+                Ok(Some(*transfer_fee_config.get_epoch_fee(epoch)))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Get the escrow account state in the newest layout, as migrated if it is in the first one.
+    async fn escrow_state(&self, escrow_account_pubkey: &Pubkey) -> Result<Escrow> {
+        let account = self.client.get_account(escrow_account_pubkey).await?;
+
+        match EscrowAccount::decode(&account.data)? {
+            EscrowAccount::V1(escrow) => {
+                // The first layout does not record the deposit, which is held by the vault
+                let (_, temp_token_account_state) = self
+                    .token_account(&escrow.temp_token_account_pubkey)
+                    .await?;
+                let (_, bump) =
+                    find_authority_address(escrow_account_pubkey, &self.escrow_program_id);
+                Ok(escrow.migrate(temp_token_account_state.amount, bump))
+            }
+            EscrowAccount::V2(escrow) => Ok(escrow),
+        }
+    }
+
+    /// Get the token account state along with its token program, the one that owns it unless
+    /// overridden.
+    async fn token_account(&self, account_pubkey: &Pubkey) -> Result<(Pubkey, Account)> {
+        let account = self.client.get_account(account_pubkey).await?;
+        let state = StateWithExtensions::<Account>::unpack(&account.data)?.base;
+
+        Ok((self.token_program_id.unwrap_or(account.owner), state))
+    }
+}
+
+/// Filter of the escrows to list.
+///
+/// Each filter that is set must match; an empty filter lists all open escrows.
+#[derive(Clone, Debug, Default)]
+pub struct ListFilter {
+    /// Seller who initialized the escrow.
+    pub seller: Option<Pubkey>,
+
+    /// Token account of the seller receiving the tokens.
+    pub seller_token_account: Option<Pubkey>,
+
+    /// Mint of the deposited tokens.
+    pub mint: Option<Pubkey>,
+
+    /// Minimum amount of tokens expected by the seller.
+    pub min_amount: Option<u64>,
+}
+
+/// Addresses of an escrow and of its tokens, given to build its transactions without reading the
+/// cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EscrowAddresses {
+    /// Address of the escrow account.
+    pub escrow_account_pubkey: Pubkey,
+
+    /// Seller who initialized the escrow.
+    pub seller_pubkey: Pubkey,
+
+    /// Token account of the seller receiving the tokens.
+    pub seller_token_account_pubkey: Pubkey,
+
+    /// Mint of the deposited tokens.
+    pub send_mint_pubkey: Pubkey,
+
+    /// Token program of the deposited tokens.
+    pub send_token_program_id: Pubkey,
+
+    /// Mint of the tokens expected by the seller.
+    pub receive_mint_pubkey: Pubkey,
+
+    /// Token program of the tokens expected by the seller.
+    pub receive_token_program_id: Pubkey,
+}
+
+/// Details of a mint.
+#[derive(Clone, Debug)]
+pub struct MintInfo {
+    /// Address of the mint.
+    pub mint_pubkey: Pubkey,
+
+    /// Token program that owns the mint.
+    pub token_program_id: Pubkey,
+
+    /// Number of decimals of the token.
+    pub decimals: u8,
+
+    /// Name of the token, if the mint has the token metadata extension.
+    pub name: Option<String>,
+
+    /// Symbol of the token, if the mint has the token metadata extension.
+    pub symbol: Option<String>,
+}
+
+/// Result of initializing an escrow.
+#[derive(Debug)]
+pub struct InitResult {
+    /// Signature of the transaction.
+    pub signature: Signature,
+
+    /// Address of the escrow account.
+    pub escrow_account_pubkey: Pubkey,
+
+    /// Associated token accounts of the seller created by the transaction.
+    pub created_token_accounts: Vec<Pubkey>,
+}
+
+/// Result of exchanging an escrow.
+///
+/// The associated token accounts of the native mint created by the transaction are closed again
+/// after unwrapping SOL, while those that existed before keep the wrapped SOL.
+#[derive(Debug)]
+pub struct ExchangeResult {
+    /// Signature of the transaction.
+    pub signature: Signature,
+
+    /// Associated token accounts of the buyer created by the transaction.
+    pub created_token_accounts: Vec<Pubkey>,
+
+    /// Amounts of the exchange, as computed by the client when building the transaction.
+    pub amounts: ExchangeAmounts,
+}
+
+/// Amounts of an exchange computed by the client from the escrow state and the transfer fees.
+///
+/// The amounts actually exchanged are logged by the program in the `Exchanged` event, and only
+/// differ if the escrow or the transfer fees changed before the transaction was executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExchangeAmounts {
+    /// Mint of the deposited tokens.
+    pub send_mint: Pubkey,
+
+    /// Amount of the deposited tokens taken by the buyer.
+    pub amount: u64,
+
+    /// Mint of the tokens expected by the seller.
+    pub receive_mint: Pubkey,
+
+    /// Amount of tokens paid by the buyer, including the transfer fee.
+    pub paid_amount: u64,
+
+    /// Amount of the deposited tokens remaining after the exchange.
+    pub remaining_amount: u64,
+}
+
+/// Compute unit limit of the transactions sent by the client.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ComputeUnitLimit {
+    /// Do not set a limit, so the cluster's default limit applies.
+    #[default]
+    Default,
+    /// Set the limit to the given compute units.
+    Static(u32),
+    /// Simulate the transaction and set the limit to the consumed compute units plus a margin.
+    Simulated,
+}
+
+/// Compute unit price of the transactions sent by the client, which sets their priority fee.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ComputeUnitPrice {
+    /// Do not set a price, so the transactions pay no priority fee.
+    #[default]
+    Default,
+    /// Set the price to the given micro-lamports per compute unit.
+    Static(u64),
+    /// Set the price to the percentile, from 0 to 100, of the prioritization fees recently paid
+    /// to write the accounts that the transaction writes.
+    Percentile(u8),
+}
+
+/// Get the percentile of the sorted values by the nearest rank, zero if there are none.
+///
+/// The nearest rank of the percentile `p` of `N` values is `ceil(p * N / 100)`, counted from one,
+/// and the 0th percentile is the smallest value. Percentiles above 100 are treated as 100.
+fn percentile_of(sorted_values: &[u64], percentile: u8) -> u64 {
+    if sorted_values.is_empty() {
+        return 0;
+    }
+    let rank = (sorted_values.len() * usize::from(percentile.min(100))).div_ceil(100);
+
+    sorted_values[rank.max(1) - 1]
+}
+
+/// Return whether the mint is a native mint, whose tokens are wrapped SOL.
+///
+/// The client wraps SOL into the authority's associated token account before sending it, so that
+/// native SOL can be traded on either side. The account is closed again to unwrap the SOL left in
+/// it only if the transaction created it; an account that already existed keeps the wrapped SOL,
+/// as it may hold SOL wrapped by the authority or receive the tokens of another escrow.
+pub fn is_native_mint(mint_pubkey: &Pubkey) -> bool {
+    *mint_pubkey == spl_token::native_mint::id()
+        || *mint_pubkey == spl_token_2022::native_mint::id()
 }
 
 /// Builder for the client for interacting with the escrow program.
@@ -232,8 +1572,28 @@ pub struct ClientBuilder {
     /// RPC client.
     client: Arc<RpcClient>,
 
-    /// Keypair of the payer.
-    payer: Keypair,
+    /// Signer acting as the seller or the buyer.
+    authority: Box<dyn Signer + Send + Sync>,
+
+    /// Signer paying the transaction fees.
+    /// Default is the authority.
+    fee_payer: Option<Box<dyn Signer + Send + Sync>>,
+
+    /// Recent blockhash of the transactions.
+    /// Default is the latest blockhash of the cluster, or the nonce with a durable nonce.
+    blockhash: Option<Hash>,
+
+    /// Durable nonce account and its authority.
+    /// Default is to use a recent blockhash.
+    nonce: Option<(Pubkey, Box<dyn Signer + Send + Sync>)>,
+
+    /// Compute unit limit of the transactions.
+    /// Default is the cluster's default limit.
+    compute_unit_limit: ComputeUnitLimit,
+
+    /// Compute unit price of the transactions.
+    /// Default is no priority fee.
+    compute_unit_price: ComputeUnitPrice,
 
     /// Configuration for sending transactions.
     rpc_send_transaction_config: RpcSendTransactionConfig,
@@ -242,16 +1602,21 @@ pub struct ClientBuilder {
     /// Default is the escrow program ID.
     escrow_program_id: Option<Pubkey>,
 
-    /// Token program ID.
-    /// Default is the token program ID.
+    /// Token program of the mints.
+    /// Default is the program that owns each mint.
     token_program_id: Option<Pubkey>,
 }
 
 impl ClientBuilder {
-    fn new(client: Arc<RpcClient>, payer: Keypair) -> Self {
+    fn new(client: Arc<RpcClient>, authority: Box<dyn Signer + Send + Sync>) -> Self {
         Self {
             client,
-            payer,
+            authority,
+            fee_payer: None,
+            blockhash: None,
+            nonce: None,
+            compute_unit_limit: ComputeUnitLimit::Default,
+            compute_unit_price: ComputeUnitPrice::Default,
             rpc_send_transaction_config: Default::default(),
             escrow_program_id: None,
             token_program_id: None,
@@ -271,19 +1636,88 @@ impl ClientBuilder {
         self
     }
 
+    /// Use the token program for all of the mints instead of detecting it from the owner of each
+    /// mint.
+    ///
+    /// The escrow program only accepts the SPL Token and Token-2022 programs.
     pub fn with_token_program_id(mut self, token_program_id: Pubkey) -> Self {
         self.token_program_id = Some(token_program_id);
         self
     }
 
+    pub fn with_fee_payer<S: Signer + Send + Sync + 'static>(mut self, fee_payer: S) -> Self {
+        self.fee_payer = Some(Box::new(fee_payer));
+        self
+    }
+
+    pub fn with_blockhash(mut self, blockhash: Hash) -> Self {
+        self.blockhash = Some(blockhash);
+        self
+    }
+
+    /// Build the transactions against the durable nonce account, advanced by the nonce authority.
+    pub fn with_nonce<S: Signer + Send + Sync + 'static>(
+        mut self,
+        nonce_account_pubkey: Pubkey,
+        nonce_authority: S,
+    ) -> Self {
+        self.nonce = Some((nonce_account_pubkey, Box::new(nonce_authority)));
+        self
+    }
+
+    pub fn with_compute_unit_limit(mut self, compute_unit_limit: ComputeUnitLimit) -> Self {
+        self.compute_unit_limit = compute_unit_limit;
+        self
+    }
+
+    pub fn with_compute_unit_price(mut self, compute_unit_price: ComputeUnitPrice) -> Self {
+        self.compute_unit_price = compute_unit_price;
+        self
+    }
+
     /// Build the client for interacting with the escrow program.
     pub fn build(self) -> Client {
         Client {
             client: self.client,
-            payer: self.payer,
+            authority: self.authority,
+            fee_payer: self.fee_payer,
+            blockhash: self.blockhash,
+            nonce: self.nonce,
+            compute_unit_limit: self.compute_unit_limit,
+            compute_unit_price: self.compute_unit_price,
             rpc_send_transaction_config: self.rpc_send_transaction_config,
             escrow_program_id: self.escrow_program_id.unwrap_or_else(escrow_program::id),
-            token_program_id: self.token_program_id.unwrap_or_else(spl_token::id),
+            token_program_id: self.token_program_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_of() {
+        assert_eq!(percentile_of(&[], 50), 0);
+
+        for percentile in [0, 1, 50, 100, 255] {
+            assert_eq!(percentile_of(&[7], percentile), 7);
         }
+
+        let values = [10, 20];
+        assert_eq!(percentile_of(&values, 0), 10);
+        assert_eq!(percentile_of(&values, 50), 10);
+        assert_eq!(percentile_of(&values, 51), 20);
+        assert_eq!(percentile_of(&values, 100), 20);
+
+        let values = [10, 20, 30, 40];
+        assert_eq!(percentile_of(&values, 0), 10);
+        assert_eq!(percentile_of(&values, 25), 10);
+        assert_eq!(percentile_of(&values, 26), 20);
+        assert_eq!(percentile_of(&values, 50), 20);
+        assert_eq!(percentile_of(&values, 75), 30);
+        assert_eq!(percentile_of(&values, 90), 40);
+        assert_eq!(percentile_of(&values, 100), 40);
+        assert_eq!(percentile_of(&values, 200), 40);
     }
 }
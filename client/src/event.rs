@@ -0,0 +1,73 @@
+use crate::client::Result;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use borsh::BorshDeserialize;
+use escrow_program::event::EscrowEvent;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Prefix of the log messages holding data logged with `sol_log_data`.
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Decode the events logged by the escrow program from the log messages of a transaction.
+///
+/// Only the data logged by the escrow program itself is decoded, not the data logged by the
+/// programs it invokes or by other programs of the transaction.
+///
+/// # Example
+///
+/// ```rust
+/// # use base64::Engine;
+/// # use escrow_client::parse_events;
+/// # use escrow_program::event::{EscrowCancelled, EscrowEvent};
+/// # use solana_sdk::pubkey::Pubkey;
+/// #
+/// let event = EscrowEvent::Cancelled(EscrowCancelled {
+///     escrow: Pubkey::new_unique(),
+///     seller: Pubkey::new_unique(),
+///     send_mint: Pubkey::new_unique(),
+///     amount: 100,
+///     refunded: false,
+///     slot: 1,
+/// });
+/// let data = base64::prelude::BASE64_STANDARD.encode(borsh::to_vec(&event).unwrap());
+/// let log_messages = vec![
+///     format!("Program {} invoke [1]", escrow_program::id()),
+///     format!("Program data: {}", data),
+///     format!("Program {} success", escrow_program::id()),
+/// ];
+///
+/// assert_eq!(parse_events(&log_messages, &escrow_program::id()).unwrap(), vec![event]);
+/// ```
+pub fn parse_events(
+    log_messages: &[String],
+    escrow_program_id: &Pubkey,
+) -> Result<Vec<EscrowEvent>> {
+    // Programs currently executing, the innermost last
+    let mut invoked_program_ids = Vec::new();
+    let mut events = Vec::new();
+    for log_message in log_messages {
+        if let Some(data) = log_message.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if invoked_program_ids.last() == Some(escrow_program_id) {
+                let data = BASE64_STANDARD.decode(data)?;
+                events.push(EscrowEvent::try_from_slice(&data)?);
+            }
+        } else if let Some(message) = log_message.strip_prefix("Program ") {
+            let mut words = message.split(' ');
+            let (Some(program_id), Some(status)) = (words.next(), words.next()) else {
+                continue;
+            };
+            let Ok(program_id) = Pubkey::from_str(program_id) else {
+                continue;
+            };
+            match status {
+                "invoke" => invoked_program_ids.push(program_id),
+                "success" | "failed:" => {
+                    invoked_program_ids.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(events)
+}
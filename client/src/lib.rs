@@ -1,3 +1,10 @@
 mod client;
+mod event;
+mod state;
 
-pub use crate::client::{Client, ClientBuilder, ClientError, Result};
+pub use crate::client::{
+    is_native_mint, Client, ClientBuilder, ClientError, ComputeUnitLimit, ComputeUnitPrice,
+    EscrowAddresses, ExchangeAmounts, ExchangeResult, InitResult, ListFilter, MintInfo, Result,
+};
+pub use crate::event::parse_events;
+pub use crate::state::EscrowAccount;
@@ -0,0 +1,50 @@
+use crate::client::Result;
+use borsh::BorshDeserialize;
+use escrow_program::error::EscrowError;
+use escrow_program::state::{Escrow, EscrowV1};
+
+/// Escrow account state decoded from any of the layouts used by the escrow program.
+///
+/// # Example
+///
+/// ```rust
+/// # use escrow_client::EscrowAccount;
+/// # use escrow_program::state::{Escrow, EscrowV1};
+/// #
+/// let v1 = borsh::to_vec(&EscrowV1 { amount: 100, ..EscrowV1::default() }).unwrap();
+/// assert_eq!(v1.len(), EscrowV1::LEN);
+/// let account = EscrowAccount::decode(&v1).unwrap();
+/// assert_eq!(account.version(), 1);
+///
+/// let mut v2 = vec![0; Escrow::LEN];
+/// Escrow { amount: 100, ..Escrow::default() }.pack(&mut v2).unwrap();
+/// let account = EscrowAccount::decode(&v2).unwrap();
+/// assert_eq!(account.version(), Escrow::VERSION);
+/// ```
+pub enum EscrowAccount {
+    /// First deployed layout, without discriminator, whose vault is owned by the legacy PDA
+    V1(EscrowV1),
+    /// Layout with the discriminator, the version and reserved padding
+    V2(Escrow),
+}
+
+impl EscrowAccount {
+    /// Decode the escrow account data in any known layout.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(&Escrow::DISCRIMINATOR) {
+            return Ok(Self::V1(EscrowV1::try_from_slice(data)?));
+        }
+        match data.get(8) {
+            Some(&Escrow::VERSION) => Ok(Self::V2(Escrow::unpack(data)?)),
+            _ => Err(EscrowError::UnsupportedVersion.into()),
+        }
+    }
+
+    /// Version of the account layout.
+    pub fn version(&self) -> u8 {
+        match self {
+            Self::V1(_) => 1,
+            Self::V2(_) => Escrow::VERSION,
+        }
+    }
+}
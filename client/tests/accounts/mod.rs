@@ -1,5 +0,0 @@
-mod escrow;
-mod token;
-
-pub use escrow::escrow_account;
-pub use token::{associated_token_account, mint_account};
@@ -0,0 +1,271 @@
+use escrow_program::pda::{
+    find_authority_address, find_escrow_address, find_legacy_authority_address, find_vault_address,
+};
+use escrow_testkit::{accounts, Scenario};
+use solana_sdk::account::{AccountSharedData, WritableAccount};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use solana_sdk::sysvar::SysvarId;
+
+/// Token program of a mint of the fixture.
+#[derive(Clone, Copy, Debug)]
+pub enum TokenProgram {
+    Token,
+    /// Token-2022, charging the transfer fee of `(basis points, maximum fee)` if given.
+    Token2022(Option<(u16, u64)>),
+}
+
+/// Accounts of an open escrow between a seller and a buyer, and of its neighbours.
+pub struct EscrowAccounts {
+    /// Scenario of the mints, users and escrows, named `send` and `receive`, `seller` and `buyer`,
+    /// and `offer` and `expired`.
+    pub scenario: Scenario,
+    pub seller: Keypair,
+    pub buyer: Keypair,
+    pub send_mint_pubkey: Pubkey,
+    pub receive_mint_pubkey: Pubkey,
+    pub send_token_program_id: Pubkey,
+    pub receive_token_program_id: Pubkey,
+    pub seller_send_token_account_pubkey: Pubkey,
+    pub seller_receive_token_account_pubkey: Pubkey,
+    pub buyer_send_token_account_pubkey: Pubkey,
+    pub buyer_receive_token_account_pubkey: Pubkey,
+    pub escrow_account_pubkey: Pubkey,
+    pub tmp_token_account_pubkey: Pubkey,
+    pub pda: Pubkey,
+    /// Another escrow of the seller that has already expired.
+    pub expired_escrow_account_pubkey: Pubkey,
+    /// Another escrow of the seller in the first deployed layout, at a keypair address.
+    pub v1_escrow_account_pubkey: Pubkey,
+    /// Vault of the escrow in the first layout, owned by the legacy PDA.
+    pub v1_tmp_token_account_pubkey: Pubkey,
+    /// Copy of the escrow account owned by another program.
+    pub fake_escrow_account_pubkey: Pubkey,
+    /// Copy of the buyer's send token account owned by another program.
+    pub fake_token_account_pubkey: Pubkey,
+}
+
+impl EscrowAccounts {
+    /// Create the accounts with mints of the SPL Token program, along with the accounts to load
+    /// into the cluster.
+    pub fn new() -> (Self, Vec<(Pubkey, AccountSharedData)>) {
+        Self::with_token_programs(TokenProgram::Token, TokenProgram::Token)
+    }
+
+    /// Create the accounts with mints of the token programs, along with the accounts to load into
+    /// the cluster.
+    pub fn with_token_programs(
+        send_token_program: TokenProgram,
+        receive_token_program: TokenProgram,
+    ) -> (Self, Vec<(Pubkey, AccountSharedData)>) {
+        let mint = |scenario: Scenario, name, token_program| match token_program {
+            TokenProgram::Token => scenario.mint(name, 0),
+            TokenProgram::Token2022(transfer_fee) => scenario.mint_2022(name, 0, transfer_fee),
+        };
+        let scenario = mint(
+            mint(Scenario::new(), "send", send_token_program),
+            "receive",
+            receive_token_program,
+        );
+        // The expired escrow leaves the seeds 1 and 2 to the escrows opened by the tests
+        let scenario = scenario
+            .user("seller")
+            .user("buyer")
+            .fund("seller", "send", 100)
+            .fund("buyer", "receive", 100)
+            .fund("buyer", "send", 0)
+            .escrow("offer", "seller", "send", 100, "receive", 100)
+            .taker("offer", "buyer")
+            .escrow("expired", "seller", "send", 100, "receive", 100)
+            .seed("expired", 3)
+            .expires_at("expired", 0);
+        let seller = scenario.keypair("seller").insecure_clone();
+        let escrow_account_pubkey = scenario.escrow_pubkey("offer");
+        let buyer_send_token_account_pubkey = scenario.token_account_pubkey("buyer", "receive");
+        let v1_escrow_account_pubkey = Pubkey::new_unique();
+        let v1_tmp_token_account_pubkey = Pubkey::new_unique();
+        let (legacy_pda, _) = find_legacy_authority_address(&escrow_program::id());
+        let fake_escrow_account_pubkey = Pubkey::new_unique();
+        let fake_token_account_pubkey = Pubkey::new_unique();
+
+        let mut accounts = scenario.accounts();
+        let fake = |pubkey: Pubkey| {
+            let (_, account) = accounts
+                .iter()
+                .find(|(key, _)| *key == pubkey)
+                .expect("account not in the scenario");
+            let mut account = account.clone();
+            account.set_owner(Pubkey::new_unique());
+            account
+        };
+        let fake_escrow_account = fake(escrow_account_pubkey);
+        let fake_token_account = fake(buyer_send_token_account_pubkey);
+        accounts.extend([
+            (
+                v1_tmp_token_account_pubkey,
+                scenario.token_account("send", legacy_pda, 100),
+            ),
+            (
+                v1_escrow_account_pubkey,
+                accounts::escrow_v1_account(
+                    seller.pubkey(),
+                    scenario.token_account_pubkey("seller", "receive"),
+                    v1_tmp_token_account_pubkey,
+                    100,
+                ),
+            ),
+            (fake_escrow_account_pubkey, fake_escrow_account),
+            (fake_token_account_pubkey, fake_token_account),
+        ]);
+
+        (
+            Self {
+                buyer: scenario.keypair("buyer").insecure_clone(),
+                send_mint_pubkey: scenario.mint_pubkey("send"),
+                receive_mint_pubkey: scenario.mint_pubkey("receive"),
+                send_token_program_id: scenario.token_program_id("send"),
+                receive_token_program_id: scenario.token_program_id("receive"),
+                seller_send_token_account_pubkey: scenario.token_account_pubkey("seller", "send"),
+                seller_receive_token_account_pubkey: scenario
+                    .token_account_pubkey("seller", "receive"),
+                buyer_send_token_account_pubkey,
+                buyer_receive_token_account_pubkey: scenario.token_account_pubkey("buyer", "send"),
+                escrow_account_pubkey,
+                tmp_token_account_pubkey: scenario.vault_pubkey("offer"),
+                pda: scenario.authority_pubkey("offer"),
+                expired_escrow_account_pubkey: scenario.escrow_pubkey("expired"),
+                v1_escrow_account_pubkey,
+                v1_tmp_token_account_pubkey,
+                fake_escrow_account_pubkey,
+                fake_token_account_pubkey,
+                seller,
+                scenario,
+            },
+            accounts,
+        )
+    }
+
+    /// Create an instruction to open another escrow of the seller with the seed.
+    pub fn init_instruction(&self, seed: u64) -> Instruction {
+        let (escrow_account_pubkey, _) =
+            find_escrow_address(&self.seller.pubkey(), seed, &escrow_program::id());
+        let (tmp_token_account_pubkey, _) =
+            find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+        escrow_program::instruction::init(
+            escrow_program::id(),
+            self.seller.pubkey(),
+            self.seller_receive_token_account_pubkey,
+            self.seller_send_token_account_pubkey,
+            self.send_mint_pubkey,
+            tmp_token_account_pubkey,
+            escrow_account_pubkey,
+            Rent::id(),
+            self.send_token_program_id,
+            system_program::id(),
+            seed,
+            100,
+            100,
+            None,
+            None,
+        )
+    }
+
+    /// Create an instruction for the buyer to take the escrow.
+    pub fn exchange_instruction(&self) -> Instruction {
+        escrow_program::instruction::exchange(
+            escrow_program::id(),
+            self.buyer.pubkey(),
+            self.buyer_send_token_account_pubkey,
+            self.buyer_receive_token_account_pubkey,
+            self.tmp_token_account_pubkey,
+            self.seller.pubkey(),
+            self.seller_receive_token_account_pubkey,
+            self.escrow_account_pubkey,
+            self.send_token_program_id,
+            self.pda,
+            self.send_mint_pubkey,
+            self.receive_mint_pubkey,
+            self.receive_token_program_id,
+            100,
+            100,
+            100,
+        )
+    }
+
+    /// Create an instruction for the seller to cancel the escrow.
+    pub fn cancel_instruction(&self) -> Instruction {
+        escrow_program::instruction::cancel(
+            escrow_program::id(),
+            self.seller.pubkey(),
+            self.seller_send_token_account_pubkey,
+            self.tmp_token_account_pubkey,
+            self.escrow_account_pubkey,
+            self.send_token_program_id,
+            self.pda,
+            self.send_mint_pubkey,
+        )
+    }
+
+    /// Create an instruction to refund the escrow of the seller.
+    pub fn refund_instruction(&self, escrow_account_pubkey: Pubkey) -> Instruction {
+        let (tmp_token_account_pubkey, _) =
+            find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+        let (pda, _) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+        escrow_program::instruction::refund(
+            escrow_program::id(),
+            self.seller.pubkey(),
+            self.seller_send_token_account_pubkey,
+            tmp_token_account_pubkey,
+            escrow_account_pubkey,
+            self.send_token_program_id,
+            pda,
+            self.send_mint_pubkey,
+        )
+    }
+
+    /// Create an instruction for the seller to update the terms of the escrow.
+    pub fn update_instruction(
+        &self,
+        amount: Option<u64>,
+        deposit_amount: u64,
+        withdraw_amount: u64,
+        expires_at: Option<Option<i64>>,
+        taker: Option<Option<Pubkey>>,
+    ) -> Instruction {
+        escrow_program::instruction::update_terms(
+            escrow_program::id(),
+            self.seller.pubkey(),
+            self.seller_send_token_account_pubkey,
+            self.tmp_token_account_pubkey,
+            self.escrow_account_pubkey,
+            self.send_token_program_id,
+            self.pda,
+            self.send_mint_pubkey,
+            amount,
+            deposit_amount,
+            withdraw_amount,
+            expires_at,
+            taker,
+        )
+    }
+
+    /// Create an instruction to migrate the escrow with its vault, paid by the buyer.
+    pub fn migrate_instruction(
+        &self,
+        escrow_account_pubkey: Pubkey,
+        tmp_token_account_pubkey: Pubkey,
+    ) -> Instruction {
+        escrow_program::instruction::migrate(
+            escrow_program::id(),
+            self.buyer.pubkey(),
+            escrow_account_pubkey,
+            system_program::id(),
+            tmp_token_account_pubkey,
+            find_legacy_authority_address(&escrow_program::id()).0,
+            self.send_token_program_id,
+        )
+    }
+}
@@ -0,0 +1,218 @@
+//! Golden-file tests of the wire formats used by integrators.
+//!
+//! Each instruction variant and each version of the escrow account layout is serialized and
+//! compared to a hex fixture in `tests/golden`, the custom error codes to `tests/golden/errors.txt`,
+//! and the borsh schema of the program is compared to `program/schema.json`. A change to any of
+//! them fails the tests until it is reviewed and the files are regenerated with
+//! `UPDATE_GOLDEN=1 cargo test --test golden_test`.
+
+use borsh::schema::{BorshSchemaContainer, Definition, Fields};
+use borsh::BorshSchema;
+use escrow_program::error::{EscrowError, ERROR_CODE_OFFSET};
+use escrow_program::event::EscrowEvent;
+use escrow_program::state::{Escrow, EscrowV1};
+use escrow_program::Instruction;
+use serde_json::{json, Map, Value};
+use solana_sdk::decode_error::DecodeError;
+use solana_sdk::pubkey::Pubkey;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// Get the path of a file relative to the client crate.
+fn path(relative_path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative_path)
+}
+
+/// Compare the contents to the golden file, or overwrite the file if `UPDATE_GOLDEN` is set.
+fn assert_golden(path: &Path, contents: &str) {
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        return;
+    }
+
+    let golden = fs::read_to_string(path).unwrap_or_default();
+    assert!(
+        golden == contents,
+        "{} is out of date, review the change and regenerate it with UPDATE_GOLDEN=1:\n\
+         expected: {}\n\
+         actual:   {}",
+        path.display(),
+        golden.trim_end(),
+        contents.trim_end()
+    );
+}
+
+/// Compare the bytes to the hex fixture of the name in `tests/golden`.
+fn assert_golden_hex(name: &str, bytes: &[u8]) {
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    assert_golden(
+        &path(&format!("tests/golden/{name}.hex")),
+        &format!("{hex}\n"),
+    );
+}
+
+/// Compare the borsh encoding of the instruction to its hex fixture.
+fn assert_golden_instruction(name: &str, instruction: Instruction) {
+    assert_golden_hex(
+        &format!("instruction_{name}"),
+        &borsh::to_vec(&instruction).unwrap(),
+    );
+}
+
+/// Convert the definition of a type to JSON.
+fn definition_json(definition: &Definition) -> Value {
+    match definition {
+        Definition::Primitive(size) => json!({ "kind": "primitive", "size": size }),
+        Definition::Sequence {
+            length_width,
+            length_range,
+            elements,
+        } => json!({
+            "kind": "sequence",
+            "length_width": length_width,
+            "length_range": [length_range.start(), length_range.end()],
+            "elements": elements,
+        }),
+        Definition::Tuple { elements } => json!({ "kind": "tuple", "elements": elements }),
+        Definition::Enum {
+            tag_width,
+            variants,
+        } => json!({
+            "kind": "enum",
+            "tag_width": tag_width,
+            "variants": variants
+                .iter()
+                .map(|(discriminant, name, declaration)| json!({
+                    "discriminant": discriminant,
+                    "name": name,
+                    "type": declaration,
+                }))
+                .collect::<Vec<_>>(),
+        }),
+        Definition::Struct { fields } => {
+            let fields = match fields {
+                Fields::NamedFields(fields) => fields
+                    .iter()
+                    .map(|(name, declaration)| json!({ "name": name, "type": declaration }))
+                    .collect::<Vec<_>>(),
+                Fields::UnnamedFields(fields) => fields
+                    .iter()
+                    .map(|declaration| json!({ "type": declaration }))
+                    .collect::<Vec<_>>(),
+                Fields::Empty => Vec::new(),
+            };
+            json!({ "kind": "struct", "fields": fields })
+        }
+    }
+}
+
+/// Add the schema of the type to the types and definitions of the schema.
+fn add_schema<T: BorshSchema>(types: &mut Vec<Value>, definitions: &mut Map<String, Value>) {
+    let container = BorshSchemaContainer::for_type::<T>();
+    types.push(json!(container.declaration()));
+    for (declaration, definition) in container.definitions() {
+        definitions.insert(declaration.clone(), definition_json(definition));
+    }
+}
+
+#[test]
+fn test_instruction_encoding() {
+    assert_golden_instruction(
+        "initialize",
+        Instruction::Initialize {
+            seed: 1,
+            deposit_amount: 100,
+            amount: 200,
+            expires_at: Some(1_700_000_000),
+            taker: Some(Pubkey::new_from_array([1; 32])),
+        },
+    );
+    assert_golden_instruction(
+        "exchange",
+        Instruction::Exchange {
+            amount: 40,
+            expected_receive: 39,
+            max_pay: 81,
+        },
+    );
+    assert_golden_instruction("cancel", Instruction::Cancel);
+    assert_golden_instruction("refund", Instruction::Refund);
+    assert_golden_instruction("migrate", Instruction::Migrate);
+    assert_golden_instruction(
+        "update_terms",
+        Instruction::UpdateTerms {
+            amount: Some(150),
+            deposit_amount: 50,
+            withdraw_amount: 10,
+            expires_at: Some(None),
+            taker: Some(Some(Pubkey::new_from_array([2; 32]))),
+        },
+    );
+}
+
+#[test]
+fn test_escrow_layout() {
+    // First deployed layout, without discriminator
+    let escrow_v1 = EscrowV1 {
+        is_initialized: true,
+        seller_pubkey: Pubkey::new_from_array([1; 32]),
+        seller_token_account_pubkey: Pubkey::new_from_array([2; 32]),
+        temp_token_account_pubkey: Pubkey::new_from_array([3; 32]),
+        amount: 200,
+    };
+    let data = borsh::to_vec(&escrow_v1).unwrap();
+    assert_eq!(data.len(), EscrowV1::LEN);
+    assert_golden_hex("escrow_v1", &data);
+
+    let escrow = escrow_v1.migrate(100, 254);
+    let mut data = vec![0; Escrow::LEN];
+    escrow.pack(&mut data).unwrap();
+    assert_golden_hex("escrow_v1_migrated", &data);
+
+    let escrow = Escrow {
+        filled_amount: 40,
+        seed: 1,
+        expires_at: Some(1_700_000_000),
+        taker: Some(Pubkey::new_from_array([4; 32])),
+        ..escrow
+    };
+    let mut data = vec![0; Escrow::LEN];
+    escrow.pack(&mut data).unwrap();
+    assert_golden_hex("escrow_v2", &data);
+}
+
+#[test]
+fn test_error_codes() {
+    let codes = (ERROR_CODE_OFFSET..)
+        .map_while(<EscrowError as DecodeError<EscrowError>>::decode_custom_error_to_enum)
+        .map(|error| format!("{} {error:?}\n", error as u32))
+        .collect::<String>();
+
+    assert_golden(&path("tests/golden/errors.txt"), &codes);
+}
+
+#[test]
+fn test_schema() {
+    let mut types = Vec::new();
+    let mut definitions = Map::new();
+    add_schema::<Instruction>(&mut types, &mut definitions);
+    add_schema::<Escrow>(&mut types, &mut definitions);
+    add_schema::<EscrowV1>(&mut types, &mut definitions);
+    add_schema::<EscrowEvent>(&mut types, &mut definitions);
+    let schema = json!({
+        "escrow_version": Escrow::VERSION,
+        "escrow_discriminator": Escrow::DISCRIMINATOR,
+        "types": types,
+        "definitions": definitions,
+    });
+
+    assert_golden(
+        &path("../program/schema.json"),
+        &format!("{}\n", serde_json::to_string_pretty(&schema).unwrap()),
+    );
+}
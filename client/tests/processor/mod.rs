@@ -0,0 +1,89 @@
+use crate::fixture::EscrowAccounts;
+use escrow_program::processor::Processor;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::account::{Account, AccountSharedData};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use std::ops::Deref;
+
+/// The native processor of the escrow program loaded with the accounts of an open escrow.
+pub struct ProcessorFixture {
+    pub context: ProgramTestContext,
+    pub accounts: EscrowAccounts,
+}
+
+impl ProcessorFixture {
+    pub async fn start() -> anyhow::Result<Self> {
+        let (accounts, account_data) = EscrowAccounts::new();
+
+        Self::start_with_accounts(accounts, account_data).await
+    }
+
+    /// Start with the account data, such as the data created with the accounts changed by a test.
+    pub async fn start_with_accounts(
+        accounts: EscrowAccounts,
+        account_data: Vec<(Pubkey, AccountSharedData)>,
+    ) -> anyhow::Result<Self> {
+        let mut program_test = ProgramTest::new(
+            "escrow_program",
+            escrow_program::id(),
+            processor!(Processor::process),
+        );
+        // Run the native processor even if the program was built with `cargo build-sbf`
+        program_test.prefer_bpf(false);
+        for (pubkey, account) in account_data {
+            program_test.add_account(pubkey, Account::from(account));
+        }
+        let context = program_test.start_with_context().await;
+
+        Ok(Self { context, accounts })
+    }
+
+    /// Send the instruction in a transaction paid by the payer.
+    pub async fn send(&self, ix: Instruction, payer: &Keypair) -> Result<(), BanksClientError> {
+        let banks_client = self.context.banks_client.clone();
+        let blockhash = banks_client.get_latest_blockhash().await?;
+        let tx =
+            Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], blockhash);
+
+        banks_client.process_transaction(tx).await
+    }
+
+    /// Get the account, `None` if it does not exist.
+    pub async fn account(&self, pubkey: &Pubkey) -> anyhow::Result<Option<Account>> {
+        let banks_client = self.context.banks_client.clone();
+
+        Ok(banks_client.get_account(*pubkey).await?)
+    }
+
+    /// Get the amount of tokens held by the token account.
+    pub async fn token_amount(&self, pubkey: &Pubkey) -> anyhow::Result<u64> {
+        let account = self
+            .account(pubkey)
+            .await?
+            .expect("token account not found");
+
+        Ok(spl_token::state::Account::unpack(&account.data)?.amount)
+    }
+
+    /// Get the escrow account state.
+    pub async fn escrow(&self, pubkey: &Pubkey) -> anyhow::Result<escrow_program::state::Escrow> {
+        let account = self
+            .account(pubkey)
+            .await?
+            .expect("escrow account not found");
+
+        Ok(escrow_program::state::Escrow::unpack(&account.data)?)
+    }
+}
+
+impl Deref for ProcessorFixture {
+    type Target = EscrowAccounts;
+
+    fn deref(&self) -> &Self::Target {
+        &self.accounts
+    }
+}
@@ -0,0 +1,558 @@
+//! Property tests of the instruction processor, run in process against random instructions,
+//! accounts and escrow states.
+//!
+//! Each case sends one random instruction, well formed or not, with its accounts permuted and
+//! substituted by a seller, a buyer or a thief, and checks the invariants of the escrow:
+//!
+//! - Tokens are never created or destroyed, counting the transfer fees withheld in Token-2022
+//!   accounts and mints.
+//! - Only the seller, the authority of the vault and the taker of the escrow, or whoever sends the
+//!   instruction if the escrow is open to anyone, ever receive tokens.
+//! - The thief never gains lamports, nor tokens unless it takes an open escrow.
+//! - The processor never panics.
+
+#[allow(dead_code)]
+mod fixture;
+#[allow(dead_code)]
+mod processor;
+
+use crate::fixture::{EscrowAccounts, TokenProgram};
+use crate::processor::ProcessorFixture;
+use escrow_program::pda::{find_authority_address, find_escrow_address};
+use escrow_testkit::accounts;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use proptest::test_runner::TestCaseError;
+use solana_program_test::BanksClientError;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::extension::transfer_fee::{TransferFeeAmount, TransferFeeConfig};
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use std::collections::HashMap;
+
+/// Number of cases, kept low since each case starts its own bank.
+const CASES: u32 = 64;
+
+/// Party sending the instruction.
+#[derive(Clone, Copy, Debug)]
+enum Party {
+    Seller,
+    Buyer,
+    /// Neither the seller nor the buyer of the escrow.
+    Thief,
+}
+
+/// Buyer designated by the escrow, which is never the thief.
+#[derive(Clone, Copy, Debug)]
+enum Taker {
+    Buyer,
+    /// Neither the seller, the buyer nor the thief, and never signing.
+    ThirdParty,
+}
+
+/// Escrow terms and token balances loaded into the bank, not necessarily consistent.
+#[derive(Clone, Debug)]
+struct EscrowState {
+    amount: u64,
+    offered_amount: u64,
+    filled_amount: u64,
+    vault_amount: u64,
+    seller_amount: u64,
+    buyer_amount: u64,
+    expires_at: Option<i64>,
+    taker: Option<Taker>,
+    send_token_program: TokenProgram,
+    receive_token_program: TokenProgram,
+}
+
+/// Instruction built by the fixture that the random data and accounts start from.
+#[derive(Clone, Copy, Debug)]
+enum BaseInstruction {
+    Initialize(u64),
+    Exchange,
+    Cancel,
+    Refund,
+    RefundExpired,
+    UpdateTerms,
+    Migrate,
+    MigrateV1,
+}
+
+/// Changes to the accounts of the instruction.
+#[derive(Clone, Debug)]
+struct AccountMutation {
+    /// Accounts swapped with each other.
+    swaps: Vec<(Index, Index)>,
+    /// Accounts replaced by another account known to the bank.
+    substitutions: Vec<(Index, Index)>,
+}
+
+/// Strategy for an amount of tokens, mostly small but sometimes at the limit.
+fn token_amount() -> impl Strategy<Value = u64> {
+    prop_oneof![8 => 0..=300u64, 1 => Just(u64::MAX)]
+}
+
+/// Strategy for an expiry, either never, long past or far away.
+fn expires_at() -> impl Strategy<Value = Option<i64>> {
+    prop_oneof![Just(None), Just(Some(0)), Just(Some(i64::MAX))]
+}
+
+/// Strategy for the taker of an escrow, if any.
+fn taker() -> impl Strategy<Value = Option<Pubkey>> {
+    proptest::option::of(any::<[u8; 32]>().prop_map(Pubkey::new_from_array))
+}
+
+/// Strategy for the buyer designated by the escrow, if any.
+fn escrow_taker() -> impl Strategy<Value = Option<Taker>> {
+    proptest::option::of(prop_oneof![Just(Taker::Buyer), Just(Taker::ThirdParty)])
+}
+
+/// Strategy for the token program of a mint, with or without a transfer fee.
+fn token_program() -> impl Strategy<Value = TokenProgram> {
+    prop_oneof![
+        Just(TokenProgram::Token),
+        Just(TokenProgram::Token2022(None)),
+        Just(TokenProgram::Token2022(Some((100, 5)))),
+    ]
+}
+
+/// Strategy for the party sending the instruction.
+fn party() -> impl Strategy<Value = Party> {
+    prop_oneof![Just(Party::Seller), Just(Party::Buyer), Just(Party::Thief)]
+}
+
+/// Strategy for the escrow state loaded into the bank.
+fn escrow_state() -> impl Strategy<Value = EscrowState> {
+    (
+        (
+            token_amount(),
+            token_amount(),
+            token_amount(),
+            token_amount(),
+        ),
+        (token_amount(), token_amount()),
+        (expires_at(), escrow_taker()),
+        (token_program(), token_program()),
+    )
+        .prop_map(
+            |(
+                (amount, offered_amount, filled_amount, vault_amount),
+                (seller_amount, buyer_amount),
+                (expires_at, taker),
+                (send_token_program, receive_token_program),
+            )| EscrowState {
+                amount,
+                offered_amount,
+                filled_amount,
+                vault_amount,
+                seller_amount,
+                buyer_amount,
+                expires_at,
+                taker,
+                send_token_program,
+                receive_token_program,
+            },
+        )
+}
+
+/// Strategy for the instruction that the random data and accounts start from.
+fn base_instruction() -> impl Strategy<Value = BaseInstruction> {
+    prop_oneof![
+        (0..3u64).prop_map(BaseInstruction::Initialize),
+        Just(BaseInstruction::Exchange),
+        Just(BaseInstruction::Cancel),
+        Just(BaseInstruction::Refund),
+        Just(BaseInstruction::RefundExpired),
+        Just(BaseInstruction::UpdateTerms),
+        Just(BaseInstruction::Migrate),
+        Just(BaseInstruction::MigrateV1),
+    ]
+}
+
+/// Strategy for the data of an instruction, either a random encoding of any instruction or
+/// random bytes.
+fn instruction_data() -> impl Strategy<Value = Vec<u8>> {
+    let instruction = prop_oneof![
+        (
+            0..3u64,
+            token_amount(),
+            token_amount(),
+            expires_at(),
+            taker()
+        )
+            .prop_map(|(seed, deposit_amount, amount, expires_at, taker)| {
+                encode(escrow_program::Instruction::Initialize {
+                    seed,
+                    deposit_amount,
+                    amount,
+                    expires_at,
+                    taker,
+                })
+            }),
+        (token_amount(), token_amount(), token_amount()).prop_map(
+            |(amount, expected_receive, max_pay)| encode(escrow_program::Instruction::Exchange {
+                amount,
+                expected_receive,
+                max_pay,
+            })
+        ),
+        Just(encode(escrow_program::Instruction::Cancel)),
+        Just(encode(escrow_program::Instruction::Refund)),
+        Just(encode(escrow_program::Instruction::Migrate)),
+        (
+            proptest::option::of(token_amount()),
+            token_amount(),
+            token_amount(),
+            proptest::option::of(expires_at()),
+            proptest::option::of(taker()),
+        )
+            .prop_map(
+                |(amount, deposit_amount, withdraw_amount, expires_at, taker)| {
+                    encode(escrow_program::Instruction::UpdateTerms {
+                        amount,
+                        deposit_amount,
+                        withdraw_amount,
+                        expires_at,
+                        taker,
+                    })
+                }
+            ),
+    ];
+
+    prop_oneof![
+        3 => instruction,
+        1 => proptest::collection::vec(any::<u8>(), 0..64),
+    ]
+}
+
+/// Encode the instruction data.
+fn encode(instruction: escrow_program::Instruction) -> Vec<u8> {
+    borsh::to_vec(&instruction).unwrap()
+}
+
+/// Strategy for the accounts swapped and substituted in an instruction.
+fn account_mutation() -> impl Strategy<Value = AccountMutation> {
+    (
+        proptest::collection::vec(any::<(Index, Index)>(), 0..3),
+        proptest::collection::vec(any::<(Index, Index)>(), 0..3),
+    )
+        .prop_map(|(swaps, substitutions)| AccountMutation {
+            swaps,
+            substitutions,
+        })
+}
+
+/// Replace the data of the account loaded into the bank.
+fn set_account(
+    account_data: &mut [(Pubkey, AccountSharedData)],
+    pubkey: Pubkey,
+    account: AccountSharedData,
+) {
+    let (_, data) = account_data
+        .iter_mut()
+        .find(|(key, _)| *key == pubkey)
+        .expect("account not loaded");
+    *data = account;
+}
+
+/// Tokens held by the token accounts and mints of either token program among the accounts.
+struct Tokens {
+    /// Token accounts, with their mint, owner and amount.
+    token_accounts: HashMap<Pubkey, spl_token_2022::state::Account>,
+    /// Transfer fees withheld in the token accounts and in the mints, by mint.
+    withheld: HashMap<Pubkey, u128>,
+}
+
+impl Tokens {
+    async fn get(fixture: &ProcessorFixture, pubkeys: &[Pubkey]) -> anyhow::Result<Self> {
+        let mut token_accounts = HashMap::new();
+        let mut withheld = HashMap::<Pubkey, u128>::new();
+        for pubkey in pubkeys {
+            let Some(account) = fixture.account(pubkey).await? else {
+                continue;
+            };
+            if account.owner != spl_token::id() && account.owner != spl_token_2022::id() {
+                continue;
+            }
+            if let Ok(state) =
+                StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            {
+                if let Ok(extension) = state.get_extension::<TransferFeeAmount>() {
+                    *withheld.entry(state.base.mint).or_default() +=
+                        u128::from(u64::from(extension.withheld_amount));
+                }
+                token_accounts.insert(*pubkey, state.base);
+            } else if let Ok(state) =
+                StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)
+            {
+                if let Ok(extension) = state.get_extension::<TransferFeeConfig>() {
+                    *withheld.entry(*pubkey).or_default() +=
+                        u128::from(u64::from(extension.withheld_amount));
+                }
+            }
+        }
+
+        Ok(Self {
+            token_accounts,
+            withheld,
+        })
+    }
+
+    /// Get the total amount of tokens of each mint, held or withheld.
+    fn supply(&self) -> HashMap<Pubkey, u128> {
+        let mut supply = self.withheld.clone();
+        for token_account in self.token_accounts.values() {
+            *supply.entry(token_account.mint).or_default() += u128::from(token_account.amount);
+        }
+        supply.retain(|_, amount| *amount > 0);
+
+        supply
+    }
+}
+
+/// Get the lamports of the account, zero if it does not exist.
+async fn lamports(fixture: &ProcessorFixture, pubkey: &Pubkey) -> anyhow::Result<u64> {
+    Ok(fixture
+        .account(pubkey)
+        .await?
+        .map_or(0, |account| account.lamports))
+}
+
+/// Send the random instruction and check the invariants of the escrow.
+async fn check_instruction(
+    state: EscrowState,
+    base: BaseInstruction,
+    data: Option<Vec<u8>>,
+    mutation: AccountMutation,
+    payer: Party,
+) -> anyhow::Result<()> {
+    let (escrow_accounts, mut account_data) =
+        EscrowAccounts::with_token_programs(state.send_token_program, state.receive_token_program);
+    let thief = Keypair::new();
+    let third_party = Keypair::new();
+    let party_pubkey = |party: Party| match party {
+        Party::Seller => escrow_accounts.seller.pubkey(),
+        Party::Buyer => escrow_accounts.buyer.pubkey(),
+        Party::Thief => thief.pubkey(),
+    };
+    let taker_pubkey = state.taker.map(|taker| match taker {
+        Taker::Buyer => escrow_accounts.buyer.pubkey(),
+        Taker::ThirdParty => third_party.pubkey(),
+    });
+
+    // Loading the random escrow state and balances
+    let (_, bump) = find_authority_address(
+        &escrow_accounts.escrow_account_pubkey,
+        &escrow_program::id(),
+    );
+    set_account(
+        &mut account_data,
+        escrow_accounts.escrow_account_pubkey,
+        accounts::escrow_account(
+            escrow_accounts.seller.pubkey(),
+            escrow_accounts.seller_receive_token_account_pubkey,
+            escrow_accounts.tmp_token_account_pubkey,
+            state.amount,
+            state.offered_amount,
+            state.filled_amount,
+            bump,
+            0,
+            state.expires_at,
+            taker_pubkey,
+        ),
+    );
+    set_account(
+        &mut account_data,
+        escrow_accounts.tmp_token_account_pubkey,
+        escrow_accounts
+            .scenario
+            .token_account("send", escrow_accounts.pda, state.vault_amount),
+    );
+    set_account(
+        &mut account_data,
+        escrow_accounts.seller_send_token_account_pubkey,
+        escrow_accounts.scenario.token_account(
+            "send",
+            escrow_accounts.seller.pubkey(),
+            state.seller_amount,
+        ),
+    );
+    set_account(
+        &mut account_data,
+        escrow_accounts.buyer_send_token_account_pubkey,
+        escrow_accounts.scenario.token_account(
+            "receive",
+            escrow_accounts.buyer.pubkey(),
+            state.buyer_amount,
+        ),
+    );
+    account_data.extend([
+        (
+            thief.pubkey(),
+            AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+        ),
+        (
+            get_associated_token_address_with_program_id(
+                &thief.pubkey(),
+                &escrow_accounts.send_mint_pubkey,
+                &escrow_accounts.send_token_program_id,
+            ),
+            escrow_accounts
+                .scenario
+                .token_account("send", thief.pubkey(), 100),
+        ),
+        (
+            get_associated_token_address_with_program_id(
+                &thief.pubkey(),
+                &escrow_accounts.receive_mint_pubkey,
+                &escrow_accounts.receive_token_program_id,
+            ),
+            escrow_accounts
+                .scenario
+                .token_account("receive", thief.pubkey(), 100),
+        ),
+    ]);
+    let mut pubkeys = account_data
+        .iter()
+        .map(|(pubkey, _)| *pubkey)
+        .collect::<Vec<_>>();
+    pubkeys.extend([
+        spl_token::id(),
+        spl_token_2022::id(),
+        system_program::id(),
+        escrow_program::id(),
+        Pubkey::new_unique(),
+    ]);
+
+    // Only the seller, the taker and the authority of the vault of the escrow may receive
+    // tokens, including the escrow opened by an Initialize instruction, and whoever takes the
+    // escrow if it is open to anyone
+    let payer_pubkey = party_pubkey(payer);
+    let mut recipients = vec![
+        escrow_accounts.seller.pubkey(),
+        taker_pubkey.unwrap_or(payer_pubkey),
+        escrow_accounts.pda,
+    ];
+    if let BaseInstruction::Initialize(seed) = base {
+        let (escrow_account_pubkey, _) = find_escrow_address(
+            &escrow_accounts.seller.pubkey(),
+            seed,
+            &escrow_program::id(),
+        );
+        recipients.push(find_authority_address(&escrow_account_pubkey, &escrow_program::id()).0);
+    }
+
+    // Building the random instruction
+    let mut ix: Instruction = match base {
+        BaseInstruction::Initialize(seed) => escrow_accounts.init_instruction(seed),
+        BaseInstruction::Exchange => escrow_accounts.exchange_instruction(),
+        BaseInstruction::Cancel => escrow_accounts.cancel_instruction(),
+        BaseInstruction::Refund => {
+            escrow_accounts.refund_instruction(escrow_accounts.escrow_account_pubkey)
+        }
+        BaseInstruction::RefundExpired => {
+            escrow_accounts.refund_instruction(escrow_accounts.expired_escrow_account_pubkey)
+        }
+        BaseInstruction::UpdateTerms => escrow_accounts.update_instruction(None, 0, 0, None, None),
+        BaseInstruction::Migrate => escrow_accounts.migrate_instruction(
+            escrow_accounts.escrow_account_pubkey,
+            escrow_accounts.tmp_token_account_pubkey,
+        ),
+        BaseInstruction::MigrateV1 => escrow_accounts.migrate_instruction(
+            escrow_accounts.v1_escrow_account_pubkey,
+            escrow_accounts.v1_tmp_token_account_pubkey,
+        ),
+    };
+    if let Some(data) = data {
+        ix.data = data;
+    }
+    for (i, j) in &mutation.swaps {
+        let len = ix.accounts.len();
+        ix.accounts.swap(i.index(len), j.index(len));
+    }
+    for (i, j) in &mutation.substitutions {
+        let len = ix.accounts.len();
+        ix.accounts[i.index(len)].pubkey = *j.get(&pubkeys);
+    }
+    // Only the payer can sign, whoever the instruction expects
+    for account in &mut ix.accounts {
+        account.is_signer &= account.pubkey == payer_pubkey;
+    }
+    pubkeys.extend(ix.accounts.iter().map(|account| account.pubkey));
+    pubkeys.sort();
+    pubkeys.dedup();
+
+    let fixture = ProcessorFixture::start_with_accounts(escrow_accounts, account_data).await?;
+    let payer = match payer {
+        Party::Seller => &fixture.seller,
+        Party::Buyer => &fixture.buyer,
+        Party::Thief => &thief,
+    };
+    let before = Tokens::get(&fixture, &pubkeys).await?;
+    let thief_lamports = lamports(&fixture, &thief.pubkey()).await?;
+    // Failing is fine, as long as the processor neither panics nor breaks the invariants
+    match fixture.send(ix, payer).await {
+        Ok(()) | Err(BanksClientError::TransactionError(_)) => {}
+        Err(err) => anyhow::bail!("instruction was not processed: {err}"),
+    }
+    let after = Tokens::get(&fixture, &pubkeys).await?;
+
+    anyhow::ensure!(
+        lamports(&fixture, &thief.pubkey()).await? <= thief_lamports,
+        "the thief gained lamports"
+    );
+
+    anyhow::ensure!(
+        before.supply() == after.supply(),
+        "tokens were created or destroyed: {:?} -> {:?}",
+        before.supply(),
+        after.supply()
+    );
+    for (pubkey, token_account) in &after.token_accounts {
+        let before_amount = before
+            .token_accounts
+            .get(pubkey)
+            .map_or(0, |before| before.amount);
+        if token_account.amount <= before_amount {
+            continue;
+        }
+        anyhow::ensure!(
+            token_account.owner != thief.pubkey() || recipients.contains(&thief.pubkey()),
+            "the thief received {} tokens in {}",
+            token_account.amount - before_amount,
+            pubkey
+        );
+        anyhow::ensure!(
+            recipients.contains(&token_account.owner),
+            "{} owned by {} received {} tokens",
+            pubkey,
+            token_account.owner,
+            token_account.amount - before_amount
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_instruction_invariants() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    proptest!(
+        ProptestConfig::with_cases(CASES),
+        |(
+            state in escrow_state(),
+            base in base_instruction(),
+            data in proptest::option::of(instruction_data()),
+            mutation in account_mutation(),
+            payer in party()
+        )| {
+            runtime
+                .block_on(check_instruction(state, base, data, mutation, payer))
+                .map_err(|err| TestCaseError::fail(err.to_string()))?;
+        }
+    );
+}
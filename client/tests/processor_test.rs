@@ -0,0 +1,819 @@
+//! Tests of the instructions of the escrow program, run in process against the native processor.
+//!
+//! Unlike `program_test.rs`, these tests need neither a test validator nor a program built with
+//! `cargo build-sbf`, so they cover each instruction and its failures in seconds.
+
+#[allow(dead_code)]
+mod fixture;
+#[allow(dead_code)]
+mod processor;
+
+use crate::fixture::EscrowAccounts;
+use crate::processor::ProcessorFixture;
+use escrow_program::error::EscrowError;
+use escrow_program::pda::{find_authority_address, find_escrow_address, find_vault_address};
+use solana_program_test::BanksClientError;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::Signer;
+use solana_sdk::system_program;
+use solana_sdk::transaction::TransactionError;
+
+/// Assert that the transaction failed with the escrow error.
+fn assert_escrow_error(result: Result<(), BanksClientError>, expected: EscrowError) {
+    match result.map_err(|err| err.unwrap()) {
+        Err(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+            assert_eq!(code, expected as u32)
+        }
+        other => panic!("expected {:?}, got {:?}", expected, other),
+    }
+}
+
+#[tokio::test]
+async fn test_initialize() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let (escrow_account_pubkey, _) =
+        find_escrow_address(&fixture.seller.pubkey(), 1, &escrow_program::id());
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    let ix = fixture.init_instruction(1);
+
+    fixture.send(ix, &fixture.seller).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.seller_send_token_account_pubkey)
+            .await?,
+        0
+    );
+    assert_eq!(fixture.token_amount(&tmp_token_account_pubkey).await?, 100);
+
+    let escrow_account_data = fixture.escrow(&escrow_account_pubkey).await?;
+    assert!(escrow_account_data.is_initialized);
+    assert_eq!(escrow_account_data.seller_pubkey, fixture.seller.pubkey());
+    assert_eq!(
+        escrow_account_data.temp_token_account_pubkey,
+        tmp_token_account_pubkey
+    );
+    assert_eq!(escrow_account_data.amount, 100);
+    assert_eq!(escrow_account_data.offered_amount, 100);
+    assert_eq!(escrow_account_data.seed, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_prefunded() -> anyhow::Result<()> {
+    let (accounts, mut account_data) = EscrowAccounts::new();
+    let (escrow_account_pubkey, _) =
+        find_escrow_address(&accounts.seller.pubkey(), 1, &escrow_program::id());
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    // Anyone may send lamports to the addresses before the escrow is opened, below and above
+    // the rent exemption
+    account_data.push((
+        escrow_account_pubkey,
+        AccountSharedData::new(1, 0, &system_program::id()),
+    ));
+    account_data.push((
+        tmp_token_account_pubkey,
+        AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+    ));
+    let fixture = ProcessorFixture::start_with_accounts(accounts, account_data).await?;
+    let ix = fixture.init_instruction(1);
+
+    fixture.send(ix, &fixture.seller).await?;
+
+    assert_eq!(fixture.token_amount(&tmp_token_account_pubkey).await?, 100);
+    let tmp_token_account = fixture
+        .account(&tmp_token_account_pubkey)
+        .await?
+        .expect("token account not found");
+    assert_eq!(tmp_token_account.owner, spl_token::id());
+    assert_eq!(tmp_token_account.lamports, 1_000_000_000);
+    let escrow_account = fixture
+        .account(&escrow_account_pubkey)
+        .await?
+        .expect("escrow account not found");
+    assert_eq!(escrow_account.owner, escrow_program::id());
+    assert_eq!(
+        escrow_account.lamports,
+        Rent::default().minimum_balance(escrow_program::state::Escrow::LEN)
+    );
+    let escrow_account_data = fixture.escrow(&escrow_account_pubkey).await?;
+    assert!(escrow_account_data.is_initialized);
+    assert_eq!(escrow_account_data.offered_amount, 100);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.exchange_instruction();
+
+    fixture.send(ix, &fixture.buyer).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.buyer_receive_token_account_pubkey)
+            .await?,
+        100
+    );
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.seller_receive_token_account_pubkey)
+            .await?,
+        100
+    );
+    assert!(fixture
+        .account(&fixture.tmp_token_account_pubkey)
+        .await?
+        .is_none());
+    assert!(fixture
+        .account(&fixture.escrow_account_pubkey)
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.cancel_instruction();
+
+    fixture.send(ix, &fixture.seller).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.seller_send_token_account_pubkey)
+            .await?,
+        200
+    );
+    assert!(fixture
+        .account(&fixture.tmp_token_account_pubkey)
+        .await?
+        .is_none());
+    assert!(fixture
+        .account(&fixture.escrow_account_pubkey)
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_seller_not_signer() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.init_instruction(1);
+    ix.accounts[0].is_signer = false;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::SellerNotSigner);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_fake_token_program() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.init_instruction(1);
+    ix.accounts[7].pubkey = Pubkey::new_unique();
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::InvalidTokenProgram);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_fake_system_program() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.init_instruction(1);
+    ix.accounts[8].pubkey = Pubkey::new_unique();
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::InvalidSystemProgram);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_wrong_token_account_owner() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.init_instruction(1);
+    ix.accounts[2].pubkey = fixture.fake_token_account_pubkey;
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::TokenProgramMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_wrong_escrow_address() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.init_instruction(1);
+    ix.accounts[5].pubkey =
+        find_escrow_address(&fixture.seller.pubkey(), 2, &escrow_program::id()).0;
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::EscrowAddressMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_already_initialized() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.init_instruction(0);
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::AlreadyInitialized);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_expired() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.init_instruction(1);
+    ix.data = borsh::to_vec(&escrow_program::Instruction::Initialize {
+        seed: 1,
+        deposit_amount: 100,
+        amount: 100,
+        expires_at: Some(0),
+        taker: None,
+    })?;
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::Expired);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_buyer_not_signer() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[0].is_signer = false;
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::BuyerNotSigner);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_wrong_escrow_owner() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[6].pubkey = fixture.fake_escrow_account_pubkey;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::InvalidEscrowOwner);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_wrong_token_account_owner() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[1].pubkey = fixture.fake_token_account_pubkey;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::TokenProgramMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_fake_pda() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[8].pubkey = Pubkey::new_unique();
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::AuthorityMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_fake_token_program() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[7].pubkey = Pubkey::new_unique();
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::InvalidTokenProgram);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_wrong_seller() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[4].pubkey = fixture.buyer.pubkey();
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::SellerMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_wrong_taker() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[0].pubkey = fixture.seller.pubkey();
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::TakerMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_expired() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let (tmp_token_account_pubkey, _) = find_vault_address(
+        &fixture.expired_escrow_account_pubkey,
+        &escrow_program::id(),
+    );
+    let (pda, _) = find_authority_address(
+        &fixture.expired_escrow_account_pubkey,
+        &escrow_program::id(),
+    );
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[3].pubkey = tmp_token_account_pubkey;
+    ix.accounts[6].pubkey = fixture.expired_escrow_account_pubkey;
+    ix.accounts[8].pubkey = pda;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::Expired);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_unmigrated() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.accounts[6].pubkey = fixture.v1_escrow_account_pubkey;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::InvalidDiscriminator);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_partial() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.data = borsh::to_vec(&escrow_program::Instruction::Exchange {
+        amount: 40,
+        expected_receive: 40,
+        max_pay: 40,
+    })?;
+    fixture.send(ix, &fixture.buyer).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.buyer_send_token_account_pubkey)
+            .await?,
+        60
+    );
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.tmp_token_account_pubkey)
+            .await?,
+        60
+    );
+    let escrow_account_data = fixture.escrow(&fixture.escrow_account_pubkey).await?;
+    assert_eq!(escrow_account_data.offered_amount, 100);
+    assert_eq!(escrow_account_data.filled_amount, 40);
+
+    // Taking the rest fills the escrow and closes the accounts
+    let mut ix = fixture.exchange_instruction();
+    ix.data = borsh::to_vec(&escrow_program::Instruction::Exchange {
+        amount: 60,
+        expected_receive: 60,
+        max_pay: 60,
+    })?;
+    fixture.send(ix, &fixture.buyer).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.buyer_receive_token_account_pubkey)
+            .await?,
+        100
+    );
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.seller_receive_token_account_pubkey)
+            .await?,
+        100
+    );
+    assert!(fixture
+        .account(&fixture.tmp_token_account_pubkey)
+        .await?
+        .is_none());
+    assert!(fixture
+        .account(&fixture.escrow_account_pubkey)
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_amount_exceeds_remaining() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.data = borsh::to_vec(&escrow_program::Instruction::Exchange {
+        amount: 101,
+        expected_receive: 101,
+        max_pay: 101,
+    })?;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::AmountMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_receive_below_expected() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.data = borsh::to_vec(&escrow_program::Instruction::Exchange {
+        amount: 100,
+        expected_receive: 101,
+        max_pay: 100,
+    })?;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::ReceiveBelowExpected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_pay_exceeds_maximum() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.data = borsh::to_vec(&escrow_program::Instruction::Exchange {
+        amount: 100,
+        expected_receive: 100,
+        max_pay: 99,
+    })?;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::PayExceedsMaximum);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_seller_not_signer() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.cancel_instruction();
+    ix.accounts[0].is_signer = false;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::SellerNotSigner);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_wrong_seller() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.cancel_instruction();
+    ix.accounts[0].pubkey = fixture.buyer.pubkey();
+    ix.accounts[1].pubkey = fixture.buyer_receive_token_account_pubkey;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::SellerMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_wrong_escrow_owner() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.cancel_instruction();
+    ix.accounts[3].pubkey = fixture.fake_escrow_account_pubkey;
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::InvalidEscrowOwner);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_fake_pda() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.cancel_instruction();
+    ix.accounts[5].pubkey = Pubkey::new_unique();
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::AuthorityMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_fake_token_program() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.cancel_instruction();
+    ix.accounts[4].pubkey = Pubkey::new_unique();
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::InvalidTokenProgram);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refund() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let (tmp_token_account_pubkey, _) = find_vault_address(
+        &fixture.expired_escrow_account_pubkey,
+        &escrow_program::id(),
+    );
+    let ix = fixture.refund_instruction(fixture.expired_escrow_account_pubkey);
+
+    // Anyone may refund an expired escrow, not only the seller
+    fixture.send(ix, &fixture.buyer).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.seller_send_token_account_pubkey)
+            .await?,
+        200
+    );
+    assert!(fixture.account(&tmp_token_account_pubkey).await?.is_none());
+    assert!(fixture
+        .account(&fixture.expired_escrow_account_pubkey)
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refund_not_expired() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.refund_instruction(fixture.escrow_account_pubkey);
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::NotExpired);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refund_wrong_seller_token_account() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.refund_instruction(fixture.expired_escrow_account_pubkey);
+    ix.accounts[1].pubkey = fixture.buyer_receive_token_account_pubkey;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::SellerTokenAccountMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.migrate_instruction(
+        fixture.v1_escrow_account_pubkey,
+        fixture.v1_tmp_token_account_pubkey,
+    );
+
+    fixture.send(ix, &fixture.buyer).await?;
+
+    let escrow_account = fixture
+        .account(&fixture.v1_escrow_account_pubkey)
+        .await?
+        .expect("escrow account not found");
+    assert_eq!(
+        escrow_account.data.len(),
+        escrow_program::state::Escrow::LEN
+    );
+    assert_eq!(
+        escrow_account.lamports,
+        Rent::default().minimum_balance(escrow_program::state::Escrow::LEN)
+    );
+    let (pda, bump) =
+        find_authority_address(&fixture.v1_escrow_account_pubkey, &escrow_program::id());
+    let escrow_account_data = escrow_program::state::Escrow::unpack(&escrow_account.data)?;
+    assert!(escrow_account_data.is_initialized);
+    assert_eq!(escrow_account_data.seller_pubkey, fixture.seller.pubkey());
+    assert_eq!(
+        escrow_account_data.temp_token_account_pubkey,
+        fixture.v1_tmp_token_account_pubkey
+    );
+    assert_eq!(escrow_account_data.amount, 100);
+    assert_eq!(escrow_account_data.offered_amount, 100);
+    assert_eq!(escrow_account_data.filled_amount, 0);
+    assert_eq!(escrow_account_data.bump, bump);
+    assert_eq!(escrow_account_data.seed, 0);
+
+    // The vault is moved from the legacy PDA to the PDA of the escrow
+    let tmp_token_account = fixture
+        .account(&fixture.v1_tmp_token_account_pubkey)
+        .await?
+        .expect("temp token account not found");
+    assert_eq!(
+        spl_token::state::Account::unpack(&tmp_token_account.data)?.owner,
+        pda
+    );
+
+    // The migrated escrow can be cancelled like any other
+    let mut ix = fixture.cancel_instruction();
+    ix.accounts[2].pubkey = fixture.v1_tmp_token_account_pubkey;
+    ix.accounts[3].pubkey = fixture.v1_escrow_account_pubkey;
+    ix.accounts[5].pubkey = pda;
+    fixture.send(ix, &fixture.seller).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.seller_send_token_account_pubkey)
+            .await?,
+        200
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_wrong_vault() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.migrate_instruction(
+        fixture.v1_escrow_account_pubkey,
+        fixture.tmp_token_account_pubkey,
+    );
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::TempAccountMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_wrong_legacy_authority() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.migrate_instruction(
+        fixture.v1_escrow_account_pubkey,
+        fixture.v1_tmp_token_account_pubkey,
+    );
+    ix.accounts[4].pubkey = fixture.pda;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::AuthorityMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_newest_layout() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let before = fixture
+        .account(&fixture.escrow_account_pubkey)
+        .await?
+        .unwrap();
+
+    let ix = fixture.migrate_instruction(
+        fixture.escrow_account_pubkey,
+        fixture.tmp_token_account_pubkey,
+    );
+    fixture.send(ix, &fixture.buyer).await?;
+
+    // The escrow is left as is, although loading it marks it as rent exempt
+    let after = fixture
+        .account(&fixture.escrow_account_pubkey)
+        .await?
+        .unwrap();
+    assert_eq!(after.data, before.data);
+    assert_eq!(after.lamports, before.lamports);
+    assert_eq!(after.owner, before.owner);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_terms() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.update_instruction(Some(150), 50, 0, Some(Some(i64::MAX)), Some(None));
+
+    fixture.send(ix, &fixture.seller).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.seller_send_token_account_pubkey)
+            .await?,
+        50
+    );
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.tmp_token_account_pubkey)
+            .await?,
+        150
+    );
+    let escrow_account_data = fixture.escrow(&fixture.escrow_account_pubkey).await?;
+    assert_eq!(escrow_account_data.amount, 150);
+    assert_eq!(escrow_account_data.offered_amount, 150);
+    assert_eq!(escrow_account_data.filled_amount, 0);
+    assert_eq!(escrow_account_data.expires_at, Some(i64::MAX));
+    assert_eq!(escrow_account_data.taker, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_terms_after_partial_fill() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.exchange_instruction();
+    ix.data = borsh::to_vec(&escrow_program::Instruction::Exchange {
+        amount: 40,
+        expected_receive: 40,
+        max_pay: 40,
+    })?;
+    fixture.send(ix, &fixture.buyer).await?;
+
+    // Withdrawing half of the remaining tokens keeps the rate of the remaining tokens
+    let ix = fixture.update_instruction(None, 0, 30, None, None);
+    fixture.send(ix, &fixture.seller).await?;
+
+    assert_eq!(
+        fixture
+            .token_amount(&fixture.seller_send_token_account_pubkey)
+            .await?,
+        130
+    );
+    let escrow_account_data = fixture.escrow(&fixture.escrow_account_pubkey).await?;
+    assert_eq!(escrow_account_data.amount, 30);
+    assert_eq!(escrow_account_data.offered_amount, 30);
+    assert_eq!(escrow_account_data.filled_amount, 0);
+    assert_eq!(escrow_account_data.taker, Some(fixture.buyer.pubkey()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_terms_seller_not_signer() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.update_instruction(Some(150), 0, 0, None, None);
+    ix.accounts[0].is_signer = false;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::SellerNotSigner);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_terms_wrong_seller() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let mut ix = fixture.update_instruction(Some(150), 0, 0, None, None);
+    ix.accounts[0].pubkey = fixture.buyer.pubkey();
+    ix.accounts[1].pubkey = fixture.buyer_receive_token_account_pubkey;
+
+    let result = fixture.send(ix, &fixture.buyer).await;
+    assert_escrow_error(result, EscrowError::SellerMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_terms_withdraw_all() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.update_instruction(None, 0, 100, None, None);
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::AmountMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_terms_expired() -> anyhow::Result<()> {
+    let fixture = ProcessorFixture::start().await?;
+    let ix = fixture.update_instruction(None, 0, 0, Some(Some(0)), None);
+
+    let result = fixture.send(ix, &fixture.seller).await;
+    assert_escrow_error(result, EscrowError::Expired);
+
+    Ok(())
+}
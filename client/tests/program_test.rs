@@ -1,18 +1,44 @@
-mod accounts;
-mod validator;
+//! End-to-end tests of the client against a test validator running the program built with
+//! `cargo build-sbf`.
+//!
+//! The failures of each instruction are covered in process by `processor_test.rs`. These tests
+//! cover the paths through the RPC node: sending and simulating transactions, decoding their
+//! errors and events, listing escrows and durable nonces.
 
-use crate::validator::Validator;
-use borsh::BorshDeserialize;
+#[allow(dead_code)]
+mod fixture;
+
+use crate::fixture::EscrowAccounts;
+use escrow_client::{ClientError, ComputeUnitLimit, ComputeUnitPrice, ExchangeAmounts, ListFilter};
+use escrow_program::error::EscrowError;
+use escrow_program::event::EscrowEvent;
+use escrow_program::pda::{find_authority_address, find_escrow_address, find_vault_address};
+use escrow_testkit::{
+    accounts, assert_escrow, assert_token_balance, ExpectedEscrow, Scenario, TestValidator,
+    Validator,
+};
 use solana_rpc_client_api::config::RpcSendTransactionConfig;
-use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::{AccountSharedData, WritableAccount};
 use solana_sdk::commitment_config::CommitmentLevel;
+use solana_sdk::compute_budget;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::nonce;
 use solana_sdk::program_option::COption;
 use solana_sdk::program_pack::Pack;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
 use solana_sdk::signature::Keypair;
 use solana_sdk::signature::Signer;
+use solana_sdk::signer::null_signer::NullSigner;
+use solana_sdk::signer::presigner::Presigner;
+use solana_sdk::system_instruction;
 use solana_sdk::system_program;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use spl_token::error::TokenError;
 use spl_token::state::AccountState;
+use spl_token_2022::extension::transfer_fee::{TransferFeeAmount, TransferFeeConfig};
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use std::ops::Deref;
 use std::sync::Arc;
 
 #[tokio::test]
@@ -84,17 +110,46 @@ async fn test_initialize() -> anyhow::Result<()> {
             ..RpcSendTransactionConfig::default()
         })
         .with_escrow_program_id(escrow_program::id())
-        .with_token_program_id(spl_token::id())
         .build();
 
-    let (_, escrow_account_pubkey) = escrow
+    let result = escrow
         .init(
+            0,
             send_mint_token_account.pubkey(),
             100,
             receive_mint_token_account.pubkey(),
             100,
+            None,
+            None,
         )
         .await?;
+    let escrow_account_pubkey = result.escrow_account_pubkey;
+
+    let events = escrow.events(&result.signature).await?;
+    match events.as_slice() {
+        [EscrowEvent::Initialized(event)] => {
+            assert_eq!(event.escrow, escrow_account_pubkey);
+            assert_eq!(event.seller, payer.pubkey());
+            assert_eq!(event.send_mint, send_mint_token_account.pubkey());
+            assert_eq!(event.receive_mint, receive_mint_token_account.pubkey());
+            assert_eq!(event.offered_amount, 100);
+            assert_eq!(event.amount, 100);
+        }
+        other => panic!("unexpected events {:?}", other),
+    }
+
+    let escrow_state = escrow.account(escrow_account_pubkey).await?;
+    assert_eq!(
+        escrow.escrow_mints(&escrow_state).await?,
+        (
+            send_mint_token_account.pubkey(),
+            receive_mint_token_account.pubkey()
+        )
+    );
+    let send_mint = escrow.mint(send_mint_token_account.pubkey()).await?;
+    assert_eq!(send_mint.token_program_id, spl_token::id());
+    assert_eq!(send_mint.decimals, 0);
+    assert_eq!(send_mint.symbol, None);
 
     let send_associated_token_account = client
         .get_account(&send_associated_token_account_pubkey)
@@ -148,21 +203,29 @@ async fn test_initialize() -> anyhow::Result<()> {
         COption::None
     );
 
+    let (expected_escrow_account_pubkey, _) =
+        find_escrow_address(&payer.pubkey(), 0, &escrow_program::id());
+    assert_eq!(escrow_account_pubkey, expected_escrow_account_pubkey);
+
     let escrow_account = client.get_account(&escrow_account_pubkey).await?;
-    let escrow_account_data = escrow_program::state::Escrow::try_from_slice(&escrow_account.data)?;
-    assert_eq!(escrow_account_data.is_initialized, true);
+    let escrow_account_data = escrow_program::state::Escrow::unpack(&escrow_account.data)?;
+    assert!(escrow_account_data.is_initialized);
     assert_eq!(escrow_account_data.seller_pubkey, payer.pubkey());
     assert_eq!(
         escrow_account_data.seller_token_account_pubkey,
         receive_associated_token_account_pubkey
     );
     assert_eq!(escrow_account_data.amount, 100);
+    assert_eq!(escrow_account_data.seed, 0);
+    let (vault_pubkey, _) = find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    assert_eq!(escrow_account_data.temp_token_account_pubkey, vault_pubkey);
 
     let temp_token_account = client
         .get_account(&escrow_account_data.temp_token_account_pubkey)
         .await?;
     let temp_token_account_data = spl_token::state::Account::unpack(&temp_token_account.data)?;
-    let (pda, _) = Pubkey::find_program_address(&[b"escrow"], &escrow_program::id());
+    let (pda, bump) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+    assert_eq!(escrow_account_data.bump, bump);
     assert_eq!(
         temp_token_account_data.mint,
         send_mint_token_account.pubkey()
@@ -208,9 +271,11 @@ async fn test_exchange() -> anyhow::Result<()> {
             &send_mint_token_account.pubkey(),
             &spl_token::id(),
         );
-    let tmp_token_account = Keypair::new();
-    let escrow_account = Keypair::new();
-    let (pda, _) = Pubkey::find_program_address(&[b"escrow"], &escrow_program::id());
+    let (escrow_account_pubkey, _) =
+        find_escrow_address(&sender.pubkey(), 0, &escrow_program::id());
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    let (pda, bump) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
 
     let (validator, _) = Validator::default()
         .with_accounts(vec![
@@ -279,7 +344,7 @@ async fn test_exchange() -> anyhow::Result<()> {
                 ),
             ),
             (
-                tmp_token_account.pubkey(),
+                tmp_token_account_pubkey,
                 accounts::associated_token_account(
                     send_mint_token_account.pubkey(),
                     pda,
@@ -292,12 +357,18 @@ async fn test_exchange() -> anyhow::Result<()> {
                 ),
             ),
             (
-                escrow_account.pubkey(),
+                escrow_account_pubkey,
                 accounts::escrow_account(
                     sender.pubkey(),
                     sender_receive_associated_token_account_pubkey,
-                    tmp_token_account.pubkey(),
+                    tmp_token_account_pubkey,
                     100,
+                    100,
+                    0,
+                    bump,
+                    0,
+                    None,
+                    None,
                 ),
             ),
         ])
@@ -312,10 +383,24 @@ async fn test_exchange() -> anyhow::Result<()> {
             ..RpcSendTransactionConfig::default()
         })
         .with_escrow_program_id(escrow_program::id())
-        .with_token_program_id(spl_token::id())
         .build();
 
-    let _ = escrow.exchange(escrow_account.pubkey()).await?;
+    let result = escrow
+        .exchange(escrow_account_pubkey, None, None, None)
+        .await?;
+
+    let events = escrow.events(&result.signature).await?;
+    match events.as_slice() {
+        [EscrowEvent::Exchanged(event)] => {
+            assert_eq!(event.escrow, escrow_account_pubkey);
+            assert_eq!(event.seller, sender.pubkey());
+            assert_eq!(event.buyer, receiver.pubkey());
+            assert_eq!(event.amount, 100);
+            assert_eq!(event.paid_amount, 100);
+            assert_eq!(event.remaining_amount, 0);
+        }
+        other => panic!("unexpected events {:?}", other),
+    }
 
     let sender_send_associated_token_account = client
         .get_account(&sender_send_associated_token_account_pubkey)
@@ -457,11 +542,1609 @@ async fn test_exchange() -> anyhow::Result<()> {
         COption::None
     );
 
-    let tmp_token_account = client.get_account(&tmp_token_account.pubkey()).await;
-    assert_eq!(tmp_token_account.is_err(), true);
+    let tmp_token_account = client.get_account(&tmp_token_account_pubkey).await;
+    assert!(tmp_token_account.is_err());
+
+    let escrow_account = client.get_account(&escrow_account_pubkey).await;
+    assert!(escrow_account.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel() -> anyhow::Result<()> {
+    let sender = Keypair::new();
+    let send_mint_token_account = Keypair::new();
+    let receive_mint_token_account = Keypair::new();
+    let sender_send_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &sender.pubkey(),
+            &send_mint_token_account.pubkey(),
+            &spl_token::id(),
+        );
+    let sender_receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &sender.pubkey(),
+            &receive_mint_token_account.pubkey(),
+            &spl_token::id(),
+        );
+    let (escrow_account_pubkey, _) =
+        find_escrow_address(&sender.pubkey(), 0, &escrow_program::id());
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    let (pda, bump) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+
+    let (validator, _) = Validator::default()
+        .with_accounts(vec![
+            (
+                sender.pubkey(),
+                AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+            ),
+            (
+                send_mint_token_account.pubkey(),
+                accounts::mint_account(None, 1_000_000_000, 0, None),
+            ),
+            (
+                receive_mint_token_account.pubkey(),
+                accounts::mint_account(None, 1_000_000_000, 0, None),
+            ),
+            (
+                sender_send_associated_token_account_pubkey,
+                accounts::associated_token_account(
+                    send_mint_token_account.pubkey(),
+                    sender.pubkey(),
+                    0,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+            (
+                sender_receive_associated_token_account_pubkey,
+                accounts::associated_token_account(
+                    receive_mint_token_account.pubkey(),
+                    sender.pubkey(),
+                    0,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+            (
+                tmp_token_account_pubkey,
+                accounts::associated_token_account(
+                    send_mint_token_account.pubkey(),
+                    pda,
+                    100,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+            (
+                escrow_account_pubkey,
+                accounts::escrow_account(
+                    sender.pubkey(),
+                    sender_receive_associated_token_account_pubkey,
+                    tmp_token_account_pubkey,
+                    100,
+                    100,
+                    0,
+                    bump,
+                    0,
+                    None,
+                    None,
+                ),
+            ),
+        ])
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(client.clone(), sender.insecure_clone())
+        .with_rpc_send_transaction_config(RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Processed),
+            ..RpcSendTransactionConfig::default()
+        })
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    let signature = escrow.cancel(escrow_account_pubkey).await?;
+
+    let events = escrow.events(&signature).await?;
+    match events.as_slice() {
+        [EscrowEvent::Cancelled(event)] => {
+            assert_eq!(event.escrow, escrow_account_pubkey);
+            assert_eq!(event.seller, sender.pubkey());
+            assert_eq!(event.amount, 100);
+            assert!(!event.refunded);
+        }
+        other => panic!("unexpected events {:?}", other),
+    }
+
+    let sender_send_associated_token_account = client
+        .get_account(&sender_send_associated_token_account_pubkey)
+        .await?;
+    let sender_send_associated_token_account_data =
+        spl_token::state::Account::unpack(&sender_send_associated_token_account.data)?;
+    assert_eq!(
+        sender_send_associated_token_account_data.mint,
+        send_mint_token_account.pubkey()
+    );
+    assert_eq!(
+        sender_send_associated_token_account_data.owner,
+        sender.pubkey()
+    );
+    assert_eq!(sender_send_associated_token_account_data.amount, 100);
+
+    let sender_receive_associated_token_account = client
+        .get_account(&sender_receive_associated_token_account_pubkey)
+        .await?;
+    let sender_receive_associated_token_account_data =
+        spl_token::state::Account::unpack(&sender_receive_associated_token_account.data)?;
+    assert_eq!(sender_receive_associated_token_account_data.amount, 0);
+
+    let tmp_token_account = client.get_account(&tmp_token_account_pubkey).await;
+    assert!(tmp_token_account.is_err());
+
+    let escrow_account = client.get_account(&escrow_account_pubkey).await;
+    assert!(escrow_account.is_err());
+
+    Ok(())
+}
+
+/// Escrow opened by the seller and waiting to be taken by the buyer.
+#[tokio::test]
+async fn test_initialize_token_2022() -> anyhow::Result<()> {
+    let payer = Keypair::new();
+    let send_mint_token_account = Keypair::new();
+    let send_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &payer.pubkey(),
+            &send_mint_token_account.pubkey(),
+            &spl_token_2022::id(),
+        );
+    let receive_mint_token_account = Keypair::new();
+    let receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &payer.pubkey(),
+            &receive_mint_token_account.pubkey(),
+            &spl_token::id(),
+        );
+    let (validator, _) = Validator::default()
+        .with_accounts(vec![
+            (
+                payer.pubkey(),
+                AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+            ),
+            (
+                send_mint_token_account.pubkey(),
+                accounts::mint_2022_account(None, 1_000_000_000, 9, None, Some((100, 1))),
+            ),
+            (
+                send_associated_token_account_pubkey,
+                accounts::token_2022_account(
+                    send_mint_token_account.pubkey(),
+                    payer.pubkey(),
+                    100,
+                    Some(0),
+                ),
+            ),
+            (
+                receive_mint_token_account.pubkey(),
+                accounts::mint_account(None, 1_000_000_000, 9, None),
+            ),
+            (
+                receive_associated_token_account_pubkey,
+                accounts::associated_token_account(
+                    receive_mint_token_account.pubkey(),
+                    payer.pubkey(),
+                    0,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+        ])
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(client.clone(), payer.insecure_clone())
+        .with_rpc_send_transaction_config(RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Processed),
+            ..RpcSendTransactionConfig::default()
+        })
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    let escrow_account_pubkey = escrow
+        .init(
+            0,
+            send_mint_token_account.pubkey(),
+            100,
+            receive_mint_token_account.pubkey(),
+            100,
+            None,
+            None,
+        )
+        .await?
+        .escrow_account_pubkey;
+
+    let send_associated_token_account = client
+        .get_account(&send_associated_token_account_pubkey)
+        .await?;
+    let send_associated_token_account_data =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(
+            &send_associated_token_account.data,
+        )?;
+    assert_eq!(send_associated_token_account_data.base.amount, 0);
+
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    let (pda, _) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+    let tmp_token_account = client.get_account(&tmp_token_account_pubkey).await?;
+    assert_eq!(tmp_token_account.owner, spl_token_2022::id());
+    let tmp_token_account_data =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&tmp_token_account.data)?;
+    assert_eq!(
+        tmp_token_account_data.base.mint,
+        send_mint_token_account.pubkey()
+    );
+    assert_eq!(tmp_token_account_data.base.owner, pda);
+    assert_eq!(tmp_token_account_data.base.amount, 99);
+    assert_eq!(
+        u64::from(
+            tmp_token_account_data
+                .get_extension::<TransferFeeAmount>()?
+                .withheld_amount
+        ),
+        1
+    );
+
+    let escrow_account = client.get_account(&escrow_account_pubkey).await?;
+    let escrow_account_data = escrow_program::state::Escrow::unpack(&escrow_account.data)?;
+    assert!(escrow_account_data.is_initialized);
+    assert_eq!(
+        escrow_account_data.temp_token_account_pubkey,
+        tmp_token_account_pubkey
+    );
+    assert_eq!(escrow_account_data.amount, 100);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_token_2022() -> anyhow::Result<()> {
+    let sender = Keypair::new();
+    let receiver = Keypair::new();
+    let send_mint_token_account = Keypair::new();
+    let receive_mint_token_account = Keypair::new();
+    let sender_send_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &sender.pubkey(),
+            &send_mint_token_account.pubkey(),
+            &spl_token_2022::id(),
+        );
+    let sender_receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &sender.pubkey(),
+            &receive_mint_token_account.pubkey(),
+            &spl_token_2022::id(),
+        );
+    let receiver_send_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &receiver.pubkey(),
+            &receive_mint_token_account.pubkey(),
+            &spl_token_2022::id(),
+        );
+    let receiver_receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &receiver.pubkey(),
+            &send_mint_token_account.pubkey(),
+            &spl_token_2022::id(),
+        );
+    let (escrow_account_pubkey, _) =
+        find_escrow_address(&sender.pubkey(), 0, &escrow_program::id());
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    let (pda, bump) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+
+    let (validator, _) = Validator::default()
+        .with_accounts(vec![
+            (
+                receiver.pubkey(),
+                AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+            ),
+            (
+                send_mint_token_account.pubkey(),
+                accounts::mint_2022_account(None, 1_000_000_000, 0, None, Some((100, 1))),
+            ),
+            (
+                receive_mint_token_account.pubkey(),
+                accounts::mint_2022_account(None, 1_000_000_000, 0, None, Some((100, 1))),
+            ),
+            (
+                sender_send_associated_token_account_pubkey,
+                accounts::token_2022_account(
+                    send_mint_token_account.pubkey(),
+                    sender.pubkey(),
+                    0,
+                    Some(0),
+                ),
+            ),
+            (
+                sender_receive_associated_token_account_pubkey,
+                accounts::token_2022_account(
+                    receive_mint_token_account.pubkey(),
+                    sender.pubkey(),
+                    0,
+                    Some(0),
+                ),
+            ),
+            (
+                receiver_send_associated_token_account_pubkey,
+                accounts::token_2022_account(
+                    receive_mint_token_account.pubkey(),
+                    receiver.pubkey(),
+                    200,
+                    Some(0),
+                ),
+            ),
+            (
+                receiver_receive_associated_token_account_pubkey,
+                accounts::token_2022_account(
+                    send_mint_token_account.pubkey(),
+                    receiver.pubkey(),
+                    0,
+                    Some(0),
+                ),
+            ),
+            (
+                tmp_token_account_pubkey,
+                accounts::token_2022_account(send_mint_token_account.pubkey(), pda, 99, Some(1)),
+            ),
+            (
+                escrow_account_pubkey,
+                accounts::escrow_account(
+                    sender.pubkey(),
+                    sender_receive_associated_token_account_pubkey,
+                    tmp_token_account_pubkey,
+                    100,
+                    99,
+                    0,
+                    bump,
+                    0,
+                    None,
+                    None,
+                ),
+            ),
+        ])
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(client.clone(), receiver.insecure_clone())
+        .with_rpc_send_transaction_config(RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Processed),
+            ..RpcSendTransactionConfig::default()
+        })
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    escrow
+        .exchange(escrow_account_pubkey, None, None, None)
+        .await?;
+
+    // The seller receives the expected amount, the fee is charged on top of it.
+    let sender_receive_associated_token_account = client
+        .get_account(&sender_receive_associated_token_account_pubkey)
+        .await?;
+    let sender_receive_associated_token_account_data =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(
+            &sender_receive_associated_token_account.data,
+        )?;
+    assert_eq!(
+        sender_receive_associated_token_account_data.base.amount,
+        100
+    );
+
+    let receiver_send_associated_token_account = client
+        .get_account(&receiver_send_associated_token_account_pubkey)
+        .await?;
+    let receiver_send_associated_token_account_data =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(
+            &receiver_send_associated_token_account.data,
+        )?;
+    assert_eq!(receiver_send_associated_token_account_data.base.amount, 99);
+
+    let receiver_receive_associated_token_account = client
+        .get_account(&receiver_receive_associated_token_account_pubkey)
+        .await?;
+    let receiver_receive_associated_token_account_data =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(
+            &receiver_receive_associated_token_account.data,
+        )?;
+    assert_eq!(
+        receiver_receive_associated_token_account_data.base.amount,
+        98
+    );
+
+    // The fees withheld in the vault are harvested to the mint before closing it.
+    let send_mint_token_account = client
+        .get_account(&send_mint_token_account.pubkey())
+        .await?;
+    let send_mint_token_account_data =
+        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&send_mint_token_account.data)?;
+    assert_eq!(
+        u64::from(
+            send_mint_token_account_data
+                .get_extension::<TransferFeeConfig>()?
+                .withheld_amount
+        ),
+        2
+    );
+
+    let tmp_token_account = client.get_account(&tmp_token_account_pubkey).await;
+    assert!(tmp_token_account.is_err());
+
+    let escrow_account = client.get_account(&escrow_account_pubkey).await;
+    assert!(escrow_account.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_native_sol() -> anyhow::Result<()> {
+    let sender = Keypair::new();
+    let receiver = Keypair::new();
+    let send_mint_token_account = Keypair::new();
+    let native_mint_pubkey = spl_token::native_mint::id();
+    let sender_receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &sender.pubkey(),
+            &native_mint_pubkey,
+            &spl_token::id(),
+        );
+    let receiver_send_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &receiver.pubkey(),
+            &native_mint_pubkey,
+            &spl_token::id(),
+        );
+    let receiver_receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &receiver.pubkey(),
+            &send_mint_token_account.pubkey(),
+            &spl_token::id(),
+        );
+    let (escrow_account_pubkey, _) =
+        find_escrow_address(&sender.pubkey(), 0, &escrow_program::id());
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    let (pda, bump) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+
+    let (validator, _) = Validator::default()
+        .with_accounts(vec![
+            (
+                receiver.pubkey(),
+                AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+            ),
+            (
+                send_mint_token_account.pubkey(),
+                accounts::mint_account(None, 1_000_000_000, 0, None),
+            ),
+            (native_mint_pubkey, accounts::mint_account(None, 0, 9, None)),
+            (
+                sender_receive_associated_token_account_pubkey,
+                accounts::associated_token_account(
+                    native_mint_pubkey,
+                    sender.pubkey(),
+                    0,
+                    None,
+                    AccountState::Initialized,
+                    Some(Rent::default().minimum_balance(spl_token::state::Account::LEN)),
+                    0,
+                    None,
+                ),
+            ),
+            (
+                receiver_receive_associated_token_account_pubkey,
+                accounts::associated_token_account(
+                    send_mint_token_account.pubkey(),
+                    receiver.pubkey(),
+                    0,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+            (
+                tmp_token_account_pubkey,
+                accounts::associated_token_account(
+                    send_mint_token_account.pubkey(),
+                    pda,
+                    100,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+            (
+                escrow_account_pubkey,
+                accounts::escrow_account(
+                    sender.pubkey(),
+                    sender_receive_associated_token_account_pubkey,
+                    tmp_token_account_pubkey,
+                    1_000_000,
+                    100,
+                    0,
+                    bump,
+                    0,
+                    None,
+                    None,
+                ),
+            ),
+        ])
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(client.clone(), receiver.insecure_clone())
+        .with_rpc_send_transaction_config(RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Processed),
+            ..RpcSendTransactionConfig::default()
+        })
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    // The buyer pays in SOL, wrapped and unwrapped by the client
+    escrow
+        .exchange(escrow_account_pubkey, None, None, None)
+        .await?;
+
+    let sender_receive_associated_token_account = client
+        .get_account(&sender_receive_associated_token_account_pubkey)
+        .await?;
+    let sender_receive_associated_token_account_data =
+        spl_token::state::Account::unpack(&sender_receive_associated_token_account.data)?;
+    assert_eq!(
+        sender_receive_associated_token_account_data.amount,
+        1_000_000
+    );
+
+    let receiver_send_associated_token_account = client
+        .get_account(&receiver_send_associated_token_account_pubkey)
+        .await;
+    assert!(receiver_send_associated_token_account.is_err());
+
+    let receiver_receive_associated_token_account = client
+        .get_account(&receiver_receive_associated_token_account_pubkey)
+        .await?;
+    let receiver_receive_associated_token_account_data =
+        spl_token::state::Account::unpack(&receiver_receive_associated_token_account.data)?;
+    assert_eq!(receiver_receive_associated_token_account_data.amount, 100);
+
+    let escrow_account = client.get_account(&escrow_account_pubkey).await;
+    assert!(escrow_account.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_native_sol_existing_token_account() -> anyhow::Result<()> {
+    let sender = Keypair::new();
+    let receiver = Keypair::new();
+    let send_mint_token_account = Keypair::new();
+    let native_mint_pubkey = spl_token::native_mint::id();
+    let sender_receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &sender.pubkey(),
+            &native_mint_pubkey,
+            &spl_token::id(),
+        );
+    let receiver_send_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &receiver.pubkey(),
+            &native_mint_pubkey,
+            &spl_token::id(),
+        );
+    let receiver_receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &receiver.pubkey(),
+            &send_mint_token_account.pubkey(),
+            &spl_token::id(),
+        );
+    let (escrow_account_pubkey, _) =
+        find_escrow_address(&sender.pubkey(), 0, &escrow_program::id());
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    let (pda, bump) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+    let mut receiver_send_associated_token_account = accounts::associated_token_account(
+        native_mint_pubkey,
+        receiver.pubkey(),
+        5_000,
+        None,
+        AccountState::Initialized,
+        Some(Rent::default().minimum_balance(spl_token::state::Account::LEN)),
+        0,
+        None,
+    );
+    receiver_send_associated_token_account.checked_add_lamports(5_000)?;
+
+    let (validator, _) = Validator::default()
+        .with_accounts(vec![
+            (
+                receiver.pubkey(),
+                AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+            ),
+            (
+                send_mint_token_account.pubkey(),
+                accounts::mint_account(None, 1_000_000_000, 0, None),
+            ),
+            (native_mint_pubkey, accounts::mint_account(None, 0, 9, None)),
+            (
+                sender_receive_associated_token_account_pubkey,
+                accounts::associated_token_account(
+                    native_mint_pubkey,
+                    sender.pubkey(),
+                    0,
+                    None,
+                    AccountState::Initialized,
+                    Some(Rent::default().minimum_balance(spl_token::state::Account::LEN)),
+                    0,
+                    None,
+                ),
+            ),
+            (
+                receiver_send_associated_token_account_pubkey,
+                receiver_send_associated_token_account,
+            ),
+            (
+                receiver_receive_associated_token_account_pubkey,
+                accounts::associated_token_account(
+                    send_mint_token_account.pubkey(),
+                    receiver.pubkey(),
+                    0,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+            (
+                tmp_token_account_pubkey,
+                accounts::associated_token_account(
+                    send_mint_token_account.pubkey(),
+                    pda,
+                    100,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+            (
+                escrow_account_pubkey,
+                accounts::escrow_account(
+                    sender.pubkey(),
+                    sender_receive_associated_token_account_pubkey,
+                    tmp_token_account_pubkey,
+                    1_000_000,
+                    100,
+                    0,
+                    bump,
+                    0,
+                    None,
+                    None,
+                ),
+            ),
+        ])
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(client.clone(), receiver.insecure_clone())
+        .with_rpc_send_transaction_config(RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Processed),
+            ..RpcSendTransactionConfig::default()
+        })
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    // The buyer already holds wrapped SOL, which is left wrapped
+    escrow
+        .exchange(escrow_account_pubkey, None, None, None)
+        .await?;
+
+    let sender_receive_associated_token_account = client
+        .get_account(&sender_receive_associated_token_account_pubkey)
+        .await?;
+    let sender_receive_associated_token_account_data =
+        spl_token::state::Account::unpack(&sender_receive_associated_token_account.data)?;
+    assert_eq!(
+        sender_receive_associated_token_account_data.amount,
+        1_000_000
+    );
+
+    let receiver_send_associated_token_account = client
+        .get_account(&receiver_send_associated_token_account_pubkey)
+        .await?;
+    let receiver_send_associated_token_account_data =
+        spl_token::state::Account::unpack(&receiver_send_associated_token_account.data)?;
+    assert_eq!(receiver_send_associated_token_account_data.amount, 5_000);
+
+    let receiver_receive_associated_token_account = client
+        .get_account(&receiver_receive_associated_token_account_pubkey)
+        .await?;
+    let receiver_receive_associated_token_account_data =
+        spl_token::state::Account::unpack(&receiver_receive_associated_token_account.data)?;
+    assert_eq!(receiver_receive_associated_token_account_data.amount, 100);
+
+    let escrow_account = client.get_account(&escrow_account_pubkey).await;
+    assert!(escrow_account.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_initialize_create_token_account() -> anyhow::Result<()> {
+    let payer = Keypair::new();
+    let send_mint_token_account = Keypair::new();
+    let send_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &payer.pubkey(),
+            &send_mint_token_account.pubkey(),
+            &spl_token::id(),
+        );
+    let receive_mint_token_account = Keypair::new();
+    let receive_associated_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &payer.pubkey(),
+            &receive_mint_token_account.pubkey(),
+            &spl_token::id(),
+        );
+    let (validator, _) = Validator::default()
+        .with_accounts(vec![
+            (
+                payer.pubkey(),
+                AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+            ),
+            (
+                send_mint_token_account.pubkey(),
+                accounts::mint_account(None, 1_000_000_000, 9, None),
+            ),
+            (
+                send_associated_token_account_pubkey,
+                accounts::associated_token_account(
+                    send_mint_token_account.pubkey(),
+                    payer.pubkey(),
+                    100,
+                    None,
+                    AccountState::Initialized,
+                    None,
+                    0,
+                    None,
+                ),
+            ),
+            (
+                receive_mint_token_account.pubkey(),
+                accounts::mint_account(None, 1_000_000_000, 9, None),
+            ),
+        ])
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(client.clone(), payer.insecure_clone())
+        .with_rpc_send_transaction_config(RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentLevel::Processed),
+            ..RpcSendTransactionConfig::default()
+        })
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    // The seller has never held the token they will receive
+    let result = escrow
+        .init(
+            0,
+            send_mint_token_account.pubkey(),
+            100,
+            receive_mint_token_account.pubkey(),
+            100,
+            None,
+            None,
+        )
+        .await?;
+    assert_eq!(
+        result.created_token_accounts,
+        vec![receive_associated_token_account_pubkey]
+    );
+
+    let receive_associated_token_account = client
+        .get_account(&receive_associated_token_account_pubkey)
+        .await?;
+    let receive_associated_token_account_data =
+        spl_token::state::Account::unpack(&receive_associated_token_account.data)?;
+    assert_eq!(
+        receive_associated_token_account_data.mint,
+        receive_mint_token_account.pubkey()
+    );
+    assert_eq!(receive_associated_token_account_data.owner, payer.pubkey());
+
+    let escrow_account = client.get_account(&result.escrow_account_pubkey).await?;
+    let escrow_account_data = escrow_program::state::Escrow::unpack(&escrow_account.data)?;
+    assert_eq!(
+        escrow_account_data.seller_token_account_pubkey,
+        receive_associated_token_account_pubkey
+    );
+
+    Ok(())
+}
+
+/// The validator loaded with the accounts of an open escrow.
+struct EscrowFixture {
+    validator: TestValidator,
+    accounts: EscrowAccounts,
+}
+
+impl EscrowFixture {
+    async fn start() -> anyhow::Result<Self> {
+        let (accounts, account_data) = EscrowAccounts::new();
+        let (validator, _) = Validator::default()
+            .with_accounts(account_data)
+            .start()
+            .await?;
+
+        Ok(Self {
+            validator,
+            accounts,
+        })
+    }
+
+    /// Create a client of the escrow program paid by the payer.
+    fn client(&self, payer: &Keypair) -> escrow_client::Client {
+        let client = Arc::new(self.validator.get_async_rpc_client());
+        escrow_client::Client::builder(client, payer.insecure_clone())
+            .with_escrow_program_id(escrow_program::id())
+            .build()
+    }
+}
+
+impl Deref for EscrowFixture {
+    type Target = EscrowAccounts;
+
+    fn deref(&self) -> &Self::Target {
+        &self.accounts
+    }
+}
+
+/// Assert that the result failed with the escrow error.
+fn assert_escrow_error<T: std::fmt::Debug>(
+    result: escrow_client::Result<T>,
+    expected: EscrowError,
+) {
+    match result {
+        Err(ClientError::EscrowError(err)) => assert_eq!(err, expected),
+        other => panic!("expected {:?}, got {:?}", expected, other),
+    }
+}
+
+#[tokio::test]
+async fn test_refund() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = fixture.validator.get_async_rpc_client();
+    let (tmp_token_account_pubkey, _) = find_vault_address(
+        &fixture.expired_escrow_account_pubkey,
+        &escrow_program::id(),
+    );
+
+    // Anyone may refund an expired escrow, not only the seller
+    fixture
+        .client(&fixture.buyer)
+        .refund(fixture.expired_escrow_account_pubkey)
+        .await?;
+
+    let seller_send_token_account = rpc_client
+        .get_account(&fixture.seller_send_token_account_pubkey)
+        .await?;
+    assert_token_balance(&seller_send_token_account, 200);
+    assert!(rpc_client
+        .get_account(&tmp_token_account_pubkey)
+        .await
+        .is_err());
+    assert!(rpc_client
+        .get_account(&fixture.expired_escrow_account_pubkey)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refund_not_expired() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+
+    // The escrow error is decoded from the failed transaction
+    let result = fixture
+        .client(&fixture.buyer)
+        .refund(fixture.escrow_account_pubkey)
+        .await;
+    assert_escrow_error(result, EscrowError::NotExpired);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refund_not_expired_simulated() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = Arc::new(fixture.validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(rpc_client, fixture.buyer.insecure_clone())
+        .with_compute_unit_limit(ComputeUnitLimit::Simulated)
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    // The escrow error is decoded from the failed simulation, before anything is sent
+    let result = escrow.refund(fixture.escrow_account_pubkey).await;
+    assert_escrow_error(result, EscrowError::NotExpired);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_wrong_seller_skip_preflight() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = Arc::new(fixture.validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(rpc_client, fixture.buyer.insecure_clone())
+        .with_rpc_send_transaction_config(RpcSendTransactionConfig {
+            skip_preflight: true,
+            ..RpcSendTransactionConfig::default()
+        })
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    // The escrow error is decoded from the status of the transaction that landed and failed
+    let result = escrow.cancel(fixture.escrow_account_pubkey).await;
+    assert_escrow_error(result, EscrowError::SellerMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_wrong_seller_sent_by_other_means() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = Arc::new(fixture.validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(rpc_client.clone(), fixture.buyer.insecure_clone())
+        .with_blockhash(rpc_client.get_latest_blockhash().await?)
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+    let mut tx = escrow.cancel_transaction(
+        fixture.escrow_account_pubkey,
+        fixture.send_mint_pubkey,
+        fixture.send_token_program_id,
+    )?;
+    escrow.sign_transaction(&mut tx)?;
+
+    // The error of a transaction sent without the client is left to the caller to decode
+    let err = rpc_client
+        .send_and_confirm_transaction(&tx)
+        .await
+        .unwrap_err();
+    let err = ClientError::from(err);
+    assert!(matches!(err, ClientError::RpcError(_)));
+    let result = Err::<(), _>(err.decode_escrow_error(&tx.message, &escrow_program::id()));
+    assert_escrow_error(result, EscrowError::SellerMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = fixture.validator.get_async_rpc_client();
+    let escrow = fixture.client(&fixture.buyer);
+
+    // The client shows the escrow as it is migrated
+    let expected = escrow.account(fixture.v1_escrow_account_pubkey).await?;
+
+    escrow.migrate(fixture.v1_escrow_account_pubkey).await?;
+
+    let escrow_account = rpc_client
+        .get_account(&fixture.v1_escrow_account_pubkey)
+        .await?;
+    assert_escrow(
+        &escrow_account,
+        &ExpectedEscrow {
+            seller_pubkey: Some(expected.seller_pubkey),
+            amount: Some(expected.amount),
+            offered_amount: Some(100),
+            filled_amount: Some(0),
+            ..ExpectedEscrow::default()
+        },
+    );
+    assert_eq!(expected.offered_amount, 100);
+    let tmp_token_account = rpc_client
+        .get_account(&fixture.v1_tmp_token_account_pubkey)
+        .await?;
+    assert_eq!(
+        spl_token::state::Account::unpack(&tmp_token_account.data)?.owner,
+        find_authority_address(&fixture.v1_escrow_account_pubkey, &escrow_program::id()).0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_terms() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = fixture.validator.get_async_rpc_client();
+
+    fixture
+        .client(&fixture.seller)
+        .update(
+            fixture.escrow_account_pubkey,
+            Some(150),
+            50,
+            0,
+            Some(Some(i64::MAX)),
+            Some(None),
+        )
+        .await?;
+
+    let seller_send_token_account = rpc_client
+        .get_account(&fixture.seller_send_token_account_pubkey)
+        .await?;
+    assert_token_balance(&seller_send_token_account, 50);
+    let escrow_account = rpc_client
+        .get_account(&fixture.escrow_account_pubkey)
+        .await?;
+    assert_escrow(
+        &escrow_account,
+        &ExpectedEscrow {
+            amount: Some(150),
+            remaining_amount: Some(150),
+            expires_at: Some(Some(i64::MAX)),
+            taker: Some(None),
+            ..ExpectedEscrow::default()
+        },
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_native_sol_terms() -> anyhow::Result<()> {
+    let seller = Keypair::new();
+    let native_mint_pubkey = spl_token::native_mint::id();
+    let seller_native_token_account_pubkey =
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &seller.pubkey(),
+            &native_mint_pubkey,
+            &spl_token::id(),
+        );
+    let (escrow_account_pubkey, _) =
+        find_escrow_address(&seller.pubkey(), 0, &escrow_program::id());
+    let (tmp_token_account_pubkey, _) =
+        find_vault_address(&escrow_account_pubkey, &escrow_program::id());
+    let (pda, bump) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+    let rent_exempt_reserve = Rent::default().minimum_balance(spl_token::state::Account::LEN);
+    let mut tmp_token_account = accounts::associated_token_account(
+        native_mint_pubkey,
+        pda,
+        1_000_000,
+        None,
+        AccountState::Initialized,
+        Some(rent_exempt_reserve),
+        0,
+        None,
+    );
+    tmp_token_account.checked_add_lamports(1_000_000)?;
+
+    let (validator, _) = Validator::default()
+        .with_accounts(vec![
+            (
+                seller.pubkey(),
+                AccountSharedData::new(1_000_000_000, 0, &system_program::id()),
+            ),
+            (native_mint_pubkey, accounts::mint_account(None, 0, 9, None)),
+            (tmp_token_account_pubkey, tmp_token_account),
+            (
+                escrow_account_pubkey,
+                accounts::escrow_account(
+                    seller.pubkey(),
+                    Pubkey::new_unique(),
+                    tmp_token_account_pubkey,
+                    100,
+                    1_000_000,
+                    0,
+                    bump,
+                    0,
+                    None,
+                    None,
+                ),
+            ),
+        ])
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(client.clone(), seller.insecure_clone())
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    // Changing the terms alone neither wraps SOL nor creates the wSOL account of the seller
+    escrow
+        .update(escrow_account_pubkey, Some(200), 0, 0, None, None)
+        .await?;
+
+    let escrow_account = client.get_account(&escrow_account_pubkey).await?;
+    assert_escrow(
+        &escrow_account,
+        &ExpectedEscrow {
+            amount: Some(200),
+            remaining_amount: Some(1_000_000),
+            ..ExpectedEscrow::default()
+        },
+    );
+    let seller_native_token_account = client
+        .get_account(&seller_native_token_account_pubkey)
+        .await;
+    assert!(seller_native_token_account.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let escrow = fixture.client(&fixture.buyer);
+
+    // The unmigrated escrow and the escrow owned by another program are not listed
+    let mut escrows = escrow
+        .list(&ListFilter {
+            seller: Some(fixture.seller.pubkey()),
+            ..ListFilter::default()
+        })
+        .await?
+        .into_iter()
+        .map(|(pubkey, _)| pubkey)
+        .collect::<Vec<_>>();
+    escrows.sort();
+    let mut expected = vec![
+        fixture.escrow_account_pubkey,
+        fixture.expired_escrow_account_pubkey,
+    ];
+    expected.sort();
+    assert_eq!(escrows, expected);
+
+    let escrows = escrow
+        .list(&ListFilter {
+            seller_token_account: Some(fixture.seller_receive_token_account_pubkey),
+            mint: Some(fixture.send_mint_pubkey),
+            min_amount: Some(100),
+            ..ListFilter::default()
+        })
+        .await?;
+    assert_eq!(escrows.len(), 2);
+    assert!(escrows.iter().all(|(_, escrow)| escrow.amount == 100));
+
+    let escrows = escrow
+        .list(&ListFilter {
+            seller: Some(fixture.buyer.pubkey()),
+            ..ListFilter::default()
+        })
+        .await?;
+    assert!(escrows.is_empty());
+
+    let escrows = escrow
+        .list(&ListFilter {
+            mint: Some(fixture.receive_mint_pubkey),
+            ..ListFilter::default()
+        })
+        .await?;
+    assert!(escrows.is_empty());
+
+    let escrows = escrow
+        .list(&ListFilter {
+            min_amount: Some(101),
+            ..ListFilter::default()
+        })
+        .await?;
+    assert!(escrows.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_offline_signing() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = Arc::new(fixture.validator.get_async_rpc_client());
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+
+    // The buyer pays the fees of the seller's cancel, each signing on their own
+    let online = escrow_client::Client::builder(
+        rpc_client.clone(),
+        NullSigner::new(&fixture.seller.pubkey()),
+    )
+    .with_fee_payer(fixture.buyer.insecure_clone())
+    .with_blockhash(blockhash)
+    .with_escrow_program_id(escrow_program::id())
+    .build();
+    let mut tx = online.cancel_transaction(
+        fixture.escrow_account_pubkey,
+        fixture.send_mint_pubkey,
+        fixture.send_token_program_id,
+    )?;
+    online.sign_transaction(&mut tx)?;
+    assert!(!tx.is_signed());
+
+    let offline =
+        escrow_client::Client::builder(rpc_client.clone(), fixture.seller.insecure_clone())
+            .with_fee_payer(NullSigner::new(&fixture.buyer.pubkey()))
+            .with_blockhash(blockhash)
+            .with_escrow_program_id(escrow_program::id())
+            .build();
+    let mut offline_tx = offline.cancel_transaction(
+        fixture.escrow_account_pubkey,
+        fixture.send_mint_pubkey,
+        fixture.send_token_program_id,
+    )?;
+    assert_eq!(offline_tx.message, tx.message);
+    offline.sign_transaction(&mut offline_tx)?;
+    let seller_signature = offline_tx.signatures[tx
+        .message
+        .account_keys
+        .iter()
+        .position(|pubkey| *pubkey == fixture.seller.pubkey())
+        .unwrap()];
+
+    // The seller's signature is added to the transaction signed by the buyer
+    let online = escrow_client::Client::builder(
+        rpc_client.clone(),
+        Presigner::new(&fixture.seller.pubkey(), &seller_signature),
+    )
+    .with_fee_payer(fixture.buyer.insecure_clone())
+    .with_blockhash(blockhash)
+    .with_escrow_program_id(escrow_program::id())
+    .build();
+    online.sign_transaction(&mut tx)?;
+    assert!(tx.is_signed());
+    online.send_transaction(&tx).await?;
+
+    let escrow_account = rpc_client.get_account(&fixture.escrow_account_pubkey).await;
+    assert!(escrow_account.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_offline_transaction_ignores_state() -> anyhow::Result<()> {
+    let scenario = Scenario::new()
+        .mint("USDC", 6)
+        .mint_2022("PYUSD", 6, None)
+        .user("alice")
+        .user("bob")
+        .fund("bob", "PYUSD", 1000)
+        .escrow("offer", "alice", "USDC", 100, "PYUSD", 50);
+    let (validator, _) = Validator::default()
+        .with_accounts(scenario.accounts())
+        .start()
+        .await?;
+    let rpc_client = Arc::new(validator.get_async_rpc_client());
+    let online = escrow_client::Client::builder(
+        rpc_client.clone(),
+        scenario.keypair("bob").insecure_clone(),
+    )
+    .with_escrow_program_id(escrow_program::id())
+    .build();
+    let addresses = online
+        .escrow_addresses(scenario.escrow_pubkey("offer"))
+        .await?;
+    let offline = escrow_client::Client::builder(
+        rpc_client.clone(),
+        scenario.keypair("bob").insecure_clone(),
+    )
+    .with_blockhash(rpc_client.get_latest_blockhash().await?)
+    .with_escrow_program_id(escrow_program::id())
+    .build();
+    let tx = offline.exchange_transaction(&addresses, 40, 40, 20)?;
+
+    // Creating the token account of the buyer and filling the escrow does not change the
+    // transaction, which still creates the account, idempotently
+    online
+        .exchange(scenario.escrow_pubkey("offer"), Some(40), None, None)
+        .await?;
+    let mut rebuilt_tx = offline.exchange_transaction(&addresses, 40, 40, 20)?;
+    assert_eq!(rebuilt_tx.message_data(), tx.message_data());
+
+    offline.sign_transaction(&mut rebuilt_tx)?;
+    offline.send_transaction(&rebuilt_tx).await?;
+    let bob_usdc = rpc_client
+        .get_account(&scenario.token_account_pubkey("bob", "USDC"))
+        .await?;
+    assert_token_balance(&bob_usdc, 80);
+    let vault = rpc_client
+        .get_account(&scenario.vault_pubkey("offer"))
+        .await?;
+    assert_token_balance(&vault, 20);
+
+    // Inputs that only the cluster knows are not read offline
+    let offline = escrow_client::Client::builder(
+        rpc_client.clone(),
+        scenario.keypair("bob").insecure_clone(),
+    )
+    .with_blockhash(rpc_client.get_latest_blockhash().await?)
+    .with_compute_unit_limit(ComputeUnitLimit::Simulated)
+    .with_escrow_program_id(escrow_program::id())
+    .build();
+    let result = offline.exchange_transaction(&addresses, 20, 20, 10);
+    assert!(matches!(result, Err(ClientError::OfflineError(_))));
+    let result = online.exchange_transaction(&addresses, 20, 20, 10);
+    assert!(matches!(result, Err(ClientError::OfflineError(_))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_durable_nonce() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = Arc::new(fixture.validator.get_async_rpc_client());
+    let nonce_account = Keypair::new();
+    let lamports = rpc_client
+        .get_minimum_balance_for_rent_exemption(nonce::State::size())
+        .await?;
+    let ixs = system_instruction::create_nonce_account(
+        &fixture.seller.pubkey(),
+        &nonce_account.pubkey(),
+        &fixture.seller.pubkey(),
+        lamports,
+    );
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&fixture.seller.pubkey()),
+        &[&fixture.seller, &nonce_account],
+        blockhash,
+    );
+    rpc_client.send_and_confirm_transaction(&tx).await?;
+    let nonce_blockhash = nonce_data(&rpc_client, &nonce_account.pubkey())
+        .await?
+        .blockhash();
+
+    // The stored nonce is given as the blockhash, as when signing offline
+    let escrow =
+        escrow_client::Client::builder(rpc_client.clone(), fixture.seller.insecure_clone())
+            .with_nonce(nonce_account.pubkey(), fixture.seller.insecure_clone())
+            .with_blockhash(nonce_blockhash)
+            .with_escrow_program_id(escrow_program::id())
+            .build();
+    let mut tx = escrow.cancel_transaction(
+        fixture.escrow_account_pubkey,
+        fixture.send_mint_pubkey,
+        fixture.send_token_program_id,
+    )?;
+    assert_eq!(tx.message.recent_blockhash, nonce_blockhash);
+    // The nonce is advanced by the first instruction
+    assert_eq!(tx.message.program_id(0), Some(&system_program::id()));
+    escrow.sign_transaction(&mut tx)?;
+    escrow.send_transaction(&tx).await?;
+
+    let escrow_account = rpc_client.get_account(&fixture.escrow_account_pubkey).await;
+    assert!(escrow_account.is_err());
+
+    // The nonce is advanced, so the transaction cannot be replayed
+    let nonce = nonce_data(&rpc_client, &nonce_account.pubkey()).await?;
+    assert_ne!(nonce.blockhash(), nonce_blockhash);
+    assert_eq!(nonce.authority, fixture.seller.pubkey());
+
+    Ok(())
+}
+
+/// Get the data of the initialized nonce account.
+async fn nonce_data(
+    rpc_client: &solana_client::nonblocking::rpc_client::RpcClient,
+    nonce_account_pubkey: &Pubkey,
+) -> anyhow::Result<nonce::state::Data> {
+    let account = rpc_client.get_account(nonce_account_pubkey).await?;
+
+    Ok(solana_client::nonce_utils::data_from_account(&account)?)
+}
+
+#[tokio::test]
+async fn test_cancel_compute_budget() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = Arc::new(fixture.validator.get_async_rpc_client());
+    let escrow =
+        escrow_client::Client::builder(rpc_client.clone(), fixture.seller.insecure_clone())
+            .with_compute_unit_limit(ComputeUnitLimit::Simulated)
+            .with_compute_unit_price(ComputeUnitPrice::Percentile(50))
+            .with_escrow_program_id(escrow_program::id())
+            .build();
+
+    // The compute unit price and limit are set before the instructions of the transaction
+    let offline =
+        escrow_client::Client::builder(rpc_client.clone(), fixture.seller.insecure_clone())
+            .with_blockhash(rpc_client.get_latest_blockhash().await?)
+            .with_compute_unit_limit(ComputeUnitLimit::Static(100_000))
+            .with_compute_unit_price(ComputeUnitPrice::Static(1))
+            .with_escrow_program_id(escrow_program::id())
+            .build();
+    let tx = offline.cancel_transaction(
+        fixture.escrow_account_pubkey,
+        fixture.send_mint_pubkey,
+        fixture.send_token_program_id,
+    )?;
+    assert_eq!(tx.message.program_id(0), Some(&compute_budget::id()));
+    assert_eq!(tx.message.program_id(1), Some(&compute_budget::id()));
+    assert_eq!(
+        tx.message.program_id(2),
+        Some(&spl_associated_token_account::id())
+    );
+    assert_eq!(tx.message.program_id(3), Some(&escrow_program::id()));
+
+    escrow.cancel(fixture.escrow_account_pubkey).await?;
+
+    let escrow_account = rpc_client.get_account(&fixture.escrow_account_pubkey).await;
+    assert!(escrow_account.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_token_program_override() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = Arc::new(fixture.validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(rpc_client, fixture.seller.insecure_clone())
+        .with_token_program_id(spl_token_2022::id())
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    // The token accounts of the escrow belong to the SPL Token program, not to the override
+    let result = escrow.cancel(fixture.escrow_account_pubkey).await;
+    assert_escrow_error(result, EscrowError::TokenProgramMismatch);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_compute_unit_limit_exceeded() -> anyhow::Result<()> {
+    let fixture = EscrowFixture::start().await?;
+    let rpc_client = Arc::new(fixture.validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(rpc_client, fixture.seller.insecure_clone())
+        .with_compute_unit_limit(ComputeUnitLimit::Static(1_000))
+        .with_escrow_program_id(escrow_program::id())
+        .build();
+
+    let result = escrow.cancel(fixture.escrow_account_pubkey).await;
+    assert!(matches!(result, Err(ClientError::RpcError(_))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_partial_scenario() -> anyhow::Result<()> {
+    let scenario = Scenario::new()
+        .mint("USDC", 6)
+        .mint_2022("PYUSD", 6, None)
+        .user("alice")
+        .user("bob")
+        .fund("bob", "PYUSD", 1000)
+        .fund("bob", "USDC", 0)
+        .escrow("offer", "alice", "USDC", 100, "PYUSD", 50)
+        .taker("offer", "bob");
+    let (validator, _) = Validator::default()
+        .with_accounts(scenario.accounts())
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow =
+        escrow_client::Client::builder(client.clone(), scenario.keypair("bob").insecure_clone())
+            .with_rpc_send_transaction_config(RpcSendTransactionConfig {
+                skip_preflight: true,
+                preflight_commitment: Some(CommitmentLevel::Processed),
+                ..RpcSendTransactionConfig::default()
+            })
+            .with_escrow_program_id(escrow_program::id())
+            .build();
+
+    let result = escrow
+        .exchange(scenario.escrow_pubkey("offer"), Some(40), None, None)
+        .await?;
+    assert_eq!(
+        result.amounts,
+        ExchangeAmounts {
+            send_mint: scenario.mint_pubkey("USDC"),
+            amount: 40,
+            receive_mint: scenario.mint_pubkey("PYUSD"),
+            paid_amount: 20,
+            remaining_amount: 60,
+        }
+    );
+
+    let bob_usdc = client
+        .get_account(&scenario.token_account_pubkey("bob", "USDC"))
+        .await?;
+    assert_token_balance(&bob_usdc, 40);
+    let bob_pyusd = client
+        .get_account(&scenario.token_account_pubkey("bob", "PYUSD"))
+        .await?;
+    assert_token_balance(&bob_pyusd, 980);
+    let alice_pyusd = client
+        .get_account(&scenario.token_account_pubkey("alice", "PYUSD"))
+        .await?;
+    assert_token_balance(&alice_pyusd, 20);
+    let vault = client.get_account(&scenario.vault_pubkey("offer")).await?;
+    assert_token_balance(&vault, 60);
+    let escrow_account = client.get_account(&scenario.escrow_pubkey("offer")).await?;
+    assert_escrow(
+        &escrow_account,
+        &ExpectedEscrow {
+            seller_pubkey: Some(scenario.pubkey("alice")),
+            filled_amount: Some(40),
+            remaining_amount: Some(60),
+            taker: Some(Some(scenario.pubkey("bob"))),
+            ..ExpectedEscrow::default()
+        },
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exchange_insufficient_funds() -> anyhow::Result<()> {
+    let scenario = Scenario::new()
+        .mint("USDC", 6)
+        .mint("PYUSD", 6)
+        .user("alice")
+        .user("bob")
+        .fund("bob", "PYUSD", 10)
+        .fund("bob", "USDC", 0)
+        .escrow("offer", "alice", "USDC", 100, "PYUSD", 50);
+    let (validator, _) = Validator::default()
+        .with_accounts(scenario.accounts())
+        .start()
+        .await?;
+
+    let client = Arc::new(validator.get_async_rpc_client());
+    let escrow = escrow_client::Client::builder(client, scenario.keypair("bob").insecure_clone())
+        .with_escrow_program_id(escrow_program::id())
+        .build();
 
-    let escrow_account = client.get_account(&escrow_account.pubkey()).await;
-    assert_eq!(escrow_account.is_err(), true);
+    // The transfer of the buyer fails in the token program, not in the escrow program
+    let result = escrow
+        .exchange(scenario.escrow_pubkey("offer"), None, None, None)
+        .await;
+    match result {
+        Err(ClientError::RpcError(err)) => assert_eq!(
+            err.get_transaction_error(),
+            Some(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(TokenError::InsufficientFunds as u32)
+            ))
+        ),
+        other => panic!("expected the token error, got {:?}", other),
+    }
 
     Ok(())
 }
@@ -0,0 +1,177 @@
+//! Property tests of the escrow state and of the decoding of instructions.
+
+use borsh::BorshDeserialize;
+use escrow_program::state::Escrow;
+use proptest::prelude::*;
+use solana_sdk::pubkey::Pubkey;
+
+/// Strategy for a public key.
+fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+/// Strategy for an escrow in any state, including inconsistent ones.
+fn escrow() -> impl Strategy<Value = Escrow> {
+    (
+        (any::<bool>(), pubkey(), pubkey(), pubkey()),
+        (any::<u64>(), any::<u64>(), any::<u64>()),
+        (any::<u8>(), any::<u64>()),
+        (any::<Option<i64>>(), proptest::option::of(pubkey())),
+    )
+        .prop_map(
+            |(
+                (
+                    is_initialized,
+                    seller_pubkey,
+                    seller_token_account_pubkey,
+                    temp_token_account_pubkey,
+                ),
+                (amount, offered_amount, filled_amount),
+                (bump, seed),
+                (expires_at, taker),
+            )| Escrow {
+                is_initialized,
+                seller_pubkey,
+                seller_token_account_pubkey,
+                temp_token_account_pubkey,
+                amount,
+                offered_amount,
+                filled_amount,
+                bump,
+                seed,
+                expires_at,
+                taker,
+            },
+        )
+}
+
+/// Strategy for the terms of an escrow and the fills taking all of its deposited tokens.
+fn fills() -> impl Strategy<Value = (u64, u64, Vec<u64>)> {
+    (1..=1_000_000_000_000u64, 1..=1_000_000u64)
+        .prop_flat_map(|(amount, offered_amount)| {
+            (
+                Just(amount),
+                Just(offered_amount),
+                proptest::collection::vec(0..=offered_amount, 0..8),
+            )
+        })
+        .prop_map(|(amount, offered_amount, mut cuts)| {
+            cuts.push(0);
+            cuts.push(offered_amount);
+            cuts.sort_unstable();
+            let fills = cuts.windows(2).map(|cut| cut[1] - cut[0]).collect();
+            (amount, offered_amount, fills)
+        })
+}
+
+proptest! {
+    #[test]
+    fn test_pack_unpack(escrow in escrow()) {
+        let mut data = vec![0; Escrow::LEN];
+        escrow.pack(&mut data).unwrap();
+        let unpacked = Escrow::unpack(&data).unwrap();
+
+        prop_assert_eq!(unpacked.is_initialized, escrow.is_initialized);
+        prop_assert_eq!(unpacked.seller_pubkey, escrow.seller_pubkey);
+        prop_assert_eq!(
+            unpacked.seller_token_account_pubkey,
+            escrow.seller_token_account_pubkey
+        );
+        prop_assert_eq!(
+            unpacked.temp_token_account_pubkey,
+            escrow.temp_token_account_pubkey
+        );
+        prop_assert_eq!(unpacked.amount, escrow.amount);
+        prop_assert_eq!(unpacked.offered_amount, escrow.offered_amount);
+        prop_assert_eq!(unpacked.filled_amount, escrow.filled_amount);
+        prop_assert_eq!(unpacked.bump, escrow.bump);
+        prop_assert_eq!(unpacked.seed, escrow.seed);
+        prop_assert_eq!(unpacked.expires_at, escrow.expires_at);
+        prop_assert_eq!(unpacked.taker, escrow.taker);
+    }
+
+    #[test]
+    fn test_decode_arbitrary_data(data in proptest::collection::vec(any::<u8>(), 0..512)) {
+        // Decoding fails on malformed data rather than panicking
+        let _ = escrow_program::Instruction::try_from_slice(&data);
+        let _ = Escrow::unpack(&data);
+    }
+
+    #[test]
+    fn test_remaining_amount(escrow in escrow()) {
+        prop_assert!(escrow.remaining_amount() <= escrow.offered_amount);
+        if escrow.filled_amount <= escrow.offered_amount {
+            prop_assert_eq!(
+                escrow.remaining_amount() + escrow.filled_amount,
+                escrow.offered_amount
+            );
+        }
+    }
+
+    #[test]
+    fn test_fills_pay_amount((amount, offered_amount, fills) in fills()) {
+        let mut escrow = Escrow {
+            amount,
+            offered_amount,
+            ..Escrow::default()
+        };
+
+        // However the deposited tokens are split, the fills pay exactly the expected amount
+        let mut paid_amount = 0;
+        for fill in fills {
+            let price = escrow.fill_price(fill).unwrap();
+            paid_amount += price;
+            escrow.filled_amount += fill;
+
+            // Rounding is in favor of the seller, so the buyer never pays less than pro rata
+            prop_assert!(
+                paid_amount as u128 * offered_amount as u128
+                    >= escrow.filled_amount as u128 * amount as u128
+            );
+        }
+
+        prop_assert_eq!(escrow.filled_amount, offered_amount);
+        prop_assert_eq!(paid_amount, amount);
+    }
+
+    #[test]
+    fn test_fill_price_overflow(escrow in escrow(), fill in any::<u64>()) {
+        // Overflow and empty escrows are reported rather than panicking
+        let price = escrow.fill_price(fill);
+        if escrow.offered_amount == 0 {
+            prop_assert_eq!(price, None);
+        }
+    }
+
+    #[test]
+    fn test_rebase_keeps_rate(
+        (amount, offered_amount, fills) in fills(),
+        new_offered_amount in 1..=1_000_000u64,
+    ) {
+        let mut escrow = Escrow {
+            amount,
+            offered_amount,
+            filled_amount: fills[0],
+            ..Escrow::default()
+        };
+        let remaining_amount = escrow.remaining_amount();
+        let remaining_price = escrow.fill_price(remaining_amount);
+
+        match escrow.rebase(new_offered_amount, None) {
+            None => prop_assert_eq!(remaining_amount, 0),
+            Some(()) => {
+                // The new tokens are priced at least at the rate of the remaining tokens
+                let remaining_price = remaining_price.unwrap();
+                prop_assert!(
+                    escrow.amount as u128 * remaining_amount as u128
+                        >= remaining_price as u128 * new_offered_amount as u128
+                );
+                prop_assert_eq!(escrow.offered_amount, new_offered_amount);
+                prop_assert_eq!(escrow.filled_amount, 0);
+                if new_offered_amount == remaining_amount {
+                    prop_assert_eq!(escrow.amount, remaining_price);
+                }
+            }
+        }
+    }
+}
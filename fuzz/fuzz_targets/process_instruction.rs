@@ -0,0 +1,467 @@
+//! Fuzz the instruction processor with random instructions, accounts and escrow states.
+//!
+//! Each input loads an open escrow with random terms and balances into a bank running the native
+//! processor, then sends one instruction, well formed or not, with its accounts permuted and
+//! substituted, paid by the seller, the buyer or a thief. Whether the instruction succeeds or
+//! fails, the run aborts if the processor panics, if tokens are created or destroyed, if a token
+//! account of anyone but the seller, the authority of the vault and the taker of the escrow, or
+//! the payer if the escrow is open to anyone, receives tokens, if the thief gains lamports, or if
+//! it gains tokens without taking an open escrow.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use escrow_program::pda::{
+    find_authority_address, find_escrow_address, find_legacy_authority_address, find_vault_address,
+};
+use escrow_program::processor::Processor;
+use escrow_testkit::accounts;
+use libfuzzer_sys::fuzz_target;
+use solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest};
+use solana_sdk::account::{Account, AccountSharedData};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::keypair_from_seed;
+use solana_sdk::transaction::Transaction;
+use solana_sdk::{system_program, sysvar};
+use spl_associated_token_account::get_associated_token_address;
+use std::collections::HashMap;
+
+/// Amount of tokens of each mint held by the thief.
+const THIEF_AMOUNT: u64 = 100;
+
+/// Party sending the instruction.
+#[derive(Arbitrary, Clone, Copy, Debug)]
+enum Party {
+    Seller,
+    Buyer,
+    /// Neither the seller nor the buyer of the escrow.
+    Thief,
+}
+
+/// Buyer designated by the escrow, which is never the thief.
+#[derive(Arbitrary, Clone, Copy, Debug)]
+enum Taker {
+    Buyer,
+    /// Neither the seller, the buyer nor the thief, and never signing.
+    ThirdParty,
+}
+
+/// Instruction built with the program that the random data and accounts start from.
+#[derive(Arbitrary, Debug)]
+enum BaseInstruction {
+    Initialize(u64),
+    Exchange,
+    Cancel,
+    Refund,
+    UpdateTerms,
+    Migrate,
+}
+
+/// Data of the instruction, replacing the data of the base instruction.
+#[derive(Arbitrary, Debug)]
+enum Data {
+    Initialize {
+        seed: u64,
+        deposit_amount: u64,
+        amount: u64,
+        expires_at: Option<i64>,
+        taker: Option<[u8; 32]>,
+    },
+    Exchange {
+        amount: u64,
+        expected_receive: u64,
+        max_pay: u64,
+    },
+    Cancel,
+    Refund,
+    Migrate,
+    UpdateTerms {
+        amount: Option<u64>,
+        deposit_amount: u64,
+        withdraw_amount: u64,
+        expires_at: Option<Option<i64>>,
+        taker: Option<Option<[u8; 32]>>,
+    },
+    Raw(Vec<u8>),
+}
+
+impl Data {
+    fn encode(self) -> Vec<u8> {
+        let instruction = match self {
+            Data::Initialize {
+                seed,
+                deposit_amount,
+                amount,
+                expires_at,
+                taker,
+            } => escrow_program::Instruction::Initialize {
+                seed,
+                deposit_amount,
+                amount,
+                expires_at,
+                taker: taker.map(Pubkey::new_from_array),
+            },
+            Data::Exchange {
+                amount,
+                expected_receive,
+                max_pay,
+            } => escrow_program::Instruction::Exchange {
+                amount,
+                expected_receive,
+                max_pay,
+            },
+            Data::Cancel => escrow_program::Instruction::Cancel,
+            Data::Refund => escrow_program::Instruction::Refund,
+            Data::Migrate => escrow_program::Instruction::Migrate,
+            Data::UpdateTerms {
+                amount,
+                deposit_amount,
+                withdraw_amount,
+                expires_at,
+                taker,
+            } => escrow_program::Instruction::UpdateTerms {
+                amount,
+                deposit_amount,
+                withdraw_amount,
+                expires_at,
+                taker: taker.map(|taker| taker.map(Pubkey::new_from_array)),
+            },
+            Data::Raw(data) => return data,
+        };
+
+        borsh::to_vec(&instruction).unwrap()
+    }
+}
+
+/// Escrow terms, token balances and instruction of a run, not necessarily consistent.
+#[derive(Arbitrary, Debug)]
+struct Input {
+    amount: u64,
+    offered_amount: u64,
+    filled_amount: u64,
+    vault_amount: u64,
+    seller_amount: u64,
+    buyer_amount: u64,
+    expires_at: Option<i64>,
+    taker: Option<Taker>,
+    payer: Party,
+    base: BaseInstruction,
+    data: Option<Data>,
+    /// Accounts swapped with each other, by index modulo the number of accounts.
+    swaps: Vec<(u8, u8)>,
+    /// Accounts replaced by another account known to the bank, by index modulo the number of
+    /// accounts.
+    substitutions: Vec<(u8, u8)>,
+}
+
+/// Create a system account holding lamports to pay for transactions and rent.
+fn system_account() -> AccountSharedData {
+    AccountSharedData::new(1_000_000_000, 0, &system_program::id())
+}
+
+/// Get the token accounts among the accounts.
+async fn token_accounts(
+    banks_client: &mut BanksClient,
+    pubkeys: &[Pubkey],
+) -> HashMap<Pubkey, spl_token::state::Account> {
+    let mut token_accounts = HashMap::new();
+    for pubkey in pubkeys {
+        let Some(account) = banks_client.get_account(*pubkey).await.unwrap() else {
+            continue;
+        };
+        if account.owner != spl_token::id() {
+            continue;
+        }
+        if let Ok(token_account) = spl_token::state::Account::unpack(&account.data) {
+            token_accounts.insert(*pubkey, token_account);
+        }
+    }
+
+    token_accounts
+}
+
+/// Get the total amount of tokens of each mint held by the token accounts.
+fn supply(token_accounts: &HashMap<Pubkey, spl_token::state::Account>) -> HashMap<Pubkey, u128> {
+    let mut supply = HashMap::new();
+    for token_account in token_accounts.values() {
+        *supply.entry(token_account.mint).or_default() += u128::from(token_account.amount);
+    }
+
+    supply
+}
+
+/// Load the escrow of the input into a bank, send its instruction and check the invariants.
+async fn run(input: Input) {
+    let program_id = escrow_program::id();
+    let seller = keypair_from_seed(&[1; 32]).unwrap();
+    let buyer = keypair_from_seed(&[2; 32]).unwrap();
+    let thief = keypair_from_seed(&[3; 32]).unwrap();
+    let third_party = keypair_from_seed(&[6; 32]).unwrap();
+    let party = |party: Party| match party {
+        Party::Seller => &seller,
+        Party::Buyer => &buyer,
+        Party::Thief => &thief,
+    };
+    let send_mint_pubkey = Pubkey::new_from_array([4; 32]);
+    let receive_mint_pubkey = Pubkey::new_from_array([5; 32]);
+    let seller_send_token_account_pubkey =
+        get_associated_token_address(&seller.pubkey(), &send_mint_pubkey);
+    let seller_receive_token_account_pubkey =
+        get_associated_token_address(&seller.pubkey(), &receive_mint_pubkey);
+    let buyer_send_token_account_pubkey =
+        get_associated_token_address(&buyer.pubkey(), &receive_mint_pubkey);
+    let buyer_receive_token_account_pubkey =
+        get_associated_token_address(&buyer.pubkey(), &send_mint_pubkey);
+    let (escrow_account_pubkey, _) = find_escrow_address(&seller.pubkey(), 0, &program_id);
+    let (vault_pubkey, _) = find_vault_address(&escrow_account_pubkey, &program_id);
+    let (pda, bump) = find_authority_address(&escrow_account_pubkey, &program_id);
+    let taker_pubkey = input.taker.map(|taker| match taker {
+        Taker::Buyer => buyer.pubkey(),
+        Taker::ThirdParty => third_party.pubkey(),
+    });
+
+    // Loading the random escrow state and balances
+    let escrow_account = accounts::escrow_account(
+        seller.pubkey(),
+        seller_receive_token_account_pubkey,
+        vault_pubkey,
+        input.amount,
+        input.offered_amount,
+        input.filled_amount,
+        bump,
+        0,
+        input.expires_at,
+        taker_pubkey,
+    );
+    let send_supply =
+        u128::from(input.vault_amount) + u128::from(input.seller_amount) + u128::from(THIEF_AMOUNT);
+    let receive_supply = u128::from(input.buyer_amount) + u128::from(THIEF_AMOUNT);
+    let accounts = [
+        (seller.pubkey(), system_account()),
+        (buyer.pubkey(), system_account()),
+        (thief.pubkey(), system_account()),
+        (
+            send_mint_pubkey,
+            accounts::mint_account(None, send_supply.try_into().unwrap_or(u64::MAX), 0, None),
+        ),
+        (
+            receive_mint_pubkey,
+            accounts::mint_account(None, receive_supply.try_into().unwrap_or(u64::MAX), 0, None),
+        ),
+        (
+            seller_send_token_account_pubkey,
+            accounts::token_account(send_mint_pubkey, seller.pubkey(), input.seller_amount),
+        ),
+        (
+            seller_receive_token_account_pubkey,
+            accounts::token_account(receive_mint_pubkey, seller.pubkey(), 0),
+        ),
+        (
+            buyer_send_token_account_pubkey,
+            accounts::token_account(receive_mint_pubkey, buyer.pubkey(), input.buyer_amount),
+        ),
+        (
+            buyer_receive_token_account_pubkey,
+            accounts::token_account(send_mint_pubkey, buyer.pubkey(), 0),
+        ),
+        (
+            get_associated_token_address(&thief.pubkey(), &send_mint_pubkey),
+            accounts::token_account(send_mint_pubkey, thief.pubkey(), THIEF_AMOUNT),
+        ),
+        (
+            get_associated_token_address(&thief.pubkey(), &receive_mint_pubkey),
+            accounts::token_account(receive_mint_pubkey, thief.pubkey(), THIEF_AMOUNT),
+        ),
+        (
+            vault_pubkey,
+            accounts::token_account(send_mint_pubkey, pda, input.vault_amount),
+        ),
+        (escrow_account_pubkey, escrow_account),
+    ];
+    let mut pubkeys = accounts
+        .iter()
+        .map(|(pubkey, _)| *pubkey)
+        .collect::<Vec<_>>();
+    pubkeys.extend([
+        pda,
+        spl_token::id(),
+        system_program::id(),
+        sysvar::rent::id(),
+        program_id,
+    ]);
+
+    // Only the seller, the taker and the authority of the vault of the escrow may receive
+    // tokens, including the escrow opened by an Initialize instruction, and whoever takes the
+    // escrow if it is open to anyone
+    let payer_pubkey = party(input.payer).pubkey();
+    let mut recipients = vec![seller.pubkey(), taker_pubkey.unwrap_or(payer_pubkey), pda];
+    if let BaseInstruction::Initialize(seed) = input.base {
+        let (escrow_account_pubkey, _) = find_escrow_address(&seller.pubkey(), seed, &program_id);
+        recipients.push(find_authority_address(&escrow_account_pubkey, &program_id).0);
+    }
+
+    // Building the random instruction
+    let mut ix: Instruction = match input.base {
+        BaseInstruction::Initialize(seed) => {
+            let (escrow_account_pubkey, _) =
+                find_escrow_address(&seller.pubkey(), seed, &program_id);
+            escrow_program::instruction::init(
+                program_id,
+                seller.pubkey(),
+                seller_receive_token_account_pubkey,
+                seller_send_token_account_pubkey,
+                send_mint_pubkey,
+                find_vault_address(&escrow_account_pubkey, &program_id).0,
+                escrow_account_pubkey,
+                sysvar::rent::id(),
+                spl_token::id(),
+                system_program::id(),
+                seed,
+                input.seller_amount,
+                input.amount,
+                input.expires_at,
+                None,
+            )
+        }
+        BaseInstruction::Exchange => escrow_program::instruction::exchange(
+            program_id,
+            buyer.pubkey(),
+            buyer_send_token_account_pubkey,
+            buyer_receive_token_account_pubkey,
+            vault_pubkey,
+            seller.pubkey(),
+            seller_receive_token_account_pubkey,
+            escrow_account_pubkey,
+            spl_token::id(),
+            pda,
+            send_mint_pubkey,
+            receive_mint_pubkey,
+            spl_token::id(),
+            input.offered_amount,
+            0,
+            u64::MAX,
+        ),
+        BaseInstruction::Cancel => escrow_program::instruction::cancel(
+            program_id,
+            seller.pubkey(),
+            seller_send_token_account_pubkey,
+            vault_pubkey,
+            escrow_account_pubkey,
+            spl_token::id(),
+            pda,
+            send_mint_pubkey,
+        ),
+        BaseInstruction::Refund => escrow_program::instruction::refund(
+            program_id,
+            seller.pubkey(),
+            seller_send_token_account_pubkey,
+            vault_pubkey,
+            escrow_account_pubkey,
+            spl_token::id(),
+            pda,
+            send_mint_pubkey,
+        ),
+        BaseInstruction::UpdateTerms => escrow_program::instruction::update_terms(
+            program_id,
+            seller.pubkey(),
+            seller_send_token_account_pubkey,
+            vault_pubkey,
+            escrow_account_pubkey,
+            spl_token::id(),
+            pda,
+            send_mint_pubkey,
+            None,
+            0,
+            0,
+            None,
+            None,
+        ),
+        BaseInstruction::Migrate => escrow_program::instruction::migrate(
+            program_id,
+            party(input.payer).pubkey(),
+            escrow_account_pubkey,
+            system_program::id(),
+            vault_pubkey,
+            find_legacy_authority_address(&program_id).0,
+            spl_token::id(),
+        ),
+    };
+    if let Some(data) = input.data {
+        ix.data = data.encode();
+    }
+    for (i, j) in input.swaps {
+        let len = ix.accounts.len();
+        ix.accounts.swap(usize::from(i) % len, usize::from(j) % len);
+    }
+    for (i, j) in input.substitutions {
+        let len = ix.accounts.len();
+        ix.accounts[usize::from(i) % len].pubkey = pubkeys[usize::from(j) % pubkeys.len()];
+    }
+    // Only the payer can sign, whoever the instruction expects
+    let payer = party(input.payer);
+    for account in &mut ix.accounts {
+        account.is_signer &= account.pubkey == payer.pubkey();
+    }
+    pubkeys.extend(ix.accounts.iter().map(|account| account.pubkey));
+    pubkeys.sort();
+    pubkeys.dedup();
+
+    let mut program_test =
+        ProgramTest::new("escrow_program", program_id, processor!(Processor::process));
+    program_test.prefer_bpf(false);
+    for (pubkey, account) in accounts {
+        program_test.add_account(pubkey, Account::from(account));
+    }
+    let (mut banks_client, _, blockhash) = program_test.start().await;
+
+    let before = token_accounts(&mut banks_client, &pubkeys).await;
+    let thief_lamports = banks_client.get_balance(thief.pubkey()).await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], blockhash);
+    // Failing is fine, as long as the processor neither panics nor breaks the invariants
+    match banks_client.process_transaction(tx).await {
+        Ok(()) | Err(BanksClientError::TransactionError(_)) => {}
+        Err(err) => panic!("instruction was not processed: {err}"),
+    }
+    let after = token_accounts(&mut banks_client, &pubkeys).await;
+
+    assert!(
+        banks_client.get_balance(thief.pubkey()).await.unwrap() <= thief_lamports,
+        "the thief gained lamports"
+    );
+
+    assert_eq!(
+        supply(&before),
+        supply(&after),
+        "tokens were created or destroyed"
+    );
+    for (pubkey, token_account) in &after {
+        let before_amount = before.get(pubkey).map_or(0, |before| before.amount);
+        if token_account.amount <= before_amount {
+            continue;
+        }
+        assert!(
+            token_account.owner != thief.pubkey() || recipients.contains(&thief.pubkey()),
+            "the thief received {} tokens in {}",
+            token_account.amount - before_amount,
+            pubkey
+        );
+        assert!(
+            recipients.contains(&token_account.owner),
+            "{} owned by {} received {} tokens",
+            pubkey,
+            token_account.owner,
+            token_account.amount - before_amount
+        );
+    }
+}
+
+fuzz_target!(|input: Input| {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(run(input));
+});
@@ -0,0 +1,327 @@
+use crate::types::{idl_fields, idl_struct};
+use borsh::schema::{BorshSchemaContainer, Definition, Fields};
+use escrow_program::error::{EscrowError, ERROR_CODE_OFFSET};
+use escrow_program::event::EscrowEvent;
+use escrow_program::pda::{AUTHORITY_SEED, ESCROW_SEED, VAULT_SEED};
+use escrow_program::state::{Escrow, EscrowV1};
+use escrow_program::{instruction, Instruction};
+use num_traits::FromPrimitive;
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+
+/// Version of the IDL specification of Anchor followed by the IDL.
+pub const IDL_SPEC: &str = "0.1.0";
+
+/// Path of the IDL committed in the repository.
+pub const IDL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../program/idl.json");
+
+/// Generate the IDL of the escrow program in the format of Anchor.
+///
+/// The instruction arguments, accounts, events and types are read from the borsh schema of the
+/// program, the signer and writable flags of the accounts from the account metas of the
+/// instruction builders, and the errors from [`EscrowError`]. The discriminators are those of the
+/// program rather than the ones Anchor derives: each instruction starts with the borsh tag of its
+/// [`Instruction`] variant, and each event with the tag of its [`EscrowEvent`] variant. The escrow
+/// account starts with [`Escrow::DISCRIMINATOR`] followed by the version of its layout.
+pub fn idl() -> Value {
+    let instruction_schema = BorshSchemaContainer::for_type::<Instruction>();
+    let escrow_schema = BorshSchemaContainer::for_type::<Escrow>();
+    let escrow_v1_schema = BorshSchemaContainer::for_type::<EscrowV1>();
+    let event_schema = BorshSchemaContainer::for_type::<EscrowEvent>();
+
+    let instructions = variants(&instruction_schema)
+        .into_iter()
+        .map(|(discriminant, name, declaration)| {
+            let name = snake_case(&name);
+            json!({
+                "name": name,
+                "discriminator": [discriminant],
+                "accounts": instruction_accounts(&name),
+                "args": idl_fields(&instruction_schema, &declaration),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut types = vec![
+        idl_struct(
+            &escrow_schema,
+            "Escrow",
+            &[
+                "Escrow account state in the newest layout.",
+                "The version of the layout follows the account discriminator, and the account \
+                 ends with bytes reserved for fields added by future versions.",
+            ],
+            vec![json!({ "name": "version", "type": "u8" })],
+        ),
+        idl_struct(
+            &escrow_v1_schema,
+            "EscrowV1",
+            &[
+                "Escrow account state in the first deployed layout, without discriminator.",
+                "The vaults of such escrows are owned by the legacy authority shared by all escrows. \
+                 They must be migrated with the `migrate` instruction before they are used.",
+            ],
+            Vec::new(),
+        ),
+    ];
+    let mut events = Vec::new();
+    for (discriminant, _, declaration) in variants(&event_schema) {
+        // Each variant wraps the struct of the event
+        let declaration = match event_schema.get_definition(&declaration) {
+            Some(Definition::Struct {
+                fields: Fields::UnnamedFields(fields),
+            }) if fields.len() == 1 => fields[0].clone(),
+            _ => declaration,
+        };
+        events.push(json!({ "name": declaration, "discriminator": [discriminant] }));
+        types.push(idl_struct(&event_schema, &declaration, &[], Vec::new()));
+    }
+
+    let errors = (ERROR_CODE_OFFSET..)
+        .map_while(EscrowError::from_u32)
+        .map(|error| {
+            json!({
+                "code": error as u32,
+                "name": format!("{error:?}"),
+                "msg": error.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "address": escrow_program::id().to_string(),
+        "metadata": {
+            "name": "escrow_program",
+            "version": env!("CARGO_PKG_VERSION"),
+            "spec": IDL_SPEC,
+            "description": "Escrow of SPL Token and Token-2022 tokens",
+        },
+        "instructions": instructions,
+        "accounts": [{ "name": "Escrow", "discriminator": Escrow::DISCRIMINATOR }],
+        "events": events,
+        "errors": errors,
+        "types": types,
+    })
+}
+
+/// Get the discriminant, name and declaration of the variants of an enum.
+fn variants(container: &BorshSchemaContainer) -> Vec<(u8, String, String)> {
+    let Some(Definition::Enum {
+        tag_width: 1,
+        variants,
+    }) = container.get_definition(container.declaration())
+    else {
+        panic!(
+            "{} is not an enum tagged by a byte",
+            container.declaration()
+        );
+    };
+
+    variants
+        .iter()
+        .map(|(discriminant, name, declaration)| {
+            let discriminant = u8::try_from(*discriminant).expect("discriminant overflows a byte");
+            (discriminant, name.clone(), declaration.clone())
+        })
+        .collect()
+}
+
+/// Convert the name of a variant to snake case.
+fn snake_case(name: &str) -> String {
+    let mut snake_case = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake_case.push('_');
+        }
+        snake_case.push(c.to_ascii_lowercase());
+    }
+
+    snake_case
+}
+
+/// Account of an instruction.
+struct Account {
+    name: &'static str,
+    docs: Option<&'static str>,
+    address: Option<Pubkey>,
+    pda: Option<Vec<Value>>,
+}
+
+fn account(name: &'static str) -> Account {
+    Account {
+        name,
+        docs: None,
+        address: None,
+        pda: None,
+    }
+}
+
+fn documented_account(name: &'static str, docs: &'static str) -> Account {
+    Account {
+        docs: Some(docs),
+        ..account(name)
+    }
+}
+
+fn address_account(name: &'static str, address: Pubkey) -> Account {
+    Account {
+        address: Some(address),
+        ..account(name)
+    }
+}
+
+fn pda_account(name: &'static str, seeds: Vec<Value>) -> Account {
+    Account {
+        pda: Some(seeds),
+        ..account(name)
+    }
+}
+
+fn const_seed(seed: &[u8]) -> Value {
+    json!({ "kind": "const", "value": seed })
+}
+
+fn account_seed(path: &str) -> Value {
+    json!({ "kind": "account", "path": path })
+}
+
+/// Escrow account of an open escrow, which is not described as a PDA since escrows migrated from
+/// the first layout stay at their keypair address.
+fn escrow_account() -> Account {
+    documented_account(
+        "escrow",
+        "Escrow account, the PDA of the seeds \"escrow\", the seller and the seed of the escrow \
+         unless it was migrated from the first layout",
+    )
+}
+
+/// Vault of an open escrow, read from the escrow rather than derived, for the same reason.
+fn vault_account() -> Account {
+    documented_account(
+        "vault",
+        "Token account at `escrow.temp_token_account_pubkey`, the PDA of the seeds \"vault\" and \
+         the escrow unless the escrow was migrated from the first layout",
+    )
+}
+
+fn authority_account() -> Account {
+    pda_account(
+        "authority",
+        vec![const_seed(AUTHORITY_SEED), account_seed("escrow")],
+    )
+}
+
+/// Get the accounts of the instruction, named in the order of the account metas of its builder.
+fn instruction_accounts(name: &str) -> Vec<Value> {
+    let key = Pubkey::default();
+    let program_id = escrow_program::id();
+    let (ix, accounts) = match name {
+        "initialize" => (
+            instruction::init(
+                program_id, key, key, key, key, key, key, key, key, key, 0, 0, 0, None, None,
+            ),
+            vec![
+                account("seller"),
+                account("seller_receive_token_account"),
+                account("seller_send_token_account"),
+                account("send_mint"),
+                pda_account(
+                    "vault",
+                    vec![const_seed(VAULT_SEED), account_seed("escrow")],
+                ),
+                pda_account(
+                    "escrow",
+                    vec![
+                        const_seed(ESCROW_SEED),
+                        account_seed("seller"),
+                        json!({ "kind": "arg", "path": "seed" }),
+                    ],
+                ),
+                address_account("rent", sysvar::rent::id()),
+                account("token_program"),
+                address_account("system_program", system_program::id()),
+            ],
+        ),
+        "exchange" => (
+            instruction::exchange(
+                program_id, key, key, key, key, key, key, key, key, key, key, key, key, 0, 0, 0,
+            ),
+            vec![
+                account("buyer"),
+                account("buyer_send_token_account"),
+                account("buyer_receive_token_account"),
+                vault_account(),
+                account("seller"),
+                account("seller_receive_token_account"),
+                escrow_account(),
+                account("token_program"),
+                authority_account(),
+                account("send_mint"),
+                account("receive_mint"),
+                account("receive_token_program"),
+            ],
+        ),
+        "cancel" | "refund" | "update_terms" => (
+            match name {
+                "cancel" => instruction::cancel(program_id, key, key, key, key, key, key, key),
+                "refund" => instruction::refund(program_id, key, key, key, key, key, key, key),
+                _ => instruction::update_terms(
+                    program_id, key, key, key, key, key, key, key, None, 0, 0, None, None,
+                ),
+            },
+            vec![
+                account("seller"),
+                account("seller_send_token_account"),
+                vault_account(),
+                escrow_account(),
+                account("token_program"),
+                authority_account(),
+                account("send_mint"),
+            ],
+        ),
+        "migrate" => (
+            instruction::migrate(program_id, key, key, key, key, key, key),
+            vec![
+                account("payer"),
+                account("escrow"),
+                address_account("system_program", system_program::id()),
+                // Vaults of the first layout are not derived from the escrow
+                account("vault"),
+                pda_account("legacy_authority", vec![const_seed(ESCROW_SEED)]),
+                account("token_program"),
+            ],
+        ),
+        _ => panic!("the accounts of the {name} instruction are not described"),
+    };
+    assert_eq!(
+        ix.accounts.len(),
+        accounts.len(),
+        "the accounts of the {name} instruction do not match its builder"
+    );
+
+    ix.accounts
+        .iter()
+        .zip(accounts)
+        .map(|(meta, account)| {
+            // Anchor omits the flags that are not set
+            let mut value = json!({ "name": account.name });
+            if let Some(docs) = account.docs {
+                value["docs"] = json!([docs]);
+            }
+            if meta.is_writable {
+                value["writable"] = json!(true);
+            }
+            if meta.is_signer {
+                value["signer"] = json!(true);
+            }
+            if let Some(address) = account.address {
+                value["address"] = json!(address.to_string());
+            }
+            if let Some(seeds) = account.pda {
+                value["pda"] = json!({ "seeds": seeds });
+            }
+            value
+        })
+        .collect()
+}
@@ -0,0 +1,4 @@
+mod idl;
+mod types;
+
+pub use crate::idl::{idl, IDL_PATH, IDL_SPEC};
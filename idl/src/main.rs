@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+/// Write the IDL of the escrow program to the path given as argument, by default the IDL
+/// committed in the repository.
+fn main() {
+    let path = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(escrow_idl::IDL_PATH));
+    let idl = serde_json::to_string_pretty(&escrow_idl::idl()).expect("Failed to serialize IDL");
+
+    if let Err(err) = fs::write(&path, format!("{idl}\n")) {
+        eprintln!("Failed to write {}: {err}", path.display());
+        process::exit(1);
+    }
+    println!("Wrote {}", path.display());
+}
@@ -0,0 +1,79 @@
+use borsh::schema::{BorshSchemaContainer, Definition, Fields};
+use serde_json::{json, Value};
+
+/// Convert the declaration of a borsh type to an IDL type.
+pub fn idl_type(container: &BorshSchemaContainer, declaration: &str) -> Value {
+    match declaration {
+        "Pubkey" => return json!("pubkey"),
+        "String" => return json!("string"),
+        "bool" | "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64" | "i128" => {
+            return json!(declaration)
+        }
+        _ => {}
+    }
+
+    match container.get_definition(declaration) {
+        Some(Definition::Enum { variants, .. }) if declaration.starts_with("Option<") => {
+            let (_, _, some) = variants
+                .iter()
+                .find(|(_, name, _)| name == "Some")
+                .unwrap_or_else(|| panic!("{declaration} has no Some variant"));
+            json!({ "option": idl_type(container, some) })
+        }
+        Some(Definition::Sequence {
+            length_width: Definition::ARRAY_LENGTH_WIDTH,
+            length_range,
+            elements,
+        }) if length_range.start() == length_range.end() => {
+            json!({ "array": [idl_type(container, elements), length_range.start()] })
+        }
+        Some(Definition::Sequence {
+            length_width: Definition::DEFAULT_LENGTH_WIDTH,
+            elements,
+            ..
+        }) => json!({ "vec": idl_type(container, elements) }),
+        Some(Definition::Struct { .. } | Definition::Enum { .. }) => {
+            json!({ "defined": { "name": declaration } })
+        }
+        definition => panic!("{declaration} has no IDL type: {definition:?}"),
+    }
+}
+
+/// Get the fields of a struct as IDL fields.
+pub fn idl_fields(container: &BorshSchemaContainer, declaration: &str) -> Vec<Value> {
+    match container.get_definition(declaration) {
+        Some(Definition::Struct {
+            fields: Fields::NamedFields(fields),
+        }) => fields
+            .iter()
+            .map(|(name, declaration)| {
+                json!({ "name": name, "type": idl_type(container, declaration) })
+            })
+            .collect(),
+        Some(Definition::Struct {
+            fields: Fields::Empty,
+        }) => Vec::new(),
+        definition => panic!("{declaration} is not a struct with named fields: {definition:?}"),
+    }
+}
+
+/// Get the IDL type definition of a struct, with the header fields preceding its own.
+pub fn idl_struct(
+    container: &BorshSchemaContainer,
+    declaration: &str,
+    docs: &[&str],
+    header: Vec<Value>,
+) -> Value {
+    let mut fields = header;
+    fields.extend(idl_fields(container, declaration));
+
+    let mut value = json!({
+        "name": declaration,
+        "type": { "kind": "struct", "fields": fields },
+    });
+    if !docs.is_empty() {
+        value["docs"] = json!(docs);
+    }
+
+    value
+}
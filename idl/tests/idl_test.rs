@@ -0,0 +1,156 @@
+use borsh::BorshDeserialize;
+use escrow_program::error::EscrowError;
+use escrow_program::event::{EscrowCancelled, EscrowEvent};
+use escrow_program::state::Escrow;
+use escrow_program::Instruction;
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::fs;
+
+/// Get the object named in the list of the IDL.
+fn named<'a>(idl: &'a Value, list: &str, name: &str) -> &'a Value {
+    idl[list]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["name"] == name)
+        .unwrap_or_else(|| panic!("{name} not found in {list}"))
+}
+
+#[test]
+fn test_idl_up_to_date() {
+    let idl = escrow_idl::idl();
+    let committed = fs::read_to_string(escrow_idl::IDL_PATH).unwrap_or_default();
+
+    assert!(
+        serde_json::from_str::<Value>(&committed).ok() == Some(idl),
+        "program/idl.json is out of date, regenerate it with `cargo run -p escrow-idl`"
+    );
+}
+
+#[test]
+fn test_instruction_discriminators() {
+    let idl = escrow_idl::idl();
+
+    // Each instruction starts with its discriminator, followed by its arguments
+    let data = borsh::to_vec(&Instruction::Exchange {
+        amount: 1,
+        expected_receive: 2,
+        max_pay: 3,
+    })
+    .unwrap();
+    let exchange = named(&idl, "instructions", "exchange");
+    assert_eq!(exchange["discriminator"], serde_json::json!([data[0]]));
+    assert_eq!(exchange["args"].as_array().unwrap().len(), 3);
+    assert_eq!(data.len(), 1 + 3 * 8);
+
+    let update_terms = named(&idl, "instructions", "update_terms");
+    let data = borsh::to_vec(&Instruction::UpdateTerms {
+        amount: None,
+        deposit_amount: 0,
+        withdraw_amount: 0,
+        expires_at: None,
+        taker: None,
+    })
+    .unwrap();
+    assert_eq!(update_terms["discriminator"], serde_json::json!([data[0]]));
+    assert_eq!(
+        update_terms["args"][3]["type"],
+        serde_json::json!({ "option": { "option": "i64" } })
+    );
+}
+
+#[test]
+fn test_instruction_accounts() {
+    let idl = escrow_idl::idl();
+
+    let initialize = named(&idl, "instructions", "initialize");
+    let seller = named(initialize, "accounts", "seller");
+    assert_eq!(seller["signer"], true);
+    assert_eq!(seller["writable"], true);
+    let escrow = named(initialize, "accounts", "escrow");
+    assert_eq!(escrow["signer"], Value::Null);
+    assert_eq!(escrow["writable"], true);
+    assert_eq!(
+        escrow["pda"]["seeds"][0]["value"],
+        serde_json::json!(b"escrow")
+    );
+    assert_eq!(escrow["pda"]["seeds"][2]["path"], "seed");
+
+    // Escrows migrated from the first layout are neither at the PDA nor with the vault derived
+    // from the seeds of initialize
+    let cancel = named(&idl, "instructions", "cancel");
+    assert_eq!(named(cancel, "accounts", "escrow")["pda"], Value::Null);
+    let vault = named(cancel, "accounts", "vault");
+    assert_eq!(vault["pda"], Value::Null);
+    assert!(vault["docs"][0]
+        .as_str()
+        .unwrap()
+        .contains("escrow.temp_token_account_pubkey"));
+    assert_eq!(
+        named(cancel, "accounts", "authority")["pda"]["seeds"][0]["value"],
+        serde_json::json!(b"authority")
+    );
+    let accounts = cancel["accounts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|account| account["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        accounts,
+        [
+            "seller",
+            "seller_send_token_account",
+            "vault",
+            "escrow",
+            "token_program",
+            "authority",
+            "send_mint"
+        ]
+    );
+}
+
+#[test]
+fn test_accounts_and_events() {
+    let idl = escrow_idl::idl();
+
+    let escrow = named(&idl, "accounts", "Escrow");
+    assert_eq!(
+        escrow["discriminator"],
+        serde_json::json!(Escrow::DISCRIMINATOR)
+    );
+    let escrow_type = named(&idl, "types", "Escrow");
+    assert_eq!(escrow_type["type"]["fields"][0]["name"], "version");
+
+    // Events are logged with the discriminator of the event followed by its fields
+    let event = EscrowEvent::Cancelled(EscrowCancelled {
+        escrow: Pubkey::new_unique(),
+        seller: Pubkey::new_unique(),
+        send_mint: Pubkey::new_unique(),
+        amount: 100,
+        refunded: false,
+        slot: 1,
+    });
+    let data = borsh::to_vec(&event).unwrap();
+    let cancelled = named(&idl, "events", "EscrowCancelled");
+    assert_eq!(cancelled["discriminator"], serde_json::json!([data[0]]));
+    let updated = named(&idl, "events", "EscrowUpdated");
+    assert_eq!(updated["discriminator"], serde_json::json!([3]));
+    assert_eq!(
+        EscrowCancelled::try_from_slice(&data[1..]).unwrap(),
+        match event {
+            EscrowEvent::Cancelled(cancelled) => cancelled,
+            _ => unreachable!(),
+        }
+    );
+}
+
+#[test]
+fn test_errors() {
+    let idl = escrow_idl::idl();
+
+    let error = named(&idl, "errors", "SellerMismatch");
+    assert_eq!(error["code"], EscrowError::SellerMismatch as u32);
+    assert_eq!(error["msg"], EscrowError::SellerMismatch.to_string());
+}
@@ -1,7 +1,9 @@
+use crate::error::EscrowError;
 use crate::processor::Processor;
 use solana_program::account_info::AccountInfo;
 use solana_program::entrypoint;
 use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::PrintProgramError;
 use solana_program::pubkey::Pubkey;
 
 entrypoint!(process_instruction);
@@ -12,5 +14,9 @@ fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    Processor::process(program_id, accounts, instruction_data)
+    if let Err(error) = Processor::process(program_id, accounts, instruction_data) {
+        error.print::<EscrowError>();
+        return Err(error);
+    }
+    Ok(())
 }
@@ -0,0 +1,144 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_program::decode_error::DecodeError;
+use solana_program::msg;
+use solana_program::program_error::{PrintProgramError, ProgramError};
+
+/// Offset of the custom error codes of the escrow program.
+///
+/// The token programs number their errors from zero, and their errors propagate unchanged from the
+/// transfers made by the escrow program, so the codes of the escrow program start far above them.
+pub const ERROR_CODE_OFFSET: u32 = 6000;
+
+/// Errors that may be returned by the escrow program.
+///
+/// The discriminant of each variant is the custom error code returned by the program, starting at
+/// [`ERROR_CODE_OFFSET`], and is therefore stable: new variants are appended, existing ones are
+/// never renumbered.
+///
+/// # Example
+///
+/// ```rust
+/// # use escrow_program::error::EscrowError;
+/// # use solana_program::decode_error::DecodeError;
+/// # use solana_program::program_error::ProgramError;
+/// #
+/// let error = ProgramError::from(EscrowError::SellerMismatch);
+/// assert_eq!(error, ProgramError::Custom(6001));
+///
+/// let ProgramError::Custom(code) = error else { unreachable!() };
+/// let decoded = EscrowError::decode_custom_error_to_enum(code);
+/// assert_eq!(decoded, Some(EscrowError::SellerMismatch));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq, FromPrimitive, thiserror::Error)]
+pub enum EscrowError {
+    /// The amount is zero or exceeds the tokens remaining in the escrow
+    #[error("Amount does not fit the remaining deposited tokens")]
+    AmountMismatch = 6000,
+
+    /// The seller account does not match the seller of the escrow
+    #[error("Seller account does not match the escrow")]
+    SellerMismatch = 6001,
+
+    /// The seller token account does not match the one registered in the escrow
+    #[error("Seller token account does not match the escrow")]
+    SellerTokenAccountMismatch = 6002,
+
+    /// The temporary token account does not match the vault of the escrow
+    #[error("Temporary token account does not match the escrow")]
+    TempAccountMismatch = 6003,
+
+    /// The account does not hold enough lamports to be rent exempt
+    #[error("Account is not rent exempt")]
+    NotRentExempt = 6004,
+
+    /// The escrow account has already been initialized
+    #[error("Escrow account is already initialized")]
+    AlreadyInitialized = 6005,
+
+    /// The escrow account has not been initialized
+    #[error("Escrow account is not initialized")]
+    NotInitialized = 6006,
+
+    /// The escrow account is not derived from the seller and the seed
+    #[error("Escrow account does not match the seller and seed")]
+    EscrowAddressMismatch = 6007,
+
+    /// The token account is not owned by the token program
+    #[error("Token account is not owned by the token program")]
+    TokenProgramMismatch = 6008,
+
+    /// The seller did not sign the instruction
+    #[error("Seller account is not a signer")]
+    SellerNotSigner = 6009,
+
+    /// The buyer did not sign the instruction
+    #[error("Buyer account is not a signer")]
+    BuyerNotSigner = 6010,
+
+    /// The escrow account is not owned by the escrow program
+    #[error("Escrow account is not owned by the escrow program")]
+    InvalidEscrowOwner = 6011,
+
+    /// The PDA account is not the authority derived from the escrow account
+    #[error("PDA account does not match the escrow")]
+    AuthorityMismatch = 6012,
+
+    /// The token program is not a supported token program
+    #[error("Token program is not supported")]
+    InvalidTokenProgram = 6013,
+
+    /// The system program is not the system program
+    #[error("System program is invalid")]
+    InvalidSystemProgram = 6014,
+
+    /// The escrow has passed its expiration and can no longer be exchanged
+    #[error("Escrow has expired")]
+    Expired = 6015,
+
+    /// The escrow has not expired yet and cannot be refunded
+    #[error("Escrow has not expired")]
+    NotExpired = 6016,
+
+    /// The buyer is not the taker designated by the seller
+    #[error("Buyer is not the designated taker")]
+    TakerMismatch = 6017,
+
+    /// The escrow account does not start with the escrow discriminator, it may use the
+    /// unversioned layout and need to be migrated
+    #[error("Escrow account has an invalid discriminator")]
+    InvalidDiscriminator = 6018,
+
+    /// The layout version of the escrow account is not supported by the program
+    #[error("Escrow account version is not supported")]
+    UnsupportedVersion = 6019,
+
+    /// The buyer would receive less than the expected amount
+    #[error("Received amount is below the expected amount")]
+    ReceiveBelowExpected = 6020,
+
+    /// The buyer would pay more than the maximum amount
+    #[error("Paid amount exceeds the maximum amount")]
+    PayExceedsMaximum = 6021,
+}
+
+impl From<EscrowError> for ProgramError {
+    fn from(e: EscrowError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for EscrowError {
+    fn type_of() -> &'static str {
+        "EscrowError"
+    }
+}
+
+impl PrintProgramError for EscrowError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        msg!("Error: {}", self);
+    }
+}
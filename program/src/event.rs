@@ -0,0 +1,165 @@
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::log::sol_log_data;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+
+/// Events logged by the escrow program for indexers.
+///
+/// Each event is logged with `sol_log_data` as the borsh serialized `EscrowEvent`, so the first
+/// byte of the data identifies the event: 0 for `Initialized`, 1 for `Exchanged`, 2 for
+/// `Cancelled` and 3 for `Updated`. New events are only ever appended.
+///
+/// # Example
+///
+/// ```rust
+/// # use borsh::BorshDeserialize;
+/// # use escrow_program::event::{EscrowEvent, EscrowUpdated};
+/// # use solana_program::pubkey::Pubkey;
+/// #
+/// let event = EscrowEvent::Updated(EscrowUpdated {
+///     escrow: Pubkey::new_unique(),
+///     seller: Pubkey::new_unique(),
+///     send_mint: Pubkey::new_unique(),
+///     amount: 100,
+///     offered_amount: 50,
+///     expires_at: None,
+///     taker: None,
+///     slot: 1,
+/// });
+/// let data = borsh::to_vec(&event).unwrap();
+///
+/// assert_eq!(data[0], 3);
+/// assert_eq!(EscrowEvent::try_from_slice(&data).unwrap(), event);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum EscrowEvent {
+    /// The seller opened an escrow
+    Initialized(EscrowInitialized),
+    /// A buyer took part or all of the tokens of an escrow
+    Exchanged(EscrowExchanged),
+    /// The seller cancelled an escrow, or anyone refunded it after it expired
+    Cancelled(EscrowCancelled),
+    /// The seller updated the terms of an escrow
+    Updated(EscrowUpdated),
+}
+
+impl EscrowEvent {
+    /// Log the event.
+    pub fn emit(&self) -> Result<(), ProgramError> {
+        sol_log_data(&[&borsh::to_vec(self)?]);
+
+        Ok(())
+    }
+}
+
+/// Terms of an escrow opened by the seller.
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct EscrowInitialized {
+    /// The escrow account
+    pub escrow: Pubkey,
+
+    /// The seller who opened the escrow
+    pub seller: Pubkey,
+
+    /// Mint of the deposited tokens
+    pub send_mint: Pubkey,
+
+    /// Mint of the tokens expected by the seller
+    pub receive_mint: Pubkey,
+
+    /// Amount of tokens deposited in the vault
+    pub offered_amount: u64,
+
+    /// Amount of tokens expected by the seller for all of the deposited tokens
+    pub amount: u64,
+
+    /// Unix timestamp after which the escrow expires, if any
+    pub expires_at: Option<i64>,
+
+    /// The only buyer allowed to take the escrow, if any
+    pub taker: Option<Pubkey>,
+
+    /// Slot in which the escrow was opened
+    pub slot: u64,
+}
+
+/// Fill of an escrow by a buyer.
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct EscrowExchanged {
+    /// The escrow account
+    pub escrow: Pubkey,
+
+    /// The seller who opened the escrow
+    pub seller: Pubkey,
+
+    /// The buyer who took the tokens
+    pub buyer: Pubkey,
+
+    /// Mint of the deposited tokens
+    pub send_mint: Pubkey,
+
+    /// Mint of the tokens expected by the seller
+    pub receive_mint: Pubkey,
+
+    /// Amount of the deposited tokens taken by the buyer
+    pub amount: u64,
+
+    /// Amount of tokens paid by the buyer, including the transfer fee
+    pub paid_amount: u64,
+
+    /// Amount of the deposited tokens remaining after the fill, zero once the escrow is closed
+    pub remaining_amount: u64,
+
+    /// Slot in which the escrow was exchanged
+    pub slot: u64,
+}
+
+/// Closing of an escrow before it was filled.
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct EscrowCancelled {
+    /// The escrow account
+    pub escrow: Pubkey,
+
+    /// The seller who opened the escrow
+    pub seller: Pubkey,
+
+    /// Mint of the deposited tokens
+    pub send_mint: Pubkey,
+
+    /// Amount of tokens returned to the seller
+    pub amount: u64,
+
+    /// Whether the escrow was refunded after it expired rather than cancelled by the seller
+    pub refunded: bool,
+
+    /// Slot in which the escrow was closed
+    pub slot: u64,
+}
+
+/// Terms of an escrow after the seller updated them.
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct EscrowUpdated {
+    /// The escrow account
+    pub escrow: Pubkey,
+
+    /// The seller who opened the escrow
+    pub seller: Pubkey,
+
+    /// Mint of the deposited tokens
+    pub send_mint: Pubkey,
+
+    /// Amount of tokens expected by the seller for all of the deposited tokens
+    pub amount: u64,
+
+    /// Amount of tokens deposited in the vault, all of which remain to be taken
+    pub offered_amount: u64,
+
+    /// Unix timestamp after which the escrow expires, if any
+    pub expires_at: Option<i64>,
+
+    /// The only buyer allowed to take the escrow, if any
+    pub taker: Option<Pubkey>,
+
+    /// Slot in which the escrow was updated
+    pub slot: u64,
+}
@@ -3,6 +3,9 @@ use solana_program::instruction::AccountMeta;
 use solana_program::pubkey::Pubkey;
 
 /// Instruction definition.
+///
+/// Both sides of the trade may use either the SPL Token program or the Token-2022 program. Mints
+/// with the transfer hook extension are not supported.
 #[derive(BorshSerialize, BorshDeserialize, BorshSchema)]
 pub enum Instruction {
     /// Initialize the escrow agent and enable the transaction.
@@ -10,14 +13,31 @@ pub enum Instruction {
     ///
     /// Accounts expected:
     ///
-    ///   0. `[signer]` The account of the person initializing the escrow
+    ///   0. `[signer, writable]` The account of the person initializing the escrow, to pay the rent fees
     ///   1. `[]` The initializer's token account for the token they will receive should the trade go through
-    ///   2. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
-    ///   3. `[writable]` The escrow account, it will hold all necessary info about the trade.
-    ///   4. `[]` The rent sysvar
-    ///   5. `[]` The token program
-    Initialize(u64),
-    /// Accepts a trade
+    ///   2. `[writable]` The initializer's token account for the token they deposit
+    ///   3. `[]` The mint of the token they deposit
+    ///   4. `[writable]` The vault token account, a PDA derived from the escrow account, to be created
+    ///   5. `[writable]` The escrow account, a PDA derived from the initializer and the seed, to be created
+    ///   6. `[]` The rent sysvar
+    ///   7. `[]` The token program of the token they deposit
+    ///   8. `[]` The system program
+    Initialize {
+        /// Seed to derive the escrow account
+        seed: u64,
+        /// Amount of tokens deposited by the initializer
+        deposit_amount: u64,
+        /// Amount of tokens expected by the initializer
+        amount: u64,
+        /// Unix timestamp after which the escrow expires, if any
+        expires_at: Option<i64>,
+        /// The only buyer allowed to take the escrow, if any
+        taker: Option<Pubkey>,
+    },
+    /// Accepts a trade, taking the given amount of the deposited tokens
+    ///
+    /// The amount may be any part of the tokens remaining in the escrow, priced pro rata to the
+    /// posted rate. The accounts are closed once all of the deposited tokens have been taken.
     ///
     ///
     /// Accounts expected:
@@ -29,9 +49,99 @@ pub enum Instruction {
     ///   4. `[writable]` The initializer's main account to send their rent fees to
     ///   5. `[writable]` The initializer's token account that will receive tokens
     ///   6. `[writable]` The escrow account holding the escrow info
-    ///   7. `[]` The token program
+    ///   7. `[]` The token program of the deposited token
     ///   8. `[]` The PDA account
-    Exchange(u64),
+    ///   9. `[writable]` The mint of the deposited token, to collect withheld transfer fees
+    ///   10. `[]` The mint of the token the initializer will receive
+    ///   11. `[]` The token program of the token the initializer will receive
+    ///
+    /// If the mint of the token the initializer will receive charges a transfer fee, the taker
+    /// sends the amount including the fee so that the initializer receives the expected amount.
+    Exchange {
+        /// Amount of the deposited tokens to take
+        amount: u64,
+        /// Minimum amount of tokens the taker receives, after the transfer fee of the deposited
+        /// token
+        expected_receive: u64,
+        /// Maximum amount of tokens the taker pays, including the transfer fee of the token the
+        /// initializer will receive
+        max_pay: u64,
+    },
+    /// Cancels a trade and returns the deposited tokens to the seller
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    ///   0. `[signer, writable]` The account of the person who initialized the escrow, to receive the rent fees
+    ///   1. `[writable]` The initializer's token account to receive the deposited tokens back
+    ///   2. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    ///   3. `[writable]` The escrow account holding the escrow info
+    ///   4. `[]` The token program of the deposited token
+    ///   5. `[]` The PDA account
+    ///   6. `[writable]` The mint of the deposited token, to collect withheld transfer fees
+    Cancel,
+    /// Refunds an expired trade and returns the deposited tokens to the seller
+    ///
+    /// Anyone may send this instruction once the escrow has expired.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    ///   0. `[writable]` The account of the person who initialized the escrow, to receive the rent fees
+    ///   1. `[writable]` The initializer's token account to receive the deposited tokens back
+    ///   2. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    ///   3. `[writable]` The escrow account holding the escrow info
+    ///   4. `[]` The token program of the deposited token
+    ///   5. `[]` The PDA account
+    ///   6. `[writable]` The mint of the deposited token, to collect withheld transfer fees
+    Refund,
+    /// Migrates an escrow account in an older layout to the newest layout
+    ///
+    /// Anyone may send this instruction. The account is reallocated and the payer funds the
+    /// additional rent. The vault of an escrow in the first layout is moved from the legacy PDA
+    /// shared by all escrows to the PDA of the escrow account, and its balance is recorded as the
+    /// deposited amount. Migrating an account already in the newest layout does nothing.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    ///   0. `[signer, writable]` The account paying for the additional rent
+    ///   1. `[writable]` The escrow account to migrate
+    ///   2. `[]` The system program
+    ///   3. `[writable]` The PDA's temp token account holding the deposited tokens
+    ///   4. `[]` The legacy PDA account shared by the escrows in the first layout
+    ///   5. `[]` The token program of the deposited token
+    Migrate,
+    /// Updates the terms of an open escrow
+    ///
+    /// The seller may change the expected amount, deposit more tokens or withdraw part of them,
+    /// and change the expiry or the taker. The escrow is rebased on the tokens remaining in the
+    /// vault after the update: the fills made so far are settled and the expected amount, if
+    /// unchanged, keeps the rate of the tokens that remained. An `EscrowUpdated` event is logged
+    /// with the new terms.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    ///   0. `[signer]` The account of the person who initialized the escrow
+    ///   1. `[writable]` The initializer's token account to deposit tokens from or withdraw them to
+    ///   2. `[writable]` The PDA's temp token account holding the deposited tokens
+    ///   3. `[writable]` The escrow account holding the escrow info
+    ///   4. `[]` The token program of the deposited token
+    ///   5. `[]` The PDA account
+    ///   6. `[]` The mint of the deposited token
+    UpdateTerms {
+        /// Amount of tokens expected by the initializer for all of the remaining tokens, if changed
+        amount: Option<u64>,
+        /// Amount of tokens deposited in addition by the initializer
+        deposit_amount: u64,
+        /// Amount of the remaining tokens withdrawn by the initializer
+        withdraw_amount: u64,
+        /// Unix timestamp after which the escrow expires, if changed
+        expires_at: Option<Option<i64>>,
+        /// The only buyer allowed to take the escrow, if changed
+        taker: Option<Option<Pubkey>>,
+    },
 }
 
 /// Create initialization instructions for escrow.
@@ -40,22 +150,38 @@ pub fn init(
     escrow_program_id: Pubkey,
     seller_account_pubkey: Pubkey,
     seller_token_account_pubkey: Pubkey,
+    seller_send_token_account_pubkey: Pubkey,
+    send_mint_pubkey: Pubkey,
     temp_token_account_pubkey: Pubkey,
     escrow_account_pubkey: Pubkey,
     rent_pubkey: Pubkey,
     token_program_pubkey: Pubkey,
+    system_program_pubkey: Pubkey,
+    seed: u64,
+    deposit_amount: u64,
     amount: u64,
+    expires_at: Option<i64>,
+    taker: Option<Pubkey>,
 ) -> solana_program::instruction::Instruction {
     solana_program::instruction::Instruction::new_with_borsh(
         escrow_program_id,
-        &Instruction::Initialize(amount),
+        &Instruction::Initialize {
+            seed,
+            deposit_amount,
+            amount,
+            expires_at,
+            taker,
+        },
         vec![
             AccountMeta::new(seller_account_pubkey, true),
             AccountMeta::new_readonly(seller_token_account_pubkey, false),
+            AccountMeta::new(seller_send_token_account_pubkey, false),
+            AccountMeta::new_readonly(send_mint_pubkey, false),
             AccountMeta::new(temp_token_account_pubkey, false),
             AccountMeta::new(escrow_account_pubkey, false),
             AccountMeta::new_readonly(rent_pubkey, false),
             AccountMeta::new_readonly(token_program_pubkey, false),
+            AccountMeta::new_readonly(system_program_pubkey, false),
         ],
     )
 }
@@ -73,11 +199,20 @@ pub fn exchange(
     escrow_account_pubkey: Pubkey,
     token_program_pubkey: Pubkey,
     pda_account_pubkey: Pubkey,
+    send_mint_pubkey: Pubkey,
+    receive_mint_pubkey: Pubkey,
+    receive_token_program_pubkey: Pubkey,
     amount: u64,
+    expected_receive: u64,
+    max_pay: u64,
 ) -> solana_program::instruction::Instruction {
     solana_program::instruction::Instruction::new_with_borsh(
         escrow_program_id,
-        &Instruction::Exchange(amount),
+        &Instruction::Exchange {
+            amount,
+            expected_receive,
+            max_pay,
+        },
         vec![
             AccountMeta::new(buyer_account_pubkey, true),
             AccountMeta::new(buyer_send_token_account_pubkey, false),
@@ -88,6 +223,125 @@ pub fn exchange(
             AccountMeta::new(escrow_account_pubkey, false),
             AccountMeta::new_readonly(token_program_pubkey, false),
             AccountMeta::new_readonly(pda_account_pubkey, false),
+            AccountMeta::new(send_mint_pubkey, false),
+            AccountMeta::new_readonly(receive_mint_pubkey, false),
+            AccountMeta::new_readonly(receive_token_program_pubkey, false),
+        ],
+    )
+}
+
+/// Create cancellation instructions for escrow.
+#[allow(clippy::too_many_arguments)]
+pub fn cancel(
+    escrow_program_id: Pubkey,
+    seller_account_pubkey: Pubkey,
+    seller_send_token_account_pubkey: Pubkey,
+    temp_token_account_pubkey: Pubkey,
+    escrow_account_pubkey: Pubkey,
+    token_program_pubkey: Pubkey,
+    pda_account_pubkey: Pubkey,
+    send_mint_pubkey: Pubkey,
+) -> solana_program::instruction::Instruction {
+    solana_program::instruction::Instruction::new_with_borsh(
+        escrow_program_id,
+        &Instruction::Cancel,
+        vec![
+            AccountMeta::new(seller_account_pubkey, true),
+            AccountMeta::new(seller_send_token_account_pubkey, false),
+            AccountMeta::new(temp_token_account_pubkey, false),
+            AccountMeta::new(escrow_account_pubkey, false),
+            AccountMeta::new_readonly(token_program_pubkey, false),
+            AccountMeta::new_readonly(pda_account_pubkey, false),
+            AccountMeta::new(send_mint_pubkey, false),
+        ],
+    )
+}
+
+/// Create refund instructions for an expired escrow.
+#[allow(clippy::too_many_arguments)]
+pub fn refund(
+    escrow_program_id: Pubkey,
+    seller_account_pubkey: Pubkey,
+    seller_send_token_account_pubkey: Pubkey,
+    temp_token_account_pubkey: Pubkey,
+    escrow_account_pubkey: Pubkey,
+    token_program_pubkey: Pubkey,
+    pda_account_pubkey: Pubkey,
+    send_mint_pubkey: Pubkey,
+) -> solana_program::instruction::Instruction {
+    solana_program::instruction::Instruction::new_with_borsh(
+        escrow_program_id,
+        &Instruction::Refund,
+        vec![
+            AccountMeta::new(seller_account_pubkey, false),
+            AccountMeta::new(seller_send_token_account_pubkey, false),
+            AccountMeta::new(temp_token_account_pubkey, false),
+            AccountMeta::new(escrow_account_pubkey, false),
+            AccountMeta::new_readonly(token_program_pubkey, false),
+            AccountMeta::new_readonly(pda_account_pubkey, false),
+            AccountMeta::new(send_mint_pubkey, false),
+        ],
+    )
+}
+
+/// Create migration instructions for escrow.
+pub fn migrate(
+    escrow_program_id: Pubkey,
+    payer_account_pubkey: Pubkey,
+    escrow_account_pubkey: Pubkey,
+    system_program_pubkey: Pubkey,
+    temp_token_account_pubkey: Pubkey,
+    legacy_pda_account_pubkey: Pubkey,
+    token_program_pubkey: Pubkey,
+) -> solana_program::instruction::Instruction {
+    solana_program::instruction::Instruction::new_with_borsh(
+        escrow_program_id,
+        &Instruction::Migrate,
+        vec![
+            AccountMeta::new(payer_account_pubkey, true),
+            AccountMeta::new(escrow_account_pubkey, false),
+            AccountMeta::new_readonly(system_program_pubkey, false),
+            AccountMeta::new(temp_token_account_pubkey, false),
+            AccountMeta::new_readonly(legacy_pda_account_pubkey, false),
+            AccountMeta::new_readonly(token_program_pubkey, false),
+        ],
+    )
+}
+
+/// Create update instructions for escrow.
+#[allow(clippy::too_many_arguments)]
+pub fn update_terms(
+    escrow_program_id: Pubkey,
+    seller_account_pubkey: Pubkey,
+    seller_send_token_account_pubkey: Pubkey,
+    temp_token_account_pubkey: Pubkey,
+    escrow_account_pubkey: Pubkey,
+    token_program_pubkey: Pubkey,
+    pda_account_pubkey: Pubkey,
+    send_mint_pubkey: Pubkey,
+    amount: Option<u64>,
+    deposit_amount: u64,
+    withdraw_amount: u64,
+    expires_at: Option<Option<i64>>,
+    taker: Option<Option<Pubkey>>,
+) -> solana_program::instruction::Instruction {
+    solana_program::instruction::Instruction::new_with_borsh(
+        escrow_program_id,
+        &Instruction::UpdateTerms {
+            amount,
+            deposit_amount,
+            withdraw_amount,
+            expires_at,
+            taker,
+        },
+        vec![
+            AccountMeta::new_readonly(seller_account_pubkey, true),
+            AccountMeta::new(seller_send_token_account_pubkey, false),
+            AccountMeta::new(temp_token_account_pubkey, false),
+            AccountMeta::new(escrow_account_pubkey, false),
+            AccountMeta::new_readonly(token_program_pubkey, false),
+            AccountMeta::new_readonly(pda_account_pubkey, false),
+            AccountMeta::new_readonly(send_mint_pubkey, false),
         ],
     )
 }
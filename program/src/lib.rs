@@ -2,7 +2,10 @@ use solana_program::declare_id;
 
 pub use crate::instruction::Instruction;
 
+pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod pda;
 pub mod processor;
 pub mod state;
 
@@ -0,0 +1,64 @@
+use solana_program::pubkey::Pubkey;
+
+/// Seed prefix of the escrow account.
+pub const ESCROW_SEED: &[u8] = b"escrow";
+
+/// Seed prefix of the token account (vault) holding the tokens deposited in an escrow.
+pub const VAULT_SEED: &[u8] = b"vault";
+
+/// Seed prefix of the PDA that owns the vault of an escrow.
+pub const AUTHORITY_SEED: &[u8] = b"authority";
+
+/// Find the escrow account of the seller for the seed.
+///
+/// # Example
+///
+/// ```rust
+/// # use escrow_program::pda::find_escrow_address;
+/// # use solana_program::pubkey::Pubkey;
+/// #
+/// let seller_pubkey = Pubkey::new_unique();
+/// let (escrow, _) = find_escrow_address(&seller_pubkey, 1, &escrow_program::id());
+///
+/// assert_eq!(escrow, find_escrow_address(&seller_pubkey, 1, &escrow_program::id()).0);
+/// assert_ne!(escrow, find_escrow_address(&seller_pubkey, 2, &escrow_program::id()).0);
+/// ```
+pub fn find_escrow_address(seller_pubkey: &Pubkey, seed: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ESCROW_SEED, seller_pubkey.as_ref(), &seed.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Find the vault holding the tokens deposited in the escrow account.
+pub fn find_vault_address(escrow_account_pubkey: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, escrow_account_pubkey.as_ref()], program_id)
+}
+
+/// Find the PDA that owns the vault of the escrow account.
+///
+/// # Example
+///
+/// ```rust
+/// # use escrow_program::pda::find_authority_address;
+/// # use solana_program::pubkey::Pubkey;
+/// #
+/// let escrow_account_pubkey = Pubkey::new_unique();
+/// let (pda, _) = find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+///
+/// assert_ne!(pda, find_authority_address(&Pubkey::new_unique(), &escrow_program::id()).0);
+/// ```
+pub fn find_authority_address(escrow_account_pubkey: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[AUTHORITY_SEED, escrow_account_pubkey.as_ref()],
+        program_id,
+    )
+}
+
+/// Find the PDA that owned the vaults of all escrows in the first layout.
+///
+/// Escrows in the [`EscrowV1`](crate::state::EscrowV1) layout are moved to their own
+/// [`find_authority_address`] by the `Migrate` instruction.
+pub fn find_legacy_authority_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ESCROW_SEED], program_id)
+}
@@ -1,63 +1,183 @@
+use crate::error::EscrowError;
+use crate::event::{
+    EscrowCancelled, EscrowEvent, EscrowExchanged, EscrowInitialized, EscrowUpdated,
+};
 use crate::instruction::Instruction;
-use crate::state::Escrow;
+use crate::pda::{
+    find_authority_address, find_escrow_address, find_legacy_authority_address, find_vault_address,
+    AUTHORITY_SEED, ESCROW_SEED, VAULT_SEED,
+};
+use crate::state::{Escrow, EscrowV1};
 use borsh::BorshDeserialize;
 use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::clock::Clock;
 use solana_program::entrypoint::ProgramResult;
 use solana_program::program::{invoke, invoke_signed};
 use solana_program::program_error::ProgramError;
-use solana_program::program_pack::{IsInitialized, Pack};
+use solana_program::program_pack::IsInitialized;
 use solana_program::pubkey::Pubkey;
 use solana_program::rent::Rent;
+use solana_program::system_instruction;
+use solana_program::system_program;
 use solana_program::sysvar::Sysvar;
-use spl_token::instruction::AuthorityType;
+use spl_token_2022::extension::transfer_fee::{TransferFeeAmount, TransferFeeConfig};
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::state::{Account, Mint};
 
 /// Processor is processing the instructions.
 pub struct Processor;
 
 impl Processor {
-    fn process_init(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    fn process_init(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seed: u64,
+        deposit_amount: u64,
+        amount: u64,
+        expires_at: Option<i64>,
+        taker: Option<Pubkey>,
+    ) -> ProgramResult {
         // Retrieving an accounts
         let account_iter = &mut accounts.iter();
         let seller_account = next_account_info(account_iter)?;
         let seller_token_account = next_account_info(account_iter)?;
+        let seller_send_token_account = next_account_info(account_iter)?;
+        let send_mint_account = next_account_info(account_iter)?;
         let temp_token_account = next_account_info(account_iter)?;
         let escrow_account = next_account_info(account_iter)?;
         let rent = Rent::from_account_info(next_account_info(account_iter)?)?;
         let token_program = next_account_info(account_iter)?;
+        let system_program = next_account_info(account_iter)?;
 
         // Validating the accounts
-        if seller_token_account.owner != token_program.key {
-            return Err(ProgramError::IncorrectProgramId);
+        if !seller_account.is_signer {
+            return Err(EscrowError::SellerNotSigner.into());
         }
-        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
-            return Err(ProgramError::AccountNotRentExempt);
+        Self::check_token_program(token_program)?;
+        if !system_program::check_id(system_program.key) {
+            return Err(EscrowError::InvalidSystemProgram.into());
+        }
+        if !Self::is_token_program(seller_token_account.owner) {
+            return Err(EscrowError::TokenProgramMismatch.into());
+        }
+        Self::check_token_account(seller_send_token_account, token_program)?;
+        Self::check_token_account(send_mint_account, token_program)?;
+        let (escrow_pubkey, escrow_bump) =
+            find_escrow_address(seller_account.key, seed, program_id);
+        if escrow_pubkey != *escrow_account.key {
+            return Err(EscrowError::EscrowAddressMismatch.into());
+        }
+        if escrow_account.owner == program_id {
+            return Err(EscrowError::AlreadyInitialized.into());
+        }
+        let (vault_pubkey, vault_bump) = find_vault_address(escrow_account.key, program_id);
+        if vault_pubkey != *temp_token_account.key {
+            return Err(EscrowError::TempAccountMismatch.into());
+        }
+        let clock = Clock::get()?;
+        if expires_at.is_some_and(|expires_at| expires_at <= clock.unix_timestamp) {
+            return Err(EscrowError::Expired.into());
         }
 
-        // Initializing the escrow account
-        let data = &mut escrow_account.data.borrow_mut();
-        let mut state = borsh::from_slice::<Escrow>(data)?;
-        if state.is_initialized() {
-            return Err(ProgramError::AccountAlreadyInitialized);
-        }
-        state.is_initialized = true;
-        state.seller_pubkey = *seller_account.key;
-        state.seller_token_account_pubkey = *seller_token_account.key;
-        state.temp_token_account_pubkey = *temp_token_account.key;
-        state.amount = amount;
-
-        data.copy_from_slice(borsh::to_vec(&state)?.as_slice());
-
-        // Change the ownership of the temporary token account to the PDA
-        let (pda, _) = Pubkey::find_program_address(&[b"escrow"], program_id);
-        let ix = spl_token::instruction::set_authority(
+        // Creating the escrow account
+        Self::create_pda_account(
+            seller_account,
+            escrow_account,
+            system_program,
+            &rent,
+            Escrow::LEN,
+            program_id,
+            &[
+                ESCROW_SEED,
+                seller_account.key.as_ref(),
+                &seed.to_le_bytes(),
+                &[escrow_bump],
+            ],
+        )?;
+
+        // Creating the temporary token account owned by the PDA
+        let (pda, bump) = find_authority_address(escrow_account.key, program_id);
+        let temp_token_account_len = Self::token_account_len(send_mint_account)?;
+        Self::create_pda_account(
+            seller_account,
+            temp_token_account,
+            system_program,
+            &rent,
+            temp_token_account_len,
+            token_program.key,
+            &[VAULT_SEED, escrow_account.key.as_ref(), &[vault_bump]],
+        )?;
+        let ix = spl_token_2022::instruction::initialize_account3(
             token_program.key,
             temp_token_account.key,
-            Some(&pda),
-            AuthorityType::AccountOwner,
+            send_mint_account.key,
+            &pda,
+        )?;
+        invoke(
+            &ix,
+            &[
+                temp_token_account.clone(),
+                send_mint_account.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        // Transfer the token from the seller to the temporary token account
+        let ix = spl_token_2022::instruction::transfer_checked(
+            token_program.key,
+            seller_send_token_account.key,
+            send_mint_account.key,
+            temp_token_account.key,
             seller_account.key,
             &[seller_account.key],
+            deposit_amount,
+            Self::unpack_mint(send_mint_account)?.decimals,
+        )?;
+        invoke(
+            &ix,
+            &[
+                seller_send_token_account.clone(),
+                send_mint_account.clone(),
+                temp_token_account.clone(),
+                seller_account.clone(),
+                token_program.clone(),
+            ],
         )?;
-        invoke(&ix, &[temp_token_account.clone(), seller_account.clone()])?;
+
+        // The vault may receive less than the deposit if the mint charges a transfer fee
+        let offered_amount = Self::unpack_token_account(temp_token_account)?.amount;
+        if offered_amount == 0 {
+            return Err(EscrowError::AmountMismatch.into());
+        }
+
+        // Initializing the escrow account
+        let state = Escrow {
+            is_initialized: true,
+            seller_pubkey: *seller_account.key,
+            seller_token_account_pubkey: *seller_token_account.key,
+            temp_token_account_pubkey: *temp_token_account.key,
+            amount,
+            offered_amount,
+            filled_amount: 0,
+            bump,
+            seed,
+            expires_at,
+            taker,
+        };
+        state.pack(&mut escrow_account.data.borrow_mut())?;
+
+        EscrowEvent::Initialized(EscrowInitialized {
+            escrow: *escrow_account.key,
+            seller: *seller_account.key,
+            send_mint: *send_mint_account.key,
+            receive_mint: Self::unpack_token_account(seller_token_account)?.mint,
+            offered_amount,
+            amount,
+            expires_at,
+            taker,
+            slot: clock.slot,
+        })
+        .emit()?;
 
         Ok(())
     }
@@ -66,6 +186,8 @@ impl Processor {
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount: u64,
+        expected_receive: u64,
+        max_pay: u64,
     ) -> ProgramResult {
         // Retrieving an accounts
         let account_iter = &mut accounts.iter();
@@ -78,75 +200,614 @@ impl Processor {
         let escrow_account = next_account_info(account_iter)?;
         let token_program = next_account_info(account_iter)?;
         let pda_account = next_account_info(account_iter)?;
+        let send_mint_account = next_account_info(account_iter)?;
+        let receive_mint_account = next_account_info(account_iter)?;
+        let receive_token_program = next_account_info(account_iter)?;
 
         // Validating the accounts
-        let temp_token_account_state =
-            spl_token::state::Account::unpack(&temp_token_account.try_borrow_data()?)?;
-        if amount != temp_token_account_state.amount {
-            return Err(ProgramError::InvalidAccountData);
+        if !buyer_account.is_signer {
+            return Err(EscrowError::BuyerNotSigner.into());
+        }
+        Self::check_token_program(token_program)?;
+        Self::check_token_program(receive_token_program)?;
+        Self::check_token_account(buyer_send_token_account, receive_token_program)?;
+        Self::check_token_account(buyer_receive_token_account, token_program)?;
+        Self::check_token_account(temp_token_account, token_program)?;
+        Self::check_token_account(seller_token_account, receive_token_program)?;
+        Self::check_token_account(send_mint_account, token_program)?;
+        Self::check_token_account(receive_mint_account, receive_token_program)?;
+        if escrow_account.owner != program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
         }
 
-        let state = borsh::from_slice::<Escrow>(&escrow_account.data.borrow())?;
+        let mut state = Escrow::unpack(&escrow_account.data.borrow())?;
         if !state.is_initialized() {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(EscrowError::NotInitialized.into());
         }
         if state.temp_token_account_pubkey != *temp_token_account.key {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(EscrowError::TempAccountMismatch.into());
         }
         if state.seller_pubkey != *seller_account.key {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(EscrowError::SellerMismatch.into());
         }
         if state.seller_token_account_pubkey != *seller_token_account.key {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(EscrowError::SellerTokenAccountMismatch.into());
+        }
+        let clock = Clock::get()?;
+        if state.is_expired(clock.unix_timestamp) {
+            return Err(EscrowError::Expired.into());
+        }
+        if state.taker.is_some_and(|taker| taker != *buyer_account.key) {
+            return Err(EscrowError::TakerMismatch.into());
+        }
+        Self::check_authority(program_id, escrow_account, pda_account, state.bump)?;
+        if amount == 0 || amount > state.remaining_amount() {
+            return Err(EscrowError::AmountMismatch.into());
+        }
+        let price = state
+            .fill_price(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        if Self::amount_after_transfer_fee(send_mint_account, amount)? < expected_receive {
+            return Err(EscrowError::ReceiveBelowExpected.into());
+        }
+        let pay = Self::amount_with_transfer_fee(receive_mint_account, price)?;
+        if pay > max_pay {
+            return Err(EscrowError::PayExceedsMaximum.into());
         }
 
-        // Transfer the token from the buyer to the seller
-        let ix = spl_token::instruction::transfer(
-            token_program.key,
+        // Transfer the token from the buyer to the seller, grossed up by the transfer fee so
+        // that the seller receives the expected amount
+        let receive_mint_state = Self::unpack_mint(receive_mint_account)?;
+        let ix = spl_token_2022::instruction::transfer_checked(
+            receive_token_program.key,
             buyer_send_token_account.key,
+            receive_mint_account.key,
             seller_token_account.key,
             buyer_account.key,
             &[buyer_account.key],
-            state.amount,
+            pay,
+            receive_mint_state.decimals,
         )?;
         invoke(
             &ix,
             &[
                 buyer_send_token_account.clone(),
+                receive_mint_account.clone(),
                 seller_token_account.clone(),
                 buyer_account.clone(),
-                token_program.clone(),
+                receive_token_program.clone(),
             ],
         )?;
 
-        // Transfer the token from the seller (temporary deposit) to the buyer
-        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
-        let ix = spl_token::instruction::transfer(
+        state.filled_amount = state
+            .filled_amount
+            .checked_add(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let event = EscrowEvent::Exchanged(EscrowExchanged {
+            escrow: *escrow_account.key,
+            seller: *seller_account.key,
+            buyer: *buyer_account.key,
+            send_mint: *send_mint_account.key,
+            receive_mint: *receive_mint_account.key,
+            amount,
+            paid_amount: pay,
+            remaining_amount: state.remaining_amount(),
+            slot: clock.slot,
+        });
+        if state.remaining_amount() > 0 {
+            // Transfer part of the token from the seller (temporary deposit) to the buyer
+            Self::transfer_from_temp_token_account(
+                escrow_account,
+                temp_token_account,
+                send_mint_account,
+                buyer_receive_token_account,
+                pda_account,
+                token_program,
+                amount,
+                state.bump,
+            )?;
+
+            // Record the fill in the escrow account
+            state.pack(&mut escrow_account.data.borrow_mut())?;
+            event.emit()?;
+
+            return Ok(());
+        }
+
+        // Transfer the rest of the token from the seller (temporary deposit) to the buyer
+        let temp_token_account_state = Self::unpack_token_account(temp_token_account)?;
+        Self::release_temp_token_account(
+            escrow_account,
+            temp_token_account,
+            send_mint_account,
+            buyer_receive_token_account,
+            seller_account,
+            pda_account,
+            token_program,
+            temp_token_account_state.amount,
+            state.bump,
+        )?;
+
+        // Close the escrow account
+        Self::close_escrow_account(escrow_account, seller_account)?;
+        event.emit()?;
+
+        Ok(())
+    }
+
+    fn process_cancel(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        // Retrieving an accounts
+        let account_iter = &mut accounts.iter();
+        let seller_account = next_account_info(account_iter)?;
+        let seller_send_token_account = next_account_info(account_iter)?;
+        let temp_token_account = next_account_info(account_iter)?;
+        let escrow_account = next_account_info(account_iter)?;
+        let token_program = next_account_info(account_iter)?;
+        let pda_account = next_account_info(account_iter)?;
+        let send_mint_account = next_account_info(account_iter)?;
+
+        // Validating the accounts
+        if !seller_account.is_signer {
+            return Err(EscrowError::SellerNotSigner.into());
+        }
+        Self::check_token_program(token_program)?;
+        Self::check_token_account(seller_send_token_account, token_program)?;
+        Self::check_token_account(temp_token_account, token_program)?;
+        Self::check_token_account(send_mint_account, token_program)?;
+        if escrow_account.owner != program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        let state = Escrow::unpack(&escrow_account.data.borrow())?;
+        if !state.is_initialized() {
+            return Err(EscrowError::NotInitialized.into());
+        }
+        if state.seller_pubkey != *seller_account.key {
+            return Err(EscrowError::SellerMismatch.into());
+        }
+        if state.temp_token_account_pubkey != *temp_token_account.key {
+            return Err(EscrowError::TempAccountMismatch.into());
+        }
+        Self::check_authority(program_id, escrow_account, pda_account, state.bump)?;
+
+        let temp_token_account_state = Self::unpack_token_account(temp_token_account)?;
+
+        // Return the token from the seller (temporary deposit) to the seller
+        Self::release_temp_token_account(
+            escrow_account,
+            temp_token_account,
+            send_mint_account,
+            seller_send_token_account,
+            seller_account,
+            pda_account,
+            token_program,
+            temp_token_account_state.amount,
+            state.bump,
+        )?;
+
+        // Close the escrow account
+        Self::close_escrow_account(escrow_account, seller_account)?;
+
+        EscrowEvent::Cancelled(EscrowCancelled {
+            escrow: *escrow_account.key,
+            seller: *seller_account.key,
+            send_mint: *send_mint_account.key,
+            amount: temp_token_account_state.amount,
+            refunded: false,
+            slot: Clock::get()?.slot,
+        })
+        .emit()?;
+
+        Ok(())
+    }
+
+    fn process_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        // Retrieving an accounts
+        let account_iter = &mut accounts.iter();
+        let seller_account = next_account_info(account_iter)?;
+        let seller_send_token_account = next_account_info(account_iter)?;
+        let temp_token_account = next_account_info(account_iter)?;
+        let escrow_account = next_account_info(account_iter)?;
+        let token_program = next_account_info(account_iter)?;
+        let pda_account = next_account_info(account_iter)?;
+        let send_mint_account = next_account_info(account_iter)?;
+
+        // Validating the accounts
+        Self::check_token_program(token_program)?;
+        Self::check_token_account(seller_send_token_account, token_program)?;
+        Self::check_token_account(temp_token_account, token_program)?;
+        Self::check_token_account(send_mint_account, token_program)?;
+        if escrow_account.owner != program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        let state = Escrow::unpack(&escrow_account.data.borrow())?;
+        if !state.is_initialized() {
+            return Err(EscrowError::NotInitialized.into());
+        }
+        if state.seller_pubkey != *seller_account.key {
+            return Err(EscrowError::SellerMismatch.into());
+        }
+        if state.temp_token_account_pubkey != *temp_token_account.key {
+            return Err(EscrowError::TempAccountMismatch.into());
+        }
+        // The seller does not sign the refund, so the tokens may only go back to an account
+        // owned by the seller
+        if Self::unpack_token_account(seller_send_token_account)?.owner != state.seller_pubkey {
+            return Err(EscrowError::SellerTokenAccountMismatch.into());
+        }
+        let clock = Clock::get()?;
+        if !state.is_expired(clock.unix_timestamp) {
+            return Err(EscrowError::NotExpired.into());
+        }
+        Self::check_authority(program_id, escrow_account, pda_account, state.bump)?;
+
+        let temp_token_account_state = Self::unpack_token_account(temp_token_account)?;
+
+        // Return the token from the seller (temporary deposit) to the seller
+        Self::release_temp_token_account(
+            escrow_account,
+            temp_token_account,
+            send_mint_account,
+            seller_send_token_account,
+            seller_account,
+            pda_account,
+            token_program,
+            temp_token_account_state.amount,
+            state.bump,
+        )?;
+
+        // Close the escrow account
+        Self::close_escrow_account(escrow_account, seller_account)?;
+
+        EscrowEvent::Cancelled(EscrowCancelled {
+            escrow: *escrow_account.key,
+            seller: *seller_account.key,
+            send_mint: *send_mint_account.key,
+            amount: temp_token_account_state.amount,
+            refunded: true,
+            slot: clock.slot,
+        })
+        .emit()?;
+
+        Ok(())
+    }
+
+    fn process_migrate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        // Retrieving an accounts
+        let account_iter = &mut accounts.iter();
+        let payer_account = next_account_info(account_iter)?;
+        let escrow_account = next_account_info(account_iter)?;
+        let system_program = next_account_info(account_iter)?;
+        let temp_token_account = next_account_info(account_iter)?;
+        let legacy_pda_account = next_account_info(account_iter)?;
+        let token_program = next_account_info(account_iter)?;
+
+        // Validating the accounts
+        if !payer_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if !system_program::check_id(system_program.key) {
+            return Err(EscrowError::InvalidSystemProgram.into());
+        }
+        if escrow_account.owner != program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        let state = {
+            let data = escrow_account.data.borrow();
+            if data.starts_with(&Escrow::DISCRIMINATOR) {
+                // Already in a versioned layout, which is the newest one as long as it unpacks
+                Escrow::unpack(&data)?;
+                return Ok(());
+            }
+            EscrowV1::try_from_slice(&data)?
+        };
+        if !state.is_initialized {
+            return Err(EscrowError::NotInitialized.into());
+        }
+        if state.temp_token_account_pubkey != *temp_token_account.key {
+            return Err(EscrowError::TempAccountMismatch.into());
+        }
+        Self::check_token_program(token_program)?;
+        Self::check_token_account(temp_token_account, token_program)?;
+        let (legacy_pda, legacy_bump) = find_legacy_authority_address(program_id);
+        if legacy_pda != *legacy_pda_account.key {
+            return Err(EscrowError::AuthorityMismatch.into());
+        }
+        let temp_token_account_state = Self::unpack_token_account(temp_token_account)?;
+        if temp_token_account_state.owner != legacy_pda {
+            return Err(EscrowError::AuthorityMismatch.into());
+        }
+
+        // Moving the temporary token account to the PDA of the escrow account
+        let (pda, bump) = find_authority_address(escrow_account.key, program_id);
+        let ix = spl_token_2022::instruction::set_authority(
             token_program.key,
             temp_token_account.key,
-            buyer_receive_token_account.key,
-            &pda,
-            &[&pda],
-            temp_token_account_state.amount,
+            Some(&pda),
+            spl_token_2022::instruction::AuthorityType::AccountOwner,
+            legacy_pda_account.key,
+            &[legacy_pda_account.key],
         )?;
         invoke_signed(
             &ix,
             &[
                 temp_token_account.clone(),
-                buyer_receive_token_account.clone(),
-                pda_account.clone(),
+                legacy_pda_account.clone(),
                 token_program.clone(),
             ],
-            &[&[&b"escrow"[..], &[nonce]]],
+            &[&[ESCROW_SEED, &[legacy_bump]]],
+        )?;
+
+        // Funding the rent of the reallocated account
+        let rent = Rent::get()?;
+        let lamports = rent
+            .minimum_balance(Escrow::LEN)
+            .saturating_sub(escrow_account.lamports());
+        if lamports > 0 {
+            let ix = system_instruction::transfer(payer_account.key, escrow_account.key, lamports);
+            invoke(
+                &ix,
+                &[
+                    payer_account.clone(),
+                    escrow_account.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        }
+
+        // Rewriting the escrow account in the newest layout, the tokens held by the vault being
+        // the deposit
+        let state = state.migrate(temp_token_account_state.amount, bump);
+        escrow_account.realloc(Escrow::LEN, true)?;
+        state.pack(&mut escrow_account.data.borrow_mut())?;
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        Ok(())
+    }
+
+    fn process_update_terms(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: Option<u64>,
+        deposit_amount: u64,
+        withdraw_amount: u64,
+        expires_at: Option<Option<i64>>,
+        taker: Option<Option<Pubkey>>,
+    ) -> ProgramResult {
+        // Retrieving an accounts
+        let account_iter = &mut accounts.iter();
+        let seller_account = next_account_info(account_iter)?;
+        let seller_send_token_account = next_account_info(account_iter)?;
+        let temp_token_account = next_account_info(account_iter)?;
+        let escrow_account = next_account_info(account_iter)?;
+        let token_program = next_account_info(account_iter)?;
+        let pda_account = next_account_info(account_iter)?;
+        let send_mint_account = next_account_info(account_iter)?;
+
+        // Validating the accounts
+        if !seller_account.is_signer {
+            return Err(EscrowError::SellerNotSigner.into());
+        }
+        Self::check_token_program(token_program)?;
+        Self::check_token_account(seller_send_token_account, token_program)?;
+        Self::check_token_account(temp_token_account, token_program)?;
+        Self::check_token_account(send_mint_account, token_program)?;
+        if escrow_account.owner != program_id {
+            return Err(EscrowError::InvalidEscrowOwner.into());
+        }
+
+        let mut state = Escrow::unpack(&escrow_account.data.borrow())?;
+        if !state.is_initialized() {
+            return Err(EscrowError::NotInitialized.into());
+        }
+        if state.seller_pubkey != *seller_account.key {
+            return Err(EscrowError::SellerMismatch.into());
+        }
+        if state.temp_token_account_pubkey != *temp_token_account.key {
+            return Err(EscrowError::TempAccountMismatch.into());
+        }
+        Self::check_authority(program_id, escrow_account, pda_account, state.bump)?;
+        if withdraw_amount > state.remaining_amount() {
+            return Err(EscrowError::AmountMismatch.into());
+        }
+        let clock = Clock::get()?;
+        if expires_at
+            .flatten()
+            .is_some_and(|expires_at| expires_at <= clock.unix_timestamp)
+        {
+            return Err(EscrowError::Expired.into());
+        }
+
+        // Transfer the additional token from the seller to the temporary token account
+        if deposit_amount > 0 {
+            let ix = spl_token_2022::instruction::transfer_checked(
+                token_program.key,
+                seller_send_token_account.key,
+                send_mint_account.key,
+                temp_token_account.key,
+                seller_account.key,
+                &[seller_account.key],
+                deposit_amount,
+                Self::unpack_mint(send_mint_account)?.decimals,
+            )?;
+            invoke(
+                &ix,
+                &[
+                    seller_send_token_account.clone(),
+                    send_mint_account.clone(),
+                    temp_token_account.clone(),
+                    seller_account.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+
+        // Return part of the token from the temporary token account to the seller
+        if withdraw_amount > 0 {
+            Self::transfer_from_temp_token_account(
+                escrow_account,
+                temp_token_account,
+                send_mint_account,
+                seller_send_token_account,
+                pda_account,
+                token_program,
+                withdraw_amount,
+                state.bump,
+            )?;
+        }
+
+        // Rebasing the escrow on the tokens now held by the vault, which may be less than
+        // deposited if the mint charges a transfer fee
+        let offered_amount = Self::unpack_token_account(temp_token_account)?.amount;
+        if offered_amount == 0 {
+            return Err(EscrowError::AmountMismatch.into());
+        }
+        state
+            .rebase(offered_amount, amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        if let Some(expires_at) = expires_at {
+            state.expires_at = expires_at;
+        }
+        if let Some(taker) = taker {
+            state.taker = taker;
+        }
+        state.pack(&mut escrow_account.data.borrow_mut())?;
+
+        EscrowEvent::Updated(EscrowUpdated {
+            escrow: *escrow_account.key,
+            seller: *seller_account.key,
+            send_mint: *send_mint_account.key,
+            amount: state.amount,
+            offered_amount: state.offered_amount,
+            expires_at: state.expires_at,
+            taker: state.taker,
+            slot: clock.slot,
+        })
+        .emit()?;
+
+        Ok(())
+    }
+
+    /// Create the account at the PDA signed by the seeds, owned by the owner program.
+    ///
+    /// Anyone can send lamports to the address of a PDA before it is created, and
+    /// `create_account` fails on an address holding lamports. Such an account is topped up to
+    /// the rent exemption, then allocated and assigned instead.
+    fn create_pda_account<'a>(
+        payer_account: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        rent: &Rent,
+        space: usize,
+        owner: &Pubkey,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let lamports = rent.minimum_balance(space);
+        if account.lamports() == 0 {
+            let ix = system_instruction::create_account(
+                payer_account.key,
+                account.key,
+                lamports,
+                space as u64,
+                owner,
+            );
+            return invoke_signed(
+                &ix,
+                &[
+                    payer_account.clone(),
+                    account.clone(),
+                    system_program.clone(),
+                ],
+                &[seeds],
+            );
+        }
+
+        // Funding the rent not covered by the lamports already sent to the account
+        let shortfall = lamports.saturating_sub(account.lamports());
+        if shortfall > 0 {
+            let ix = system_instruction::transfer(payer_account.key, account.key, shortfall);
+            invoke(
+                &ix,
+                &[
+                    payer_account.clone(),
+                    account.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        }
+        let ix = system_instruction::allocate(account.key, space as u64);
+        invoke_signed(&ix, &[account.clone(), system_program.clone()], &[seeds])?;
+        let ix = system_instruction::assign(account.key, owner);
+        invoke_signed(&ix, &[account.clone(), system_program.clone()], &[seeds])?;
+
+        Ok(())
+    }
+
+    /// Transfer all tokens from the temporary token account to the destination token account,
+    /// then close it and send its rent fees to the seller.
+    #[allow(clippy::too_many_arguments)]
+    fn release_temp_token_account<'a>(
+        escrow_account: &AccountInfo<'a>,
+        temp_token_account: &AccountInfo<'a>,
+        mint_account: &AccountInfo<'a>,
+        destination_token_account: &AccountInfo<'a>,
+        seller_account: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        amount: u64,
+        bump: u8,
+    ) -> ProgramResult {
+        let seeds: &[&[u8]] = &[AUTHORITY_SEED, escrow_account.key.as_ref(), &[bump]];
+
+        // Transfer the token from the temporary token account
+        Self::transfer_from_temp_token_account(
+            escrow_account,
+            temp_token_account,
+            mint_account,
+            destination_token_account,
+            pda_account,
+            token_program,
+            amount,
+            bump,
         )?;
 
+        // Move transfer fees withheld in the temporary token account to the mint, as an account
+        // holding withheld fees cannot be closed
+        let withheld_amount = {
+            let data = temp_token_account.try_borrow_data()?;
+            let state = StateWithExtensions::<Account>::unpack(&data)?;
+            state
+                .get_extension::<TransferFeeAmount>()
+                .map(|extension| u64::from(extension.withheld_amount))
+                .unwrap_or(0)
+        };
+        if withheld_amount > 0 {
+            let ix = spl_token_2022::extension::transfer_fee::instruction::harvest_withheld_tokens_to_mint(
+                token_program.key,
+                mint_account.key,
+                &[temp_token_account.key],
+            )?;
+            invoke(
+                &ix,
+                &[
+                    mint_account.clone(),
+                    temp_token_account.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+
         // Close the temporary account
-        let ix = spl_token::instruction::close_account(
+        let ix = spl_token_2022::instruction::close_account(
             token_program.key,
             temp_token_account.key,
             seller_account.key,
-            &pda,
-            &[&pda],
+            pda_account.key,
+            &[pda_account.key],
         )?;
         invoke_signed(
             &ix,
@@ -156,12 +817,151 @@ impl Processor {
                 pda_account.clone(),
                 token_program.clone(),
             ],
-            &[&[&b"escrow"[..], &[nonce]]],
+            &[seeds],
         )?;
 
-        // Close the escrow account
-        let mut seller_account_lamports = seller_account.lamports.borrow_mut();
-        **seller_account_lamports = seller_account_lamports
+        Ok(())
+    }
+
+    /// Transfer tokens from the temporary token account to the destination token account.
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_from_temp_token_account<'a>(
+        escrow_account: &AccountInfo<'a>,
+        temp_token_account: &AccountInfo<'a>,
+        mint_account: &AccountInfo<'a>,
+        destination_token_account: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        amount: u64,
+        bump: u8,
+    ) -> ProgramResult {
+        let ix = spl_token_2022::instruction::transfer_checked(
+            token_program.key,
+            temp_token_account.key,
+            mint_account.key,
+            destination_token_account.key,
+            pda_account.key,
+            &[pda_account.key],
+            amount,
+            Self::unpack_mint(mint_account)?.decimals,
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                temp_token_account.clone(),
+                mint_account.clone(),
+                destination_token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[AUTHORITY_SEED, escrow_account.key.as_ref(), &[bump]]],
+        )?;
+
+        Ok(())
+    }
+
+    /// Return whether the program is a supported token program.
+    fn is_token_program(program_id: &Pubkey) -> bool {
+        spl_token::check_id(program_id) || spl_token_2022::check_id(program_id)
+    }
+
+    /// Validate that the token program is a supported token program.
+    fn check_token_program(token_program: &AccountInfo) -> ProgramResult {
+        if !Self::is_token_program(token_program.key) {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+
+        Ok(())
+    }
+
+    /// Validate that the account is owned by the token program.
+    fn check_token_account(account: &AccountInfo, token_program: &AccountInfo) -> ProgramResult {
+        if account.owner != token_program.key {
+            return Err(EscrowError::TokenProgramMismatch.into());
+        }
+
+        Ok(())
+    }
+
+    /// Validate that the PDA account is the authority derived from the escrow account.
+    fn check_authority(
+        program_id: &Pubkey,
+        escrow_account: &AccountInfo,
+        pda_account: &AccountInfo,
+        bump: u8,
+    ) -> ProgramResult {
+        let pda = Pubkey::create_program_address(
+            &[AUTHORITY_SEED, escrow_account.key.as_ref(), &[bump]],
+            program_id,
+        )
+        .map_err(|_| EscrowError::AuthorityMismatch)?;
+        if pda != *pda_account.key {
+            return Err(EscrowError::AuthorityMismatch.into());
+        }
+
+        Ok(())
+    }
+
+    /// Unpack the token account, including accounts with extensions.
+    fn unpack_token_account(account: &AccountInfo) -> Result<Account, ProgramError> {
+        let data = account.try_borrow_data()?;
+        Ok(StateWithExtensions::<Account>::unpack(&data)?.base)
+    }
+
+    /// Unpack the mint, including mints with extensions.
+    fn unpack_mint(account: &AccountInfo) -> Result<Mint, ProgramError> {
+        let data = account.try_borrow_data()?;
+        Ok(StateWithExtensions::<Mint>::unpack(&data)?.base)
+    }
+
+    /// Get the size of a token account for the mint, including the extensions it requires.
+    fn token_account_len(mint_account: &AccountInfo) -> Result<usize, ProgramError> {
+        let data = mint_account.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+        let extension_types =
+            ExtensionType::get_required_init_account_extensions(&mint.get_extension_types()?);
+        ExtensionType::try_calculate_account_len::<Account>(&extension_types)
+    }
+
+    /// Get the amount to transfer so that the recipient receives the amount after transfer fees.
+    fn amount_with_transfer_fee(
+        mint_account: &AccountInfo,
+        amount: u64,
+    ) -> Result<u64, ProgramError> {
+        let data = mint_account.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+        match mint.get_extension::<TransferFeeConfig>() {
+            Ok(transfer_fee_config) => transfer_fee_config
+                .get_epoch_fee(Clock::get()?.epoch)
+                .calculate_pre_fee_amount(amount)
+                .ok_or(ProgramError::ArithmeticOverflow),
+            Err(_) => Ok(amount),
+        }
+    }
+
+    /// Get the amount the recipient receives after transfer fees when sending the amount.
+    fn amount_after_transfer_fee(
+        mint_account: &AccountInfo,
+        amount: u64,
+    ) -> Result<u64, ProgramError> {
+        let data = mint_account.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+        match mint.get_extension::<TransferFeeConfig>() {
+            Ok(transfer_fee_config) => transfer_fee_config
+                .calculate_epoch_fee(Clock::get()?.epoch, amount)
+                .and_then(|fee| amount.checked_sub(fee))
+                .ok_or(ProgramError::ArithmeticOverflow),
+            Err(_) => Ok(amount),
+        }
+    }
+
+    /// Close the escrow account and send its rent fees to the destination account.
+    fn close_escrow_account(
+        escrow_account: &AccountInfo,
+        destination_account: &AccountInfo,
+    ) -> ProgramResult {
+        let mut destination_account_lamports = destination_account.lamports.borrow_mut();
+        **destination_account_lamports = destination_account_lamports
             .checked_add(escrow_account.lamports())
             .ok_or(ProgramError::ArithmeticOverflow)?;
         let mut escrow_account_lamports = escrow_account.lamports.borrow_mut();
@@ -176,8 +976,44 @@ impl Processor {
     pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
         let instruction = Instruction::deserialize(&mut &input[..])?;
         match instruction {
-            Instruction::Initialize(amount) => Self::process_init(program_id, accounts, amount),
-            Instruction::Exchange(amount) => Self::process_exchange(program_id, accounts, amount),
+            Instruction::Initialize {
+                seed,
+                deposit_amount,
+                amount,
+                expires_at,
+                taker,
+            } => Self::process_init(
+                program_id,
+                accounts,
+                seed,
+                deposit_amount,
+                amount,
+                expires_at,
+                taker,
+            ),
+            Instruction::Exchange {
+                amount,
+                expected_receive,
+                max_pay,
+            } => Self::process_exchange(program_id, accounts, amount, expected_receive, max_pay),
+            Instruction::Cancel => Self::process_cancel(program_id, accounts),
+            Instruction::Refund => Self::process_refund(program_id, accounts),
+            Instruction::Migrate => Self::process_migrate(program_id, accounts),
+            Instruction::UpdateTerms {
+                amount,
+                deposit_amount,
+                withdraw_amount,
+                expires_at,
+                taker,
+            } => Self::process_update_terms(
+                program_id,
+                accounts,
+                amount,
+                deposit_amount,
+                withdraw_amount,
+                expires_at,
+                taker,
+            ),
         }
     }
 }
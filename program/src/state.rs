@@ -1,26 +1,47 @@
+use crate::error::EscrowError;
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::program_error::ProgramError;
 use solana_program::program_pack::IsInitialized;
 use solana_program::pubkey::Pubkey;
 
 /// Escrow represents a state for intermediate safe transactions.
 ///
+/// The account starts with [`Escrow::DISCRIMINATOR`] and the layout [`Escrow::VERSION`],
+/// followed by the borsh serialized state and [`Escrow::RESERVED_LEN`] bytes reserved for fields
+/// added by future versions. Accounts created by the first deployment are described by
+/// [`EscrowV1`] and can be brought to the newest layout with the `Migrate` instruction.
+///
 /// # Example
 ///
 /// ```rust
-/// # use borsh::BorshDeserialize;
 /// # use escrow_program::state::Escrow;
+/// # use solana_program::pubkey::Pubkey;
 /// #
-/// let escrow = Escrow::default();
-/// let serialized = borsh::to_vec(&escrow).unwrap();
-/// let deserialized = borsh::from_slice::<Escrow>(&serialized).unwrap();
+/// let escrow = Escrow {
+///     expires_at: Some(1_700_000_000),
+///     taker: Some(Pubkey::new_unique()),
+///     ..Escrow::default()
+/// };
+/// let mut data = vec![0; Escrow::LEN];
+/// escrow.pack(&mut data).unwrap();
+/// let deserialized = Escrow::unpack(&data).unwrap();
 ///
+/// assert_eq!(data[..8], Escrow::DISCRIMINATOR);
+/// assert_eq!(data[8], Escrow::VERSION);
 /// assert_eq!(escrow.is_initialized, deserialized.is_initialized);
 /// assert_eq!(escrow.seller_pubkey, deserialized.seller_pubkey);
 /// assert_eq!(escrow.seller_token_account_pubkey, deserialized.seller_token_account_pubkey);
 /// assert_eq!(escrow.temp_token_account_pubkey, deserialized.temp_token_account_pubkey);
 /// assert_eq!(escrow.amount, deserialized.amount);
+/// assert_eq!(escrow.offered_amount, deserialized.offered_amount);
+/// assert_eq!(escrow.filled_amount, deserialized.filled_amount);
+/// assert_eq!(escrow.bump, deserialized.bump);
+/// assert_eq!(escrow.seed, deserialized.seed);
+/// assert_eq!(escrow.expires_at, deserialized.expires_at);
+/// assert_eq!(escrow.taker, deserialized.taker);
+/// assert_eq!(borsh::max_serialized_size::<Escrow>().unwrap(), Escrow::DATA_LEN);
 /// ```
-#[derive(Default, BorshSerialize, BorshDeserialize, BorshSchema)]
+#[derive(Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema)]
 pub struct Escrow {
     /// If true, state has been initialized
     pub is_initialized: bool,
@@ -31,11 +52,186 @@ pub struct Escrow {
     /// Token account to be received by the seller at the conclusion of the transaction
     pub seller_token_account_pubkey: Pubkey,
 
-    /// Token account temporarily deposited in escrow by the seller
+    /// Token account (vault) holding the tokens deposited in escrow by the seller
     pub temp_token_account_pubkey: Pubkey,
 
-    /// Amount of tokens expected by the seller
+    /// Amount of tokens expected by the seller for all of the deposited tokens
     pub amount: u64,
+
+    /// Amount of tokens deposited in the vault by the seller
+    pub offered_amount: u64,
+
+    /// Amount of deposited tokens already taken by buyers
+    pub filled_amount: u64,
+
+    /// Bump seed of the PDA that owns the temporary token account
+    pub bump: u8,
+
+    /// Seed chosen by the seller to derive the escrow account
+    pub seed: u64,
+
+    /// Unix timestamp after which the escrow can no longer be exchanged and may be refunded by
+    /// anyone, or `None` if the escrow never expires
+    pub expires_at: Option<i64>,
+
+    /// The only buyer allowed to take the escrow, or `None` if anyone may take it
+    pub taker: Option<Pubkey>,
+}
+
+impl Escrow {
+    /// Discriminator at the start of the escrow account, the first 8 bytes of
+    /// `sha256("account:Escrow")`.
+    ///
+    /// ```rust
+    /// # use escrow_program::state::Escrow;
+    /// # use solana_program::hash::hash;
+    /// #
+    /// assert_eq!(hash(b"account:Escrow").to_bytes()[..8], Escrow::DISCRIMINATOR);
+    /// ```
+    pub const DISCRIMINATOR: [u8; 8] = [31, 213, 123, 187, 186, 22, 218, 155];
+
+    /// Version of the account layout.
+    ///
+    /// Version 1 is the first deployed layout of [`EscrowV1`], without discriminator.
+    pub const VERSION: u8 = 2;
+
+    /// Size of the discriminator and the version.
+    pub const HEADER_LEN: usize = 8 + 1;
+
+    /// Maximum size of the serialized state.
+    pub const DATA_LEN: usize = 1 + 32 + 32 + 32 + 8 + 8 + 8 + 1 + 8 + (1 + 8) + (1 + 32);
+
+    /// Size reserved for fields added by future versions.
+    pub const RESERVED_LEN: usize = 64;
+
+    /// Size of the escrow account.
+    pub const LEN: usize = Self::HEADER_LEN + Self::DATA_LEN + Self::RESERVED_LEN;
+
+    /// Write the escrow to the account data in the newest layout.
+    pub fn pack(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        let (header, data) = dst.split_at_mut(Self::HEADER_LEN);
+        header[..8].copy_from_slice(&Self::DISCRIMINATOR);
+        header[8] = Self::VERSION;
+        data.fill(0);
+        self.serialize(&mut &mut data[..])?;
+
+        Ok(())
+    }
+
+    /// Read the escrow from account data in the newest layout.
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        if !src.starts_with(&Self::DISCRIMINATOR) {
+            return Err(EscrowError::InvalidDiscriminator.into());
+        }
+        if src.get(8) != Some(&Self::VERSION) {
+            return Err(EscrowError::UnsupportedVersion.into());
+        }
+
+        Ok(Self::deserialize(&mut &src[Self::HEADER_LEN..])?)
+    }
+
+    /// Amount of deposited tokens that buyers can still take.
+    pub fn remaining_amount(&self) -> u64 {
+        self.offered_amount.saturating_sub(self.filled_amount)
+    }
+
+    /// Amount of tokens the buyer pays to take `amount` of the remaining deposited tokens.
+    ///
+    /// The price is pro rata to the posted rate and rounded up in favor of the seller. Rounding is
+    /// applied to the cumulative amount paid rather than to each fill, so the fills of an escrow
+    /// always add up to exactly [`Escrow::amount`]. Returns `None` on overflow or if nothing was
+    /// offered.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use escrow_program::state::Escrow;
+    /// #
+    /// let mut escrow = Escrow {
+    ///     amount: 10,
+    ///     offered_amount: 3,
+    ///     ..Escrow::default()
+    /// };
+    ///
+    /// assert_eq!(escrow.fill_price(1), Some(4));
+    /// escrow.filled_amount += 1;
+    /// assert_eq!(escrow.fill_price(1), Some(3));
+    /// escrow.filled_amount += 1;
+    /// assert_eq!(escrow.fill_price(1), Some(3));
+    /// ```
+    pub fn fill_price(&self, amount: u64) -> Option<u64> {
+        let paid = self.pro_rata_amount(self.filled_amount)?;
+        let total = self.pro_rata_amount(self.filled_amount.checked_add(amount)?)?;
+        total.checked_sub(paid)
+    }
+
+    /// Amount of tokens expected for `filled_amount` of the deposited tokens, rounded up.
+    fn pro_rata_amount(&self, filled_amount: u64) -> Option<u64> {
+        if self.offered_amount == 0 {
+            return None;
+        }
+        let amount = (filled_amount as u128)
+            .checked_mul(self.amount as u128)?
+            .div_ceil(self.offered_amount as u128);
+        u64::try_from(amount).ok()
+    }
+
+    /// Restart the escrow with `offered_amount` of deposited tokens, expecting `amount` for all
+    /// of them.
+    ///
+    /// The fills made so far are settled, so [`Escrow::filled_amount`] restarts at zero. Without a
+    /// new amount, the tokens are priced at the rate of the tokens remaining before the rebase,
+    /// rounded up in favor of the seller. Returns `None` on overflow or if nothing remained.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use escrow_program::state::Escrow;
+    /// #
+    /// let mut escrow = Escrow {
+    ///     amount: 10,
+    ///     offered_amount: 3,
+    ///     filled_amount: 1,
+    ///     ..Escrow::default()
+    /// };
+    ///
+    /// // The remaining 2 tokens are worth 6, so 4 tokens are worth 12
+    /// escrow.rebase(4, None).unwrap();
+    /// assert_eq!((escrow.amount, escrow.offered_amount, escrow.filled_amount), (12, 4, 0));
+    ///
+    /// escrow.rebase(4, Some(20)).unwrap();
+    /// assert_eq!((escrow.amount, escrow.offered_amount, escrow.filled_amount), (20, 4, 0));
+    /// ```
+    pub fn rebase(&mut self, offered_amount: u64, amount: Option<u64>) -> Option<()> {
+        let remaining_amount = self.remaining_amount();
+        if remaining_amount == 0 {
+            return None;
+        }
+        let amount = match amount {
+            Some(amount) => amount,
+            None => {
+                let remaining_price = self.fill_price(remaining_amount)?;
+                let amount = (remaining_price as u128 * offered_amount as u128)
+                    .div_ceil(remaining_amount as u128);
+                u64::try_from(amount).ok()?
+            }
+        };
+
+        self.amount = amount;
+        self.offered_amount = offered_amount;
+        self.filled_amount = 0;
+
+        Some(())
+    }
+
+    /// Return whether the escrow has expired at the unix timestamp.
+    pub fn is_expired(&self, unix_timestamp: i64) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| unix_timestamp >= expires_at)
+    }
 }
 
 impl IsInitialized for Escrow {
@@ -43,3 +239,70 @@ impl IsInitialized for Escrow {
         self.is_initialized
     }
 }
+
+/// Escrow account state in the first deployed layout (version 1).
+///
+/// The account holds the borsh serialized state alone, starting with `is_initialized`. The escrow
+/// account was created by the seller at any address, and the vault of every escrow was owned by the
+/// single PDA of [`find_legacy_authority_address`](crate::pda::find_legacy_authority_address).
+/// The layout records neither the deposited amount, which is the balance of the vault, nor a seed.
+#[derive(Default, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct EscrowV1 {
+    /// If true, state has been initialized
+    pub is_initialized: bool,
+
+    /// Seller's public key
+    pub seller_pubkey: Pubkey,
+
+    /// Token account to be received by the seller at the conclusion of the transaction
+    pub seller_token_account_pubkey: Pubkey,
+
+    /// Token account (vault) holding the tokens deposited in escrow by the seller
+    pub temp_token_account_pubkey: Pubkey,
+
+    /// Amount of tokens expected by the seller for all of the deposited tokens
+    pub amount: u64,
+}
+
+impl EscrowV1 {
+    /// Size of the escrow account.
+    pub const LEN: usize = 1 + 32 + 32 + 32 + 8;
+
+    /// Convert the escrow to the newest layout.
+    ///
+    /// `offered_amount` is the balance of the vault and `bump` the bump seed of the
+    /// [`find_authority_address`](crate::pda::find_authority_address) of the escrow account,
+    /// which owns the vault once migrated. The seed is zero, as the escrow account is not derived
+    /// from the seller.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use escrow_program::state::EscrowV1;
+    /// #
+    /// let escrow = EscrowV1 {
+    ///     is_initialized: true,
+    ///     amount: 50,
+    ///     ..EscrowV1::default()
+    /// }
+    /// .migrate(100, 255);
+    ///
+    /// assert_eq!(escrow.fill_price(100), Some(50));
+    /// assert_eq!(escrow.remaining_amount(), 100);
+    /// ```
+    pub fn migrate(self, offered_amount: u64, bump: u8) -> Escrow {
+        Escrow {
+            is_initialized: self.is_initialized,
+            seller_pubkey: self.seller_pubkey,
+            seller_token_account_pubkey: self.seller_token_account_pubkey,
+            temp_token_account_pubkey: self.temp_token_account_pubkey,
+            amount: self.amount,
+            offered_amount,
+            filled_amount: 0,
+            bump,
+            seed: 0,
+            expires_at: None,
+            taker: None,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+
+/// Create an escrow account in the newest layout, owned by the escrow program.
+#[allow(clippy::too_many_arguments)]
+pub fn escrow_account(
+    seller_pubkey: Pubkey,
+    seller_token_account_pubkey: Pubkey,
+    temp_token_account_pubkey: Pubkey,
+    amount: u64,
+    offered_amount: u64,
+    filled_amount: u64,
+    bump: u8,
+    seed: u64,
+    expires_at: Option<i64>,
+    taker: Option<Pubkey>,
+) -> AccountSharedData {
+    let len = escrow_program::state::Escrow::LEN;
+    let mut account = AccountSharedData::new(
+        Rent::default().minimum_balance(len),
+        len,
+        &escrow_program::id(),
+    );
+    let escrow = escrow_program::state::Escrow {
+        is_initialized: true,
+        seller_pubkey,
+        seller_token_account_pubkey,
+        temp_token_account_pubkey,
+        amount,
+        offered_amount,
+        filled_amount,
+        bump,
+        seed,
+        expires_at,
+        taker,
+    };
+    let mut data = vec![0; len];
+    escrow.pack(&mut data).unwrap();
+    account.set_data_from_slice(&data);
+
+    account
+}
+
+/// Create an escrow account in the first deployed layout, whose vault is owned by the legacy
+/// authority of [`find_legacy_authority_address`](escrow_program::pda::find_legacy_authority_address).
+pub fn escrow_v1_account(
+    seller_pubkey: Pubkey,
+    seller_token_account_pubkey: Pubkey,
+    temp_token_account_pubkey: Pubkey,
+    amount: u64,
+) -> AccountSharedData {
+    let len = escrow_program::state::EscrowV1::LEN;
+    let mut account = AccountSharedData::new(
+        Rent::default().minimum_balance(len),
+        len,
+        &escrow_program::id(),
+    );
+    let escrow = escrow_program::state::EscrowV1 {
+        is_initialized: true,
+        seller_pubkey,
+        seller_token_account_pubkey,
+        temp_token_account_pubkey,
+        amount,
+    };
+    account.set_data_from_slice(&borsh::to_vec(&escrow).unwrap());
+
+    account
+}
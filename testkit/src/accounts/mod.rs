@@ -0,0 +1,7 @@
+mod escrow;
+mod token;
+
+pub use escrow::{escrow_account, escrow_v1_account};
+pub use token::{
+    associated_token_account, mint_2022_account, mint_account, token_2022_account, token_account,
+};
@@ -0,0 +1,178 @@
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::program_option::COption;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use spl_token::state::AccountState;
+use spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeAmount, TransferFeeConfig};
+use spl_token_2022::extension::{
+    BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+};
+
+/// Create a mint of the SPL Token program.
+pub fn mint_account(
+    mint_authority: Option<Pubkey>,
+    supply: u64,
+    decimals: u8,
+    freeze_authority: Option<Pubkey>,
+) -> AccountSharedData {
+    let mut account = AccountSharedData::new(
+        Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+        spl_token::state::Mint::LEN,
+        &spl_token::id(),
+    );
+    let mint = spl_token::state::Mint {
+        mint_authority: COption::from(mint_authority),
+        supply,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::from(freeze_authority),
+    };
+    let data = &mut [0; spl_token::state::Mint::LEN];
+    mint.pack_into_slice(data);
+    account.set_data_from_slice(data);
+
+    account
+}
+
+/// Create a token account of the SPL Token program.
+#[allow(clippy::too_many_arguments)]
+pub fn associated_token_account(
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    delegate: Option<Pubkey>,
+    state: AccountState,
+    is_native: Option<u64>,
+    delegated_amount: u64,
+    close_authority: Option<Pubkey>,
+) -> AccountSharedData {
+    let mut account = AccountSharedData::new(
+        Rent::default().minimum_balance(spl_token::state::Account::LEN),
+        spl_token::state::Account::LEN,
+        &spl_token::id(),
+    );
+    let token_account = spl_token::state::Account {
+        mint,
+        owner,
+        amount,
+        delegate: COption::from(delegate),
+        state,
+        is_native: COption::from(is_native),
+        delegated_amount,
+        close_authority: COption::from(close_authority),
+    };
+    let data = &mut [0; spl_token::state::Account::LEN];
+    token_account.pack_into_slice(data);
+    account.set_data_from_slice(data);
+
+    account
+}
+
+/// Create an initialized token account of the SPL Token program holding the amount, with neither
+/// a delegate nor a close authority.
+pub fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> AccountSharedData {
+    associated_token_account(
+        mint,
+        owner,
+        amount,
+        None,
+        AccountState::Initialized,
+        None,
+        0,
+        None,
+    )
+}
+
+/// Create a mint of the Token-2022 program, charging the transfer fee of
+/// `(basis points, maximum fee)` if given.
+pub fn mint_2022_account(
+    mint_authority: Option<Pubkey>,
+    supply: u64,
+    decimals: u8,
+    freeze_authority: Option<Pubkey>,
+    transfer_fee: Option<(u16, u64)>,
+) -> AccountSharedData {
+    let extension_types = transfer_fee
+        .map(|_| vec![ExtensionType::TransferFeeConfig])
+        .unwrap_or_default();
+    let len =
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&extension_types)
+            .expect("Failed to calculate mint length");
+    let mut account = AccountSharedData::new(
+        Rent::default().minimum_balance(len),
+        len,
+        &spl_token_2022::id(),
+    );
+    let mut data = vec![0; len];
+    let mut state =
+        StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)
+            .unwrap();
+    if let Some((transfer_fee_basis_points, maximum_fee)) = transfer_fee {
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: transfer_fee_basis_points.into(),
+        };
+        let extension = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        extension.older_transfer_fee = transfer_fee;
+        extension.newer_transfer_fee = transfer_fee;
+    }
+    state.base = spl_token_2022::state::Mint {
+        mint_authority: COption::from(mint_authority),
+        supply,
+        decimals,
+        is_initialized: true,
+        freeze_authority: COption::from(freeze_authority),
+    };
+    state.pack_base();
+    state.init_account_type().unwrap();
+    account.set_data_from_slice(&data);
+
+    account
+}
+
+/// Create a token account of the Token-2022 program, holding the withheld
+/// transfer fees if given.
+pub fn token_2022_account(
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    withheld_amount: Option<u64>,
+) -> AccountSharedData {
+    let extension_types = withheld_amount
+        .map(|_| vec![ExtensionType::TransferFeeAmount])
+        .unwrap_or_default();
+    let len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(
+        &extension_types,
+    )
+    .expect("Failed to calculate account length");
+    let mut account = AccountSharedData::new(
+        Rent::default().minimum_balance(len),
+        len,
+        &spl_token_2022::id(),
+    );
+    let mut data = vec![0; len];
+    let mut state =
+        StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack_uninitialized(&mut data)
+            .unwrap();
+    if let Some(withheld_amount) = withheld_amount {
+        let extension = state.init_extension::<TransferFeeAmount>(true).unwrap();
+        extension.withheld_amount = withheld_amount.into();
+    }
+    state.base = spl_token_2022::state::Account {
+        mint,
+        owner,
+        amount,
+        delegate: COption::None,
+        state: spl_token_2022::state::AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    };
+    state.pack_base();
+    state.init_account_type().unwrap();
+    account.set_data_from_slice(&data);
+
+    account
+}
@@ -0,0 +1,91 @@
+use escrow_program::state::Escrow;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::StateWithExtensions;
+
+/// Expected state of an escrow account, checking only the fields that are set.
+#[derive(Debug, Default)]
+pub struct ExpectedEscrow {
+    pub seller_pubkey: Option<Pubkey>,
+    pub amount: Option<u64>,
+    pub offered_amount: Option<u64>,
+    pub filled_amount: Option<u64>,
+    /// Amount of the deposited tokens that have not been taken yet.
+    pub remaining_amount: Option<u64>,
+    pub expires_at: Option<Option<i64>>,
+    pub taker: Option<Option<Pubkey>>,
+}
+
+/// Get the amount of tokens held by a token account of the SPL Token or Token-2022 program.
+///
+/// Panics if the account is not a token account.
+#[track_caller]
+pub fn token_amount(account: &impl ReadableAccount) -> u64 {
+    assert!(
+        *account.owner() == spl_token::id() || *account.owner() == spl_token_2022::id(),
+        "account owned by {} is not a token account",
+        account.owner()
+    );
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(account.data())
+        .expect("Failed to unpack token account")
+        .base
+        .amount
+}
+
+/// Assert that the token account holds the amount of tokens.
+#[track_caller]
+pub fn assert_token_balance(account: &impl ReadableAccount, amount: u64) {
+    assert_eq!(token_amount(account), amount, "unexpected token balance");
+}
+
+/// Assert that the account was closed, i.e. it does not exist or holds no lamports.
+#[track_caller]
+pub fn assert_closed<T: ReadableAccount>(account: Option<&T>) {
+    if let Some(account) = account {
+        assert_eq!(account.lamports(), 0, "account was not closed");
+    }
+}
+
+/// Assert that the escrow account is in the newest layout and in the expected state.
+#[track_caller]
+pub fn assert_escrow(account: &impl ReadableAccount, expected: &ExpectedEscrow) {
+    assert_eq!(
+        *account.owner(),
+        escrow_program::id(),
+        "escrow account not owned by the escrow program"
+    );
+    let escrow = Escrow::unpack(account.data()).expect("Failed to unpack escrow account");
+
+    assert!(escrow.is_initialized, "escrow is not initialized");
+    if let Some(seller_pubkey) = expected.seller_pubkey {
+        assert_eq!(escrow.seller_pubkey, seller_pubkey, "unexpected seller");
+    }
+    if let Some(amount) = expected.amount {
+        assert_eq!(escrow.amount, amount, "unexpected amount");
+    }
+    if let Some(offered_amount) = expected.offered_amount {
+        assert_eq!(
+            escrow.offered_amount, offered_amount,
+            "unexpected offered amount"
+        );
+    }
+    if let Some(filled_amount) = expected.filled_amount {
+        assert_eq!(
+            escrow.filled_amount, filled_amount,
+            "unexpected filled amount"
+        );
+    }
+    if let Some(remaining_amount) = expected.remaining_amount {
+        assert_eq!(
+            escrow.remaining_amount(),
+            remaining_amount,
+            "unexpected remaining amount"
+        );
+    }
+    if let Some(expires_at) = expected.expires_at {
+        assert_eq!(escrow.expires_at, expires_at, "unexpected expiry");
+    }
+    if let Some(taker) = expected.taker {
+        assert_eq!(escrow.taker, taker, "unexpected taker");
+    }
+}
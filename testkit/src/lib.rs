@@ -0,0 +1,12 @@
+pub mod accounts;
+mod assert;
+mod scenario;
+#[cfg(feature = "validator")]
+mod validator;
+
+pub use crate::assert::{
+    assert_closed, assert_escrow, assert_token_balance, token_amount, ExpectedEscrow,
+};
+pub use crate::scenario::{Scenario, USER_LAMPORTS};
+#[cfg(feature = "validator")]
+pub use crate::validator::{TestValidator, Validator};
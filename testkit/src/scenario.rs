@@ -0,0 +1,361 @@
+use crate::accounts;
+use escrow_program::pda::{find_authority_address, find_escrow_address, find_vault_address};
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Lamports held by each user, to pay for transactions and rent.
+pub const USER_LAMPORTS: u64 = 10 * LAMPORTS_PER_SOL;
+
+/// A mint of a scenario.
+struct ScenarioMint {
+    pubkey: Pubkey,
+    decimals: u8,
+    token_program_id: Pubkey,
+    /// Transfer fee of a Token-2022 mint, as `(basis points, maximum fee)`.
+    transfer_fee: Option<(u16, u64)>,
+}
+
+/// An open escrow of a scenario.
+struct ScenarioEscrow {
+    seller: String,
+    send_mint: String,
+    receive_mint: String,
+    offered_amount: u64,
+    amount: u64,
+    seed: u64,
+    expires_at: Option<i64>,
+    taker: Option<String>,
+}
+
+/// Mints, users, token balances and open escrows, named and built up one at a time, whose
+/// accounts are loaded into a test validator or a `ProgramTest`.
+///
+/// The token accounts are the associated token accounts of the users. Each mint has the supply of
+/// all of the tokens funded and deposited in escrows. Methods given a name that was not added
+/// panic, as the scenario is a fixture of a test.
+///
+/// # Example
+///
+/// ```rust
+/// # use escrow_testkit::Scenario;
+/// #
+/// let scenario = Scenario::new()
+///     .mint("USDC", 6)
+///     .mint_2022("PYUSD", 6, Some((100, 1_000)))
+///     .user("alice")
+///     .user("bob")
+///     .fund("alice", "USDC", 1000)
+///     .fund("bob", "PYUSD", 500)
+///     .escrow("offer", "alice", "USDC", 100, "PYUSD", 50)
+///     .taker("offer", "bob");
+/// let accounts = scenario.accounts();
+///
+/// assert_eq!(scenario.balance("alice", "USDC"), 1000);
+/// assert!(accounts
+///     .iter()
+///     .any(|(pubkey, _)| *pubkey == scenario.escrow_pubkey("offer")));
+/// assert!(accounts
+///     .iter()
+///     .any(|(pubkey, _)| *pubkey == scenario.token_account_pubkey("alice", "PYUSD")));
+/// ```
+#[derive(Default)]
+pub struct Scenario {
+    mints: BTreeMap<String, ScenarioMint>,
+    users: BTreeMap<String, Keypair>,
+    balances: BTreeMap<(String, String), u64>,
+    escrows: BTreeMap<String, ScenarioEscrow>,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a mint of the SPL Token program.
+    pub fn mint(mut self, name: &str, decimals: u8) -> Self {
+        self.mints.insert(
+            name.to_string(),
+            ScenarioMint {
+                pubkey: Pubkey::new_unique(),
+                decimals,
+                token_program_id: spl_token::id(),
+                transfer_fee: None,
+            },
+        );
+        self
+    }
+
+    /// Add a mint of the Token-2022 program, charging the transfer fee of
+    /// `(basis points, maximum fee)` if given.
+    pub fn mint_2022(mut self, name: &str, decimals: u8, transfer_fee: Option<(u16, u64)>) -> Self {
+        self.mints.insert(
+            name.to_string(),
+            ScenarioMint {
+                pubkey: Pubkey::new_unique(),
+                decimals,
+                token_program_id: spl_token_2022::id(),
+                transfer_fee,
+            },
+        );
+        self
+    }
+
+    /// Add a user holding [`USER_LAMPORTS`].
+    pub fn user(mut self, name: &str) -> Self {
+        self.users.insert(name.to_string(), Keypair::new());
+        self
+    }
+
+    /// Add tokens of the mint to the associated token account of the user.
+    pub fn fund(mut self, user: &str, mint: &str, amount: u64) -> Self {
+        self.mint_by_name(mint);
+        self.keypair(user);
+        let balance = self
+            .balances
+            .entry((user.to_string(), mint.to_string()))
+            .or_default();
+        *balance = balance
+            .checked_add(amount)
+            .unwrap_or_else(|| panic!("balance of {user} in {mint} overflows"));
+        self
+    }
+
+    /// Add an escrow of the seller, whose vault holds `offered_amount` tokens of `send_mint`
+    /// against `amount` tokens of `receive_mint`.
+    ///
+    /// The escrow is given the smallest seed not used by another escrow of the seller, unless set
+    /// with [`Scenario::seed`]. The deposited tokens are held by the vault in addition to the
+    /// balances funded to the seller.
+    pub fn escrow(
+        mut self,
+        name: &str,
+        seller: &str,
+        send_mint: &str,
+        offered_amount: u64,
+        receive_mint: &str,
+        amount: u64,
+    ) -> Self {
+        self.mint_by_name(send_mint);
+        self.mint_by_name(receive_mint);
+        self.keypair(seller);
+        let seeds = self.seeds(seller, name);
+        let seed = (0..).find(|seed| !seeds.contains(seed)).unwrap();
+        // The seller receives the tokens in their associated token account
+        self.balances
+            .entry((seller.to_string(), receive_mint.to_string()))
+            .or_default();
+        self.escrows.insert(
+            name.to_string(),
+            ScenarioEscrow {
+                seller: seller.to_string(),
+                send_mint: send_mint.to_string(),
+                receive_mint: receive_mint.to_string(),
+                offered_amount,
+                amount,
+                seed,
+                expires_at: None,
+                taker: None,
+            },
+        );
+        self
+    }
+
+    /// Set the seed of the escrow, instead of the next seed of the seller, such as to leave the
+    /// seeds in between to the escrows opened by a test.
+    ///
+    /// Panics if another escrow of the seller has the seed, as both would be the same account.
+    pub fn seed(mut self, escrow: &str, seed: u64) -> Self {
+        let seller = self.escrow_by_name(escrow).seller.clone();
+        if self.seeds(&seller, escrow).contains(&seed) {
+            panic!("seed {seed} of {escrow} is already used by another escrow of {seller}");
+        }
+        self.escrow_by_name_mut(escrow).seed = seed;
+        self
+    }
+
+    /// Set the unix timestamp after which the escrow expires.
+    pub fn expires_at(mut self, escrow: &str, expires_at: i64) -> Self {
+        self.escrow_by_name_mut(escrow).expires_at = Some(expires_at);
+        self
+    }
+
+    /// Restrict the escrow to the user as its only buyer.
+    pub fn taker(mut self, escrow: &str, user: &str) -> Self {
+        self.keypair(user);
+        self.escrow_by_name_mut(escrow).taker = Some(user.to_string());
+        self
+    }
+
+    /// Get the keypair of the user.
+    pub fn keypair(&self, user: &str) -> &Keypair {
+        self.users
+            .get(user)
+            .unwrap_or_else(|| panic!("unknown user {user}"))
+    }
+
+    /// Get the public key of the user.
+    pub fn pubkey(&self, user: &str) -> Pubkey {
+        self.keypair(user).pubkey()
+    }
+
+    /// Get the public key of the mint.
+    pub fn mint_pubkey(&self, mint: &str) -> Pubkey {
+        self.mint_by_name(mint).pubkey
+    }
+
+    /// Get the token program of the mint.
+    pub fn token_program_id(&self, mint: &str) -> Pubkey {
+        self.mint_by_name(mint).token_program_id
+    }
+
+    /// Get the associated token account of the user for the mint.
+    pub fn token_account_pubkey(&self, user: &str, mint: &str) -> Pubkey {
+        let mint = self.mint_by_name(mint);
+        spl_associated_token_account::get_associated_token_address_with_program_id(
+            &self.pubkey(user),
+            &mint.pubkey,
+            &mint.token_program_id,
+        )
+    }
+
+    /// Get the amount of tokens of the mint funded to the user.
+    pub fn balance(&self, user: &str, mint: &str) -> u64 {
+        self.balances
+            .get(&(user.to_string(), mint.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Get the public key of the escrow account.
+    pub fn escrow_pubkey(&self, escrow: &str) -> Pubkey {
+        let escrow = self.escrow_by_name(escrow);
+        let (escrow_account_pubkey, _) = find_escrow_address(
+            &self.pubkey(&escrow.seller),
+            escrow.seed,
+            &escrow_program::id(),
+        );
+
+        escrow_account_pubkey
+    }
+
+    /// Get the public key of the vault holding the tokens deposited in the escrow.
+    pub fn vault_pubkey(&self, escrow: &str) -> Pubkey {
+        let (vault_pubkey, _) =
+            find_vault_address(&self.escrow_pubkey(escrow), &escrow_program::id());
+
+        vault_pubkey
+    }
+
+    /// Get the public key of the authority of the vault of the escrow.
+    pub fn authority_pubkey(&self, escrow: &str) -> Pubkey {
+        let (authority_pubkey, _) =
+            find_authority_address(&self.escrow_pubkey(escrow), &escrow_program::id());
+
+        authority_pubkey
+    }
+
+    /// Create the accounts of the scenario, to load into the cluster.
+    pub fn accounts(&self) -> Vec<(Pubkey, AccountSharedData)> {
+        let mut supplies = BTreeMap::<&str, u64>::new();
+        for ((_, mint), amount) in &self.balances {
+            let supply = supplies.entry(mint).or_default();
+            *supply = supply.saturating_add(*amount);
+        }
+        for escrow in self.escrows.values() {
+            let supply = supplies.entry(&escrow.send_mint).or_default();
+            *supply = supply.saturating_add(escrow.offered_amount);
+        }
+
+        let mut accounts = Vec::new();
+        for keypair in self.users.values() {
+            accounts.push((
+                keypair.pubkey(),
+                AccountSharedData::new(USER_LAMPORTS, 0, &system_program::id()),
+            ));
+        }
+        for (name, mint) in &self.mints {
+            let supply = supplies.get(name.as_str()).copied().unwrap_or_default();
+            let account = if mint.token_program_id == spl_token_2022::id() {
+                accounts::mint_2022_account(None, supply, mint.decimals, None, mint.transfer_fee)
+            } else {
+                accounts::mint_account(None, supply, mint.decimals, None)
+            };
+            accounts.push((mint.pubkey, account));
+        }
+        for ((user, mint), amount) in &self.balances {
+            accounts.push((
+                self.token_account_pubkey(user, mint),
+                self.token_account(mint, self.pubkey(user), *amount),
+            ));
+        }
+        for (name, escrow) in &self.escrows {
+            let escrow_account_pubkey = self.escrow_pubkey(name);
+            let vault_pubkey = self.vault_pubkey(name);
+            let (authority_pubkey, bump) =
+                find_authority_address(&escrow_account_pubkey, &escrow_program::id());
+            accounts.push((
+                vault_pubkey,
+                self.token_account(&escrow.send_mint, authority_pubkey, escrow.offered_amount),
+            ));
+            accounts.push((
+                escrow_account_pubkey,
+                accounts::escrow_account(
+                    self.pubkey(&escrow.seller),
+                    self.token_account_pubkey(&escrow.seller, &escrow.receive_mint),
+                    vault_pubkey,
+                    escrow.amount,
+                    escrow.offered_amount,
+                    0,
+                    bump,
+                    escrow.seed,
+                    escrow.expires_at,
+                    escrow.taker.as_deref().map(|taker| self.pubkey(taker)),
+                ),
+            ));
+        }
+
+        accounts
+    }
+
+    /// Create a token account of the mint, with the extensions required by the mint, such as a
+    /// vault or the account of a user who is not part of the scenario.
+    pub fn token_account(&self, mint: &str, owner: Pubkey, amount: u64) -> AccountSharedData {
+        let mint = self.mint_by_name(mint);
+        if mint.token_program_id == spl_token_2022::id() {
+            accounts::token_2022_account(mint.pubkey, owner, amount, mint.transfer_fee.map(|_| 0))
+        } else {
+            accounts::token_account(mint.pubkey, owner, amount)
+        }
+    }
+
+    /// Get the seeds of the escrows of the seller, except for the named escrow.
+    fn seeds(&self, seller: &str, except: &str) -> BTreeSet<u64> {
+        self.escrows
+            .iter()
+            .filter(|(name, escrow)| escrow.seller == seller && name.as_str() != except)
+            .map(|(_, escrow)| escrow.seed)
+            .collect()
+    }
+
+    fn mint_by_name(&self, mint: &str) -> &ScenarioMint {
+        self.mints
+            .get(mint)
+            .unwrap_or_else(|| panic!("unknown mint {mint}"))
+    }
+
+    fn escrow_by_name(&self, escrow: &str) -> &ScenarioEscrow {
+        self.escrows
+            .get(escrow)
+            .unwrap_or_else(|| panic!("unknown escrow {escrow}"))
+    }
+
+    fn escrow_by_name_mut(&mut self, escrow: &str) -> &mut ScenarioEscrow {
+        self.escrows
+            .get_mut(escrow)
+            .unwrap_or_else(|| panic!("unknown escrow {escrow}"))
+    }
+}
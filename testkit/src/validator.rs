@@ -3,38 +3,28 @@ use solana_sdk::account::AccountSharedData;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{EncodableKey, Keypair, Signer};
 use solana_sdk::{bpf_loader_upgradeable, system_program};
-use solana_test_validator::{TestValidator, TestValidatorGenesis, UpgradeableProgramInfo};
+pub use solana_test_validator::TestValidator;
+use solana_test_validator::{TestValidatorGenesis, UpgradeableProgramInfo};
 use std::path::PathBuf;
 use std::{env, fs};
 use uuid::Uuid;
 
 /// A struct to configure the validator for testing.
+#[derive(Default)]
 pub struct Validator {
     ledger_path: Option<PathBuf>,
     program_dir: Option<PathBuf>,
     accounts: Vec<(Pubkey, AccountSharedData)>,
 }
 
-impl Default for Validator {
-    fn default() -> Self {
-        Self {
-            ledger_path: None,
-            program_dir: None,
-            accounts: vec![],
-        }
-    }
-}
-
 impl Validator {
     /// Set the ledger path for the validator.
-    #[allow(dead_code)]
     pub fn with_ledger_path(mut self, ledger_path: PathBuf) -> Self {
         self.ledger_path = Some(ledger_path);
         self
     }
 
     /// Set the program directory for the validator.
-    #[allow(dead_code)]
     pub fn with_program_dir(mut self, program_dir: PathBuf) -> Self {
         self.program_dir = Some(program_dir);
         self
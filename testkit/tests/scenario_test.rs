@@ -0,0 +1,192 @@
+use escrow_program::pda::find_escrow_address;
+use escrow_testkit::{
+    assert_closed, assert_escrow, assert_token_balance, token_amount, ExpectedEscrow, Scenario,
+    USER_LAMPORTS,
+};
+use solana_sdk::account::{AccountSharedData, ReadableAccount};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::transfer_fee::{TransferFeeAmount, TransferFeeConfig};
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use std::collections::HashMap;
+
+fn scenario() -> Scenario {
+    Scenario::new()
+        .mint("USDC", 6)
+        .mint_2022("PYUSD", 6, Some((100, 1_000)))
+        .user("alice")
+        .user("bob")
+        .fund("alice", "USDC", 1000)
+        .fund("alice", "USDC", 500)
+        .fund("bob", "PYUSD", 300)
+        .escrow("offer", "alice", "USDC", 100, "PYUSD", 50)
+        .escrow("expiring", "alice", "USDC", 10, "PYUSD", 5)
+        .expires_at("expiring", 1_700_000_000)
+        .taker("expiring", "bob")
+}
+
+#[test]
+fn test_users() {
+    let scenario = scenario();
+    let accounts = scenario.accounts().into_iter().collect::<HashMap<_, _>>();
+
+    let alice = &accounts[&scenario.pubkey("alice")];
+    assert_eq!(alice.lamports(), USER_LAMPORTS);
+    assert_eq!(*alice.owner(), solana_sdk::system_program::id());
+    assert_ne!(scenario.pubkey("alice"), scenario.pubkey("bob"));
+}
+
+#[test]
+fn test_token_accounts() {
+    let scenario = scenario();
+    let accounts = scenario.accounts().into_iter().collect::<HashMap<_, _>>();
+
+    // Funding twice adds up
+    assert_eq!(scenario.balance("alice", "USDC"), 1500);
+    let alice_usdc = &accounts[&scenario.token_account_pubkey("alice", "USDC")];
+    assert_eq!(*alice_usdc.owner(), spl_token::id());
+    assert_token_balance(alice_usdc, 1500);
+    let alice_usdc_data = spl_token::state::Account::unpack(alice_usdc.data()).unwrap();
+    assert_eq!(alice_usdc_data.owner, scenario.pubkey("alice"));
+    assert_eq!(alice_usdc_data.mint, scenario.mint_pubkey("USDC"));
+
+    // Token accounts of a mint charging a transfer fee can hold withheld fees
+    let bob_pyusd = &accounts[&scenario.token_account_pubkey("bob", "PYUSD")];
+    assert_eq!(*bob_pyusd.owner(), spl_token_2022::id());
+    assert_eq!(token_amount(bob_pyusd), 300);
+    let bob_pyusd_data =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(bob_pyusd.data()).unwrap();
+    assert!(bob_pyusd_data.get_extension::<TransferFeeAmount>().is_ok());
+
+    // The seller of an escrow holds the token account receiving the tokens
+    let alice_pyusd = &accounts[&scenario.token_account_pubkey("alice", "PYUSD")];
+    assert_token_balance(alice_pyusd, 0);
+}
+
+#[test]
+fn test_mints() {
+    let scenario = scenario();
+    let accounts = scenario.accounts().into_iter().collect::<HashMap<_, _>>();
+
+    // The supply includes the tokens deposited in escrows
+    let usdc = &accounts[&scenario.mint_pubkey("USDC")];
+    assert_eq!(scenario.token_program_id("USDC"), spl_token::id());
+    let usdc_data = spl_token::state::Mint::unpack(usdc.data()).unwrap();
+    assert_eq!(usdc_data.supply, 1610);
+    assert_eq!(usdc_data.decimals, 6);
+
+    let pyusd = &accounts[&scenario.mint_pubkey("PYUSD")];
+    assert_eq!(scenario.token_program_id("PYUSD"), spl_token_2022::id());
+    let pyusd_data =
+        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(pyusd.data()).unwrap();
+    assert_eq!(pyusd_data.base.supply, 300);
+    let transfer_fee_config = pyusd_data.get_extension::<TransferFeeConfig>().unwrap();
+    assert_eq!(
+        u16::from(
+            transfer_fee_config
+                .newer_transfer_fee
+                .transfer_fee_basis_points
+        ),
+        100
+    );
+    assert_eq!(
+        u64::from(transfer_fee_config.newer_transfer_fee.maximum_fee),
+        1_000
+    );
+}
+
+#[test]
+fn test_escrows() {
+    let scenario = scenario();
+    let accounts = scenario.accounts().into_iter().collect::<HashMap<_, _>>();
+
+    // The escrows of a seller are given seeds in the order they are added
+    let (offer_pubkey, _) =
+        find_escrow_address(&scenario.pubkey("alice"), 0, &escrow_program::id());
+    assert_eq!(scenario.escrow_pubkey("offer"), offer_pubkey);
+    let (expiring_pubkey, _) =
+        find_escrow_address(&scenario.pubkey("alice"), 1, &escrow_program::id());
+    assert_eq!(scenario.escrow_pubkey("expiring"), expiring_pubkey);
+
+    assert_escrow(
+        &accounts[&offer_pubkey],
+        &ExpectedEscrow {
+            seller_pubkey: Some(scenario.pubkey("alice")),
+            amount: Some(50),
+            offered_amount: Some(100),
+            filled_amount: Some(0),
+            remaining_amount: Some(100),
+            expires_at: Some(None),
+            taker: Some(None),
+        },
+    );
+    assert_escrow(
+        &accounts[&expiring_pubkey],
+        &ExpectedEscrow {
+            expires_at: Some(Some(1_700_000_000)),
+            taker: Some(Some(scenario.pubkey("bob"))),
+            ..ExpectedEscrow::default()
+        },
+    );
+
+    let vault = &accounts[&scenario.vault_pubkey("offer")];
+    assert_token_balance(vault, 100);
+    let vault_data = spl_token::state::Account::unpack(vault.data()).unwrap();
+    assert_eq!(vault_data.owner, scenario.authority_pubkey("offer"));
+}
+
+#[test]
+fn test_escrow_seeds() {
+    let scenario = Scenario::new()
+        .mint("USDC", 6)
+        .mint("PYUSD", 6)
+        .user("alice")
+        .escrow("first", "alice", "USDC", 10, "PYUSD", 5)
+        .seed("first", 1)
+        .escrow("second", "alice", "USDC", 10, "PYUSD", 5)
+        .escrow("third", "alice", "USDC", 10, "PYUSD", 5);
+
+    // Escrows added after a seed is set take the seeds left free
+    let seed_of = |escrow| {
+        (0..3)
+            .find(|seed| {
+                find_escrow_address(&scenario.pubkey("alice"), *seed, &escrow_program::id()).0
+                    == scenario.escrow_pubkey(escrow)
+            })
+            .unwrap()
+    };
+    assert_eq!(seed_of("first"), 1);
+    assert_eq!(seed_of("second"), 0);
+    assert_eq!(seed_of("third"), 2);
+    let accounts = scenario.accounts().into_iter().collect::<HashMap<_, _>>();
+    for escrow in ["first", "second", "third"] {
+        assert!(accounts.contains_key(&scenario.escrow_pubkey(escrow)));
+    }
+}
+
+#[test]
+#[should_panic(expected = "seed 0 of expiring is already used by another escrow of alice")]
+fn test_duplicate_seed() {
+    let _ = scenario().seed("expiring", 0);
+}
+
+#[test]
+#[should_panic(expected = "unexpected token balance")]
+fn test_assert_token_balance_mismatch() {
+    let scenario = scenario();
+    let accounts = scenario.accounts().into_iter().collect::<HashMap<_, _>>();
+
+    assert_token_balance(&accounts[&scenario.token_account_pubkey("bob", "PYUSD")], 1);
+}
+
+#[test]
+fn test_assert_closed() {
+    assert_closed::<AccountSharedData>(None);
+    assert_closed(Some(&AccountSharedData::new(0, 0, &Pubkey::default())));
+}
+
+#[test]
+#[should_panic(expected = "unknown user carol")]
+fn test_unknown_user() {
+    let _ = scenario().fund("carol", "USDC", 1);
+}